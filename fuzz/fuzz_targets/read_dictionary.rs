@@ -0,0 +1,38 @@
+#![no_main]
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use sudachiclone::dictionary_lib::binary_dictionary::BinaryDictionary;
+use sudachiclone::dictionary_lib::lexicon::{Lexicon, Size};
+
+const SYSTEM_DIC: &[u8] = include_bytes!("../../src/resources/test/system.dic");
+
+fuzz_target!(|data: &[u8]| {
+  let dictionary = match BinaryDictionary::read_dictionary_from_reader(&mut Cursor::new(data)) {
+    Ok(dictionary) => dictionary,
+    Err(_) => return,
+  };
+  if dictionary.is_user_dictionary() {
+    // the system dictionary the seeds of user dictionaries are built on
+    let system = BinaryDictionary::from_system_dictionary_bytes(SYSTEM_DIC).unwrap();
+    let pos_size = system.grammar.get_part_of_speech_size();
+    if dictionary
+      .validate_user_dictionary(None, &system.grammar, pos_size, system.lexicon.size())
+      .is_err()
+    {
+      return;
+    }
+  }
+  let lexicon = &dictionary.lexicon;
+  for (headword, _) in lexicon.get_headwords() {
+    for (word_id, _) in lexicon.lookup(headword.as_bytes(), 0) {
+      lexicon.get_left_id(word_id);
+      lexicon.get_right_id(word_id);
+      lexicon.get_cost(word_id);
+    }
+    lexicon.exact_lookup(headword.as_bytes());
+  }
+  for word_id in 0..lexicon.size() {
+    lexicon.get_word_info(word_id);
+  }
+});
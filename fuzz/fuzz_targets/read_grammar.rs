@@ -0,0 +1,18 @@
+#![no_main]
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use sudachiclone::dictionary_lib::grammar::Grammar;
+
+fuzz_target!(|data: &[u8]| {
+  if let Ok(grammar) = Grammar::from_reader(&mut Cursor::new(data)) {
+    for pos_id in 0..grammar.get_part_of_speech_size() {
+      grammar.get_part_of_speech_string(pos_id);
+    }
+    for left_id in 0..grammar.get_left_id_size() {
+      for right_id in 0..grammar.get_right_id_size() {
+        grammar.get_connect_cost(left_id, right_id);
+      }
+    }
+  }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use sudachiclone::dictionary_lib::dictionary_header::DictionaryHeader;
+
+fuzz_target!(|data: &[u8]| {
+  if let Ok(header) = DictionaryHeader::from_reader(&mut Cursor::new(data)) {
+    header.version_name();
+    header.create_time_string();
+  }
+});
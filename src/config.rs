@@ -1,7 +1,11 @@
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::env::{current_dir, current_exe};
 use std::ffi::OsStr;
 use std::fs::{symlink_metadata, File};
-use std::io::{BufReader, Error as IOError, ErrorKind as IOErrorKind, Read, Write};
+use std::io::{
+  BufRead, BufReader, Cursor, Error as IOError, ErrorKind as IOErrorKind, Read, Write,
+};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::str::FromStr;
@@ -30,7 +34,17 @@ fn symlink_dir<P: AsRef<Path>, Q: AsRef<Path>>(_src: P, _dst: Q) -> Result<(), I
   Err(IOError::new(IOErrorKind::Other, "can't call symlink_dir"))
 }
 
+use super::dictionary_lib::dictionary_header::DictionaryHeader;
+use super::dictionary_lib::grammar::{GetPartOfSpeech, Grammar};
+use super::plugin::oov_provider_plugin::OovProviderPluginGetErr;
+use super::plugin::simple_oov_plugin::SimpleOovPluginSetupErr;
 use super::resources;
+use dictionary_locator::DictionaryLocator;
+use settings::{merge_settings, resolve_paths, OovProviderPluginSettings, Settings, SettingsErr};
+
+pub mod dictionary_locator;
+pub mod dictionary_store;
+pub mod settings;
 
 #[derive(Error, Debug)]
 pub enum ConfigErr {
@@ -44,105 +58,315 @@ pub enum ConfigErr {
   FromUtf8Error(#[from] FromUtf8Error),
   #[error("`characterDefinitionFile` not defined in setting file")]
   CharDefiFileNotFoundError,
+  #[error("{0}")]
+  SettingsErr(#[from] SettingsErr),
 }
 
 #[derive(Debug)]
 #[allow(non_snake_case)]
 pub struct Config {
-  pub settings: Value,
+  pub settings: Settings,
   pub DEFAULT_RESOURCEDIR: PathBuf,
   pub DEFAULT_SETTINGFILE: PathBuf,
   pub resource_dir: PathBuf,
+  /// Resources kept in memory, keyed by file name. They take precedence over `resource_dir`.
+  pub resources: HashMap<String, Vec<u8>>,
 }
 
 impl Config {
   pub fn empty() -> Result<Config, ConfigErr> {
-    let here = String::from_utf8(Command::new("which").arg("sudachiclone").output()?.stdout)?;
     let dir = ok_or_io_err(
-      PathBuf::from_str(&here)?
-        .parent()
-        .map(|p| p.to_path_buf())
-        .or_else(|| match PathBuf::from_str(file!()) {
-          Ok(p) => p.parent().map(|p| p.to_path_buf()),
-          Err(_) => None,
-        }),
+      current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        .or_else(|| PathBuf::from(file!()).parent().map(|p| p.to_path_buf())),
       "NotFoundParentDir",
     )?;
     Ok(Config {
-      settings: Value::Null,
+      settings: Settings::default(),
       DEFAULT_RESOURCEDIR: dir.join("resources"),
       DEFAULT_SETTINGFILE: dir.join("resources/sudachi.json"),
       resource_dir: dir.join("resources"),
+      resources: HashMap::new(),
     })
   }
 
+  /// Create a config whose resources are served from memory only.
+  ///
+  /// The filesystem is never used; a resource missing from `resources` falls back to the
+  /// bundled one of the same name.
+  pub fn from_memory(settings: Settings, resources: HashMap<String, Vec<u8>>) -> Config {
+    Config {
+      settings,
+      DEFAULT_RESOURCEDIR: PathBuf::new(),
+      DEFAULT_SETTINGFILE: PathBuf::new(),
+      resource_dir: PathBuf::new(),
+      resources,
+    }
+  }
+
+  pub fn builder() -> ConfigBuilder {
+    ConfigBuilder::default()
+  }
+
   pub fn setup(path: Option<&str>, resource_dir: Option<&str>) -> Result<Config, ConfigErr> {
-    let mut config = Config::empty()?;
-    let default_setting_file = &config.DEFAULT_SETTINGFILE;
-    if path.is_none() {
-      resources::write_sudachi_json(&default_setting_file)?;
+    let mut builder = Config::builder();
+    if let Some(path) = path {
+      builder = builder.settings_file(path);
     }
-    let path = ok_or_io_err(
-      path.or_else(|| default_setting_file.to_str()),
-      "InvalidDefaultSettingFilePath",
-    )?;
-    let resource_dir = if let Some(d) = resource_dir {
-      PathBuf::from_str(d)?
-    } else {
-      let resource_dir =
-        ok_or_io_err(Path::new(path).parent(), "InvalidDefaultSettingDirPath")?.to_path_buf();
-      resources::write_resources(&resource_dir)?;
-      resource_dir
-    };
-    let mut buf = String::new();
-    BufReader::new(&mut File::open(path)?).read_to_string(&mut buf)?;
-    let settings = serde_json::from_str(&buf)?;
-    config.resource_dir = resource_dir;
-    config.settings = settings;
-    Ok(config)
+    if let Some(resource_dir) = resource_dir {
+      builder = builder.resource_dir(resource_dir);
+    }
+    builder.build()
   }
 
+  /// Locate the system dictionary
+  ///
+  /// `systemDict` in the settings is tried first, then the locations checked by
+  /// `DictionaryLocator`.
   pub fn system_dict_path(
     &mut self,
     python_exe: Option<&OsStr>,
   ) -> Result<PathBuf, SudachiDictErr> {
-    if let Some(Value::String(p)) = self.settings.get("systemDict") {
-      let path = self.resource_dir.join(p);
-      if path.exists() {
-        return Ok(path);
-      }
-    }
-    let dict_path = get_sudachi_dict_path(python_exe)?;
-    self.settings.as_object_mut().unwrap().insert(
-      String::from("systemDict"),
-      Value::String(dict_path.to_str().unwrap().to_string()),
+    let explicit_path = self
+      .settings
+      .system_dict
+      .as_ref()
+      .map(|p| self.resource_dir.join(p));
+    let (source, dict_path) = DictionaryLocator::default()
+      .explicit_path(explicit_path)
+      .python_exe(python_exe)
+      .locate()?;
+    info!(
+      "Using the system dictionary at {:?} ({})",
+      dict_path, source
     );
+    self.settings.system_dict = Some(dict_path.to_string_lossy().to_string());
     Ok(dict_path)
   }
 
   pub fn char_def_path(&self) -> Result<PathBuf, ConfigErr> {
-    if let Some(Value::String(p)) = self.settings.get("characterDefinitionFile") {
-      let path = self.resource_dir.join(p);
-      if path.exists() {
-        return Ok(path);
-      }
+    let path = self
+      .resource_dir
+      .join(&self.settings.character_definition_file);
+    if path.exists() {
+      return Ok(path);
     }
     Err(ConfigErr::CharDefiFileNotFoundError)
   }
 
+  /// Open the file named by `characterDefinitionFile`
+  pub fn open_char_def(&self) -> Result<Box<dyn BufRead + '_>, ConfigErr> {
+    Ok(self.open_resource(&self.settings.character_definition_file)?)
+  }
+
+  /// Open the resource named `name`
+  ///
+  /// In-memory resources are looked up first, then `resource_dir`, then the bundled resources.
+  /// Only relative names, as in the bundled defaults, fall back to the bundled resources; an
+  /// absolute `name`, as resolved from a setting file, has to exist. An empty `resource_dir` is
+  /// never read.
+  pub fn open_resource(&self, name: &str) -> Result<Box<dyn BufRead + '_>, IOError> {
+    if let Some(bytes) = self.resources.get(name) {
+      return Ok(Box::new(Cursor::new(bytes.as_slice())));
+    }
+    let path = Path::new(name);
+    if !self.resource_dir.as_os_str().is_empty() || path.is_absolute() {
+      let path = self.resource_dir.join(path);
+      if path.is_file() {
+        return Ok(Box::new(BufReader::new(File::open(path)?)));
+      }
+    }
+    let bundled = if path.is_absolute() {
+      None
+    } else {
+      resources::get_bundled_resource(name)
+    };
+    match bundled {
+      Some(text) => Ok(Box::new(Cursor::new(text.as_bytes()))),
+      None => Err(IOError::new(
+        IOErrorKind::NotFound,
+        format!("resource `{}` is not found", name),
+      )),
+    }
+  }
+
   pub fn user_dict_paths(&self) -> Vec<PathBuf> {
-    let mut paths = vec![];
-    if let Some(Value::Array(arr)) = self.settings.get("userDict") {
-      for v in arr {
-        if let Value::String(path) = v {
-          paths.push(self.resource_dir.join(path));
+    self
+      .settings
+      .user_dict
+      .iter()
+      .map(|path| self.resource_dir.join(path))
+      .collect()
+  }
+}
+
+/// Builds a `Config` without writing any file
+///
+/// Settings are layered: the bundled defaults, then the setting files in the order they are
+/// added, then the programmatic overrides. `userDict` is appended to and every other key,
+/// including the plugin lists, is replaced. Relative paths in a setting file are resolved
+/// against the directory of that file; those in the defaults and overrides against the
+/// resource directory, which is the directory of the last setting file unless given.
+/// Missing resources are served from the bundled ones.
+#[derive(Default)]
+pub struct ConfigBuilder {
+  settings_files: Vec<PathBuf>,
+  overrides: Vec<Value>,
+  resource_dir: Option<PathBuf>,
+}
+
+impl ConfigBuilder {
+  /// Add a setting file over the defaults and the files added before
+  pub fn settings_file<P: AsRef<Path>>(mut self, path: P) -> ConfigBuilder {
+    self.settings_files.push(path.as_ref().to_path_buf());
+    self
+  }
+  /// Add settings over every setting file, e.g. `json!({"userDict": ["user.dic"]})`
+  pub fn override_settings(mut self, settings: Value) -> ConfigBuilder {
+    self.overrides.push(settings);
+    self
+  }
+  pub fn resource_dir<P: AsRef<Path>>(mut self, dir: P) -> ConfigBuilder {
+    self.resource_dir = Some(dir.as_ref().to_path_buf());
+    self
+  }
+  pub fn build(self) -> Result<Config, ConfigErr> {
+    let mut config = Config::empty()?;
+    config.settings = Settings::from_value(&self.read_settings()?)?;
+    if let Some(resource_dir) = self.resource_dir {
+      config.resource_dir = resource_dir;
+    } else if let Some(path) = self.settings_files.last() {
+      config.resource_dir = settings_dir(path)?;
+    }
+    Ok(config)
+  }
+
+  /// Report every problem of the settings and the files they refer to
+  ///
+  /// Unlike `build`, this doesn't stop at the first problem. Problems of a setting file are
+  /// prefixed with its path, and the files are still checked as long as the merged settings
+  /// can be read. An empty list means the config can be used to set up a `Dictionary`.
+  pub fn check(self, python_exe: Option<&OsStr>) -> Vec<String> {
+    let mut problems = vec![];
+    for path in self.settings_files.iter() {
+      match read_settings_file(path) {
+        Ok(layer) => {
+          if let Err(e) = Settings::from_value(&layer) {
+            problems.extend(e.0.iter().map(|e| format!("{}: {}", path.display(), e)));
+          }
         }
+        Err(e) => problems.push(format!("{}: {}", path.display(), e)),
+      }
+    }
+    // problems of the merged settings are the ones of the layers, unless they conflict
+    let settings = match self.read_settings() {
+      Ok(settings) => settings,
+      Err(e) => {
+        if problems.is_empty() {
+          problems.push(e.to_string());
+        }
+        return problems;
+      }
+    };
+    if let Err(e) = Settings::from_value(&settings) {
+      if problems.is_empty() {
+        problems.extend(e.0.iter().map(|e| e.to_string()));
       }
+      return problems;
     }
-    paths
+    let mut config = match self.build() {
+      Ok(config) => config,
+      Err(e) => {
+        problems.push(e.to_string());
+        return problems;
+      }
+    };
+    match config.system_dict_path(python_exe) {
+      Ok(path) => match read_system_grammar(&path) {
+        Ok(grammar) => problems.extend(check_oov_pos(&config.settings, &grammar)),
+        Err(e) => problems.push(format!("`systemDict` {}: {}", path.display(), e)),
+      },
+      Err(e) => problems.push(e.to_string()),
+    }
+    for path in config.user_dict_paths() {
+      if !path.is_file() {
+        problems.push(format!("`userDict` {} doesn't exist", path.display()));
+      }
+    }
+    let mut resource_names = vec![(
+      "characterDefinitionFile",
+      &config.settings.character_definition_file,
+    )];
+    for plugin in config.settings.oov_provider_plugin.iter() {
+      if let OovProviderPluginSettings::MecabOovPlugin(settings) = plugin {
+        resource_names.push(("charDef", &settings.char_def));
+        resource_names.push(("unkDef", &settings.unk_def));
+      }
+    }
+    for (key, name) in resource_names {
+      if let Err(e) = config.open_resource(name) {
+        problems.push(format!("`{}` {}", key, e));
+      }
+    }
+    problems
+  }
+
+  /// Merge the layers of settings
+  fn read_settings(&self) -> Result<Value, ConfigErr> {
+    let mut settings = serde_json::from_str(resources::SUDACHI_JSON)?;
+    for path in self.settings_files.iter() {
+      let mut layer = read_settings_file(path)?;
+      resolve_paths(&mut layer, &settings_dir(path)?);
+      merge_settings(&mut settings, layer);
+    }
+    for layer in self.overrides.iter() {
+      merge_settings(&mut settings, layer.clone());
+    }
+    Ok(settings)
   }
 }
 
+/// The grammar of a system dictionary without reading its lexicon
+fn read_system_grammar(path: &Path) -> Result<Grammar, String> {
+  let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+  DictionaryHeader::from_reader(&mut reader).map_err(|e| e.to_string())?;
+  Grammar::from_reader(&mut reader).map_err(|e| e.to_string())
+}
+
+/// `oovPOS` of simple OOV plugins which aren't parts of speech of `grammar`
+fn check_oov_pos(settings: &Settings, grammar: &Grammar) -> Vec<String> {
+  let mut problems = vec![];
+  for (i, plugin) in settings.oov_provider_plugin.iter().enumerate() {
+    if let OovProviderPluginSettings::SimpleOovPlugin(settings) = plugin {
+      let pos: Vec<&str> = settings.oov_pos.iter().map(|p| p.as_str()).collect();
+      if grammar.get_part_of_speech_id(&pos).is_none() {
+        let e = SimpleOovPluginSetupErr::UnknownPosErr(settings.oov_pos.clone());
+        problems.push(OovProviderPluginGetErr::SimpleOovPluginSetupErr(i, e).to_string());
+      }
+    }
+  }
+  problems
+}
+
+fn read_settings_file(path: &Path) -> Result<Value, ConfigErr> {
+  let mut buf = String::new();
+  BufReader::new(&mut File::open(path)?).read_to_string(&mut buf)?;
+  Ok(serde_json::from_str(&buf)?)
+}
+
+/// Write the bundled setting file and resources into `dir`, keeping existing files
+pub fn write_default_resources<P: AsRef<Path>>(dir: P) -> Result<(), IOError> {
+  let dir = dir.as_ref();
+  resources::write_sudachi_json(dir.join("sudachi.json"))?;
+  resources::write_resources(dir)
+}
+
+/// The absolute directory of the setting file `path`, the current one for a bare file name
+fn settings_dir(path: &Path) -> Result<PathBuf, IOError> {
+  Ok(current_dir()?.join(ok_or_io_err(path.parent(), "InvalidSettingDirPath")?))
+}
+
 fn ok_or_io_err<T>(t: Option<T>, err: &str) -> Result<T, IOError> {
   t.ok_or_else(|| IOError::new(IOErrorKind::Other, err))
 }
@@ -157,6 +381,10 @@ pub enum SudachiDictErr {
   IOError(#[from] IOError),
   #[error("`systemDict` must be specified if `SudachiDict_core` not installed")]
   NotFoundSudachiDictCoreErr,
+  #[error("`{0}` not installed")]
+  NotFoundSudachiDictPackageErr(String),
+  #[error("system dictionary not found, tried:\n  {}", .0.join("\n  "))]
+  NotFoundSystemDictErr(Vec<String>),
   #[error("Multiple packages of `SudachiDict_*` installed. Set default dict with link command.")]
   SetDefaultDictErr,
   #[error("unlink faild (dictionary exists)")]
@@ -278,8 +506,240 @@ pub fn create_default_link_for_sudachidict_core(
   Ok(())
 }
 
+/// Link `sudachidict_<dict_type>` as the default `sudachidict` package
+pub fn create_default_link_for_sudachidict(
+  dict_type: &str,
+  python_exe: Option<&OsStr>,
+) -> Result<(), SudachiDictErr> {
+  let dict_pkg_name = format!("{}_{}", SUDACHIDICT_PKG_NAME, dict_type);
+  if !success_import(python_exe, &dict_pkg_name) {
+    return Err(SudachiDictErr::NotFoundSudachiDictPackageErr(dict_pkg_name));
+  }
+  set_default_dict_package(python_exe, &dict_pkg_name)?;
+  Ok(())
+}
+
 fn get_sudachi_dict_path(python_exe: Option<&OsStr>) -> Result<PathBuf, SudachiDictErr> {
   info!("Getting sudachi dictionary path");
   let package_path = get_sudachi_py_package_path(python_exe)?;
   Ok(PathBuf::from_str(&package_path)?.join("resources/system.dic"))
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use std::env::temp_dir;
+  use std::fs::create_dir_all;
+
+  fn resources_test_dir() -> PathBuf {
+    PathBuf::from(file!())
+      .parent()
+      .unwrap()
+      .join("resources/test")
+  }
+
+  fn read_all<R: BufRead + ?Sized>(reader: &mut R) -> String {
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf).unwrap();
+    buf
+  }
+
+  #[test]
+  fn test_build_with_bundled_settings() {
+    let config = Config::builder().build().unwrap();
+    assert_eq!("char.def", config.settings.character_definition_file);
+    assert!(!read_all(&mut config.open_char_def().unwrap()).is_empty());
+    assert!(!config.DEFAULT_SETTINGFILE.exists());
+  }
+
+  #[test]
+  fn test_build_with_settings_file() {
+    let config = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .build()
+      .unwrap();
+    assert_eq!(
+      current_dir().unwrap().join(resources_test_dir()),
+      config.resource_dir
+    );
+    assert_eq!(
+      include_str!("resources/test/char.def"),
+      read_all(&mut config.open_char_def().unwrap())
+    );
+  }
+
+  #[test]
+  fn test_check() {
+    let problems = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .check(None);
+    assert!(problems.is_empty(), "{:?}", problems);
+
+    let problems = Config::builder()
+      .settings_file(resources_test_dir().join("not_found.json"))
+      .check(None);
+    assert_eq!(1, problems.len());
+
+    let problems = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .resource_dir(resources_test_dir().join("not_found"))
+      .override_settings(json!({"userDict": ["user.dic"]}))
+      .check(None);
+    assert_eq!(1, problems.len(), "{:?}", problems);
+    assert!(problems[0].starts_with("`userDict`"));
+  }
+
+  #[test]
+  fn test_check_after_layer_problems() {
+    let dir = temp_dir().join("sudachiclone_config_check");
+    create_dir_all(&dir).unwrap();
+    let layer_file = dir.join("layer.json");
+    File::create(&layer_file)
+      .unwrap()
+      .write_all(br#"{"characterDefinitionFile": 3}"#)
+      .unwrap();
+
+    let problems = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .settings_file(&layer_file)
+      .override_settings(json!({
+        "characterDefinitionFile": "char.def",
+        "oovProviderPlugin": [{
+          "class": "sudachipy.plugin.oov.SimpleOovProviderPlugin",
+          "oovPOS": ["名詞", "固有名詞", "*", "*", "*", "*"],
+          "leftId": 8,
+          "rightId": 8,
+          "cost": 6000
+        }]
+      }))
+      .check(None);
+    assert_eq!(2, problems.len(), "{:?}", problems);
+    assert!(problems[0].starts_with(&layer_file.display().to_string()));
+    assert_eq!(
+      "`oovProviderPlugin[0].oovPOS` 名詞,固有名詞,*,*,*,* is not a part of speech of the system dictionary",
+      problems[1]
+    );
+  }
+
+  #[test]
+  fn test_build_with_layers() {
+    let dir = temp_dir().join("sudachiclone_config_layers");
+    create_dir_all(&dir).unwrap();
+    let override_file = dir.join("override.json");
+    File::create(&override_file)
+      .unwrap()
+      .write_all(br#"{"userDict": ["extra.dic"], "inputTextPlugin": []}"#)
+      .unwrap();
+
+    let config = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .settings_file(&override_file)
+      .override_settings(json!({"userDict": ["more.dic"]}))
+      .build()
+      .unwrap();
+    assert_eq!(dir, config.resource_dir);
+    assert_eq!(
+      vec![
+        current_dir()
+          .unwrap()
+          .join(resources_test_dir())
+          .join("user.dic"),
+        dir.join("extra.dic"),
+        dir.join("more.dic"),
+      ],
+      config.user_dict_paths()
+    );
+    assert!(config.settings.input_text_plugin.is_empty());
+    assert_eq!(1, config.settings.oov_provider_plugin.len());
+    assert_eq!(
+      include_str!("resources/test/char.def"),
+      read_all(&mut config.open_char_def().unwrap())
+    );
+  }
+
+  #[test]
+  fn test_build_over_bundled_defaults() {
+    let config = Config::builder()
+      .override_settings(json!({"userDict": ["user.dic"]}))
+      .build()
+      .unwrap();
+    assert_eq!(2, config.settings.input_text_plugin.len());
+    assert_eq!(vec!["user.dic"], config.settings.user_dict);
+  }
+
+  #[test]
+  fn test_open_resource_falls_back_to_bundled() {
+    let config = Config::builder()
+      .resource_dir(resources_test_dir().join("not_found"))
+      .build()
+      .unwrap();
+    assert!(!read_all(&mut config.open_resource("rewrite.def").unwrap()).is_empty());
+    assert!(config.open_resource("not_found.def").is_err());
+    let missing = current_dir().unwrap().join("mecab/rewrite.def");
+    let e = config
+      .open_resource(missing.to_str().unwrap())
+      .err()
+      .unwrap();
+    assert_eq!(IOErrorKind::NotFound, e.kind());
+  }
+
+  #[test]
+  fn test_relative_settings_file() {
+    assert_eq!(
+      current_dir().unwrap(),
+      settings_dir(Path::new("sudachi.json")).unwrap()
+    );
+    let config = Config::builder()
+      .settings_file(resources_test_dir().join("sudachi.json"))
+      .build()
+      .unwrap();
+    // the rewrite.def next to the setting file, not the bundled one
+    assert_eq!(
+      std::fs::read_to_string(resources_test_dir().join("rewrite.def")).unwrap(),
+      read_all(&mut config.open_resource("rewrite.def").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_check_missing_unk_def() {
+    let dir = temp_dir().join("sudachiclone_config_missing_unk_def");
+    create_dir_all(&dir).unwrap();
+    let settings_file = dir.join("sudachi.json");
+    let char_def = current_dir()
+      .unwrap()
+      .join(resources_test_dir().join("char.def"));
+    File::create(&settings_file)
+      .unwrap()
+      .write_all(
+        json!({
+          "systemDict": current_dir().unwrap().join(resources_test_dir().join("system.dic")),
+          "oovProviderPlugin": [{
+            "class": "sudachipy.plugin.oov.MeCabOovProviderPlugin",
+            "charDef": char_def,
+            "unkDef": "mecab/unk.def"
+          }]
+        })
+        .to_string()
+        .as_bytes(),
+      )
+      .unwrap();
+
+    let problems = Config::builder().settings_file(&settings_file).check(None);
+    assert_eq!(1, problems.len(), "{:?}", problems);
+    assert!(problems[0].starts_with("`unkDef`"), "{}", problems[0]);
+    assert!(problems[0].contains("mecab"), "{}", problems[0]);
+  }
+
+  #[test]
+  fn test_open_resource_from_memory() {
+    let mut resources = HashMap::new();
+    resources.insert(String::from("char.def"), b"0x0030..0x0039 NUMERIC".to_vec());
+    let config = Config::from_memory(Settings::default(), resources);
+    assert_eq!(
+      "0x0030..0x0039 NUMERIC",
+      read_all(&mut config.open_resource("char.def").unwrap())
+    );
+  }
+}
@@ -0,0 +1,271 @@
+use std::env::{split_paths, var_os};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use super::{get_sudachi_dict_path, SudachiDictErr};
+
+/// Environment variable naming the system dictionary
+pub const SUDACHI_DICT_PATH_ENV: &str = "SUDACHI_DICT_PATH";
+/// Directory name under the data and cache directories
+pub const APP_DIR_NAME: &str = "sudachiclone";
+/// File name of the system dictionary under the data and cache directories
+pub const SYSTEM_DICT_FILE_NAME: &str = "system.dic";
+
+/// Where a system dictionary candidate comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DictionarySource {
+  Explicit,
+  Environment,
+  DataDir,
+  CacheDir,
+  PythonPackage,
+}
+
+impl fmt::Display for DictionarySource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      DictionarySource::Explicit => "explicit path",
+      DictionarySource::Environment => SUDACHI_DICT_PATH_ENV,
+      DictionarySource::DataDir => "data dir",
+      DictionarySource::CacheDir => "cache dir",
+      DictionarySource::PythonPackage => "sudachidict Python package",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// Finds the system dictionary without relying on Python
+///
+/// Candidates are checked in order: an explicit path, `SUDACHI_DICT_PATH`, the XDG data
+/// dirs, the user's cache dir and, as a last resort, the `sudachidict` Python package.
+pub struct DictionaryLocator<'a> {
+  explicit_path: Option<PathBuf>,
+  env_path: Option<PathBuf>,
+  data_dirs: Vec<PathBuf>,
+  cache_dir: Option<PathBuf>,
+  python_exe: Option<&'a OsStr>,
+  use_python: bool,
+}
+
+impl<'a> Default for DictionaryLocator<'a> {
+  fn default() -> DictionaryLocator<'a> {
+    DictionaryLocator {
+      explicit_path: None,
+      env_path: var_os(SUDACHI_DICT_PATH_ENV)
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from),
+      data_dirs: get_data_dirs(),
+      cache_dir: get_cache_dir(),
+      python_exe: None,
+      use_python: true,
+    }
+  }
+}
+
+impl<'a> DictionaryLocator<'a> {
+  pub fn explicit_path<P: AsRef<Path>>(mut self, path: Option<P>) -> DictionaryLocator<'a> {
+    self.explicit_path = path.map(|p| p.as_ref().to_path_buf());
+    self
+  }
+  pub fn env_path<P: AsRef<Path>>(mut self, path: Option<P>) -> DictionaryLocator<'a> {
+    self.env_path = path.map(|p| p.as_ref().to_path_buf());
+    self
+  }
+  pub fn data_dirs(mut self, dirs: Vec<PathBuf>) -> DictionaryLocator<'a> {
+    self.data_dirs = dirs;
+    self
+  }
+  pub fn cache_dir<P: AsRef<Path>>(mut self, dir: Option<P>) -> DictionaryLocator<'a> {
+    self.cache_dir = dir.map(|p| p.as_ref().to_path_buf());
+    self
+  }
+  pub fn python_exe(mut self, python_exe: Option<&'a OsStr>) -> DictionaryLocator<'a> {
+    self.python_exe = python_exe;
+    self
+  }
+  pub fn use_python(mut self, use_python: bool) -> DictionaryLocator<'a> {
+    self.use_python = use_python;
+    self
+  }
+  /// Candidate paths in the order they are checked, except the Python package
+  pub fn candidates(&self) -> Vec<(DictionarySource, PathBuf)> {
+    let mut candidates = vec![];
+    if let Some(path) = &self.explicit_path {
+      candidates.push((DictionarySource::Explicit, path.clone()));
+    }
+    if let Some(path) = &self.env_path {
+      candidates.push((DictionarySource::Environment, path.clone()));
+    }
+    for dir in self.data_dirs.iter() {
+      candidates.push((
+        DictionarySource::DataDir,
+        dir.join(APP_DIR_NAME).join(SYSTEM_DICT_FILE_NAME),
+      ));
+    }
+    if let Some(dir) = &self.cache_dir {
+      candidates.push((
+        DictionarySource::CacheDir,
+        dir.join(APP_DIR_NAME).join(SYSTEM_DICT_FILE_NAME),
+      ));
+    }
+    candidates
+  }
+  pub fn locate(&self) -> Result<(DictionarySource, PathBuf), SudachiDictErr> {
+    let mut tried = vec![];
+    for (source, path) in self.candidates() {
+      debug!(
+        "Looking for the system dictionary at {:?} ({})",
+        path, source
+      );
+      if path.is_file() {
+        return Ok((source, path));
+      }
+      tried.push(format!("{} ({})", path.display(), source));
+    }
+    if self.use_python {
+      match get_sudachi_dict_path(self.python_exe) {
+        Ok(path) if path.is_file() => return Ok((DictionarySource::PythonPackage, path)),
+        Ok(path) => tried.push(format!(
+          "{} ({})",
+          path.display(),
+          DictionarySource::PythonPackage
+        )),
+        Err(e) => tried.push(format!("{} ({})", DictionarySource::PythonPackage, e)),
+      }
+    }
+    Err(SudachiDictErr::NotFoundSystemDictErr(tried))
+  }
+}
+
+fn home_dir() -> Option<PathBuf> {
+  var_os("HOME")
+    .or_else(|| var_os("USERPROFILE"))
+    .filter(|p| !p.is_empty())
+    .map(PathBuf::from)
+}
+
+fn non_empty_var(key: &str) -> Option<OsString> {
+  var_os(key).filter(|p| !p.is_empty())
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`
+pub fn get_data_dirs() -> Vec<PathBuf> {
+  let mut dirs = vec![];
+  if let Some(dir) = non_empty_var("XDG_DATA_HOME") {
+    dirs.push(PathBuf::from(dir));
+  } else if let Some(dir) = non_empty_var("APPDATA") {
+    dirs.push(PathBuf::from(dir));
+  } else if let Some(home) = home_dir() {
+    dirs.push(home.join(".local/share"));
+  }
+  match non_empty_var("XDG_DATA_DIRS") {
+    Some(data_dirs) => dirs.extend(split_paths(&data_dirs)),
+    None if cfg!(unix) => {
+      dirs.push(PathBuf::from("/usr/local/share"));
+      dirs.push(PathBuf::from("/usr/share"));
+    }
+    None => (),
+  }
+  dirs
+}
+
+/// `$XDG_CACHE_HOME`, or the platform default cache directory of the user
+pub fn get_cache_dir() -> Option<PathBuf> {
+  if let Some(dir) = non_empty_var("XDG_CACHE_HOME") {
+    return Some(PathBuf::from(dir));
+  }
+  if let Some(dir) = non_empty_var("LOCALAPPDATA") {
+    return Some(PathBuf::from(dir));
+  }
+  home_dir().map(|home| {
+    if cfg!(target_os = "macos") {
+      home.join("Library/Caches")
+    } else {
+      home.join(".cache")
+    }
+  })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn resources_test_dir() -> PathBuf {
+    PathBuf::from(file!())
+      .parent()
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("resources/test")
+  }
+
+  fn build_locator<'a>() -> DictionaryLocator<'a> {
+    DictionaryLocator::default()
+      .env_path(None::<PathBuf>)
+      .data_dirs(vec![])
+      .cache_dir(None::<PathBuf>)
+      .use_python(false)
+  }
+
+  #[test]
+  fn test_locate_explicit_path() {
+    let path = resources_test_dir().join("system.dic");
+    let located = build_locator()
+      .explicit_path(Some(&path))
+      .env_path(Some(resources_test_dir().join("user.dic")))
+      .locate()
+      .unwrap();
+    assert_eq!((DictionarySource::Explicit, path), located);
+  }
+
+  #[test]
+  fn test_locate_in_order() {
+    let path = resources_test_dir().join("system.dic");
+    let located = build_locator()
+      .explicit_path(Some(resources_test_dir().join("not_found.dic")))
+      .env_path(Some(&path))
+      .locate()
+      .unwrap();
+    assert_eq!((DictionarySource::Environment, path), located);
+  }
+
+  #[test]
+  fn test_candidates() {
+    let candidates = build_locator()
+      .data_dirs(vec![PathBuf::from("/data")])
+      .cache_dir(Some("/cache"))
+      .candidates();
+    assert_eq!(
+      vec![
+        (
+          DictionarySource::DataDir,
+          PathBuf::from("/data/sudachiclone/system.dic")
+        ),
+        (
+          DictionarySource::CacheDir,
+          PathBuf::from("/cache/sudachiclone/system.dic")
+        ),
+      ],
+      candidates
+    );
+  }
+
+  #[test]
+  fn test_not_found_reports_all_locations() {
+    let err = build_locator()
+      .explicit_path(Some("/not_found/explicit.dic"))
+      .data_dirs(vec![PathBuf::from("/not_found/data")])
+      .cache_dir(Some("/not_found/cache"))
+      .locate()
+      .err()
+      .unwrap();
+    let message = format!("{}", err);
+    assert!(message.contains("/not_found/explicit.dic"));
+    assert!(message.contains("/not_found/data/sudachiclone/system.dic"));
+    assert!(message.contains("/not_found/cache/sudachiclone/system.dic"));
+  }
+}
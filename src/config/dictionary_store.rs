@@ -0,0 +1,457 @@
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_file, rename, File};
+use std::io::{copy as io_copy, Cursor, Error as IOError, Read, Write};
+use std::path::{Path, PathBuf};
+
+use log::info;
+#[cfg(any(target_os = "redox", unix, windows))]
+use symlink::symlink_file;
+use thiserror::Error;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use super::dictionary_locator::{get_data_dirs, APP_DIR_NAME, SYSTEM_DICT_FILE_NAME};
+use crate::dictionary_lib::dictionary_header::{DictionaryHeader, DictionaryHeaderErr};
+use crate::dictionary_lib::system_dictionary_version::{
+  SYSTEM_DICT_VERSION, USER_DICT_VERSION_1, USER_DICT_VERSION_2,
+};
+
+const DICTS_DIR_NAME: &str = "dicts";
+const CURRENT_FILE_NAME: &str = "current";
+const DICT_EXTENSION: &str = "dic";
+const HEADER_SIZE: usize = 8 + 8 + 256;
+
+#[derive(Error, Debug)]
+pub enum DictionaryStoreErr {
+  #[error("{0}")]
+  IOError(#[from] IOError),
+  #[error("{0}")]
+  ZipError(#[from] ZipError),
+  #[error("{0}")]
+  DictionaryHeaderErr(#[from] DictionaryHeaderErr),
+  #[error("no data dir to store dictionaries")]
+  NotFoundDataDirErr,
+  #[error("`{0}` is not a Sudachi dictionary")]
+  InvalidDictionaryErr(String),
+  #[error("no dictionary found in `{0}`")]
+  NotFoundDictionaryInArchiveErr(String),
+  #[error("`{0}` contains several dictionaries; install them without a name")]
+  AmbiguousNameErr(String),
+  #[error("`{0}` is an invalid dictionary name")]
+  InvalidNameErr(String),
+  #[error("dictionary `{0}` is already installed")]
+  AlreadyInstalledErr(String),
+  #[error("dictionary `{0}` is not installed")]
+  NotInstalledErr(String),
+  #[error("dictionary `{0}` is not a system dictionary")]
+  NotSystemDictionaryErr(String),
+}
+
+/// A dictionary kept in a `DictionaryStore`
+pub struct InstalledDictionary {
+  pub name: String,
+  pub path: PathBuf,
+  pub header: DictionaryHeader,
+  pub is_current: bool,
+}
+
+impl InstalledDictionary {
+  pub fn is_system_dictionary(&self) -> bool {
+    self.header.version == SYSTEM_DICT_VERSION
+  }
+  /// Kind of the dictionary told by its header version
+  pub fn version_name(&self) -> &'static str {
+    self.header.version_name()
+  }
+}
+
+/// Local store of dictionaries the user already has
+///
+/// Dictionaries are kept as `<root>/dicts/<name>.dic`. The current system dictionary is
+/// linked to `<root>/system.dic`, where `DictionaryLocator` finds it.
+pub struct DictionaryStore {
+  root: PathBuf,
+}
+
+impl DictionaryStore {
+  pub fn new<P: AsRef<Path>>(root: P) -> DictionaryStore {
+    DictionaryStore {
+      root: root.as_ref().to_path_buf(),
+    }
+  }
+  /// Store under the first data dir, usually `$XDG_DATA_HOME/sudachiclone`
+  pub fn open_default() -> Result<DictionaryStore, DictionaryStoreErr> {
+    get_data_dirs()
+      .into_iter()
+      .next()
+      .map(|dir| DictionaryStore::new(dir.join(APP_DIR_NAME)))
+      .ok_or(DictionaryStoreErr::NotFoundDataDirErr)
+  }
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+  fn dicts_dir(&self) -> PathBuf {
+    self.root.join(DICTS_DIR_NAME)
+  }
+  fn dict_path(&self, name: &str) -> PathBuf {
+    self
+      .dicts_dir()
+      .join(format!("{}.{}", name, DICT_EXTENSION))
+  }
+  fn system_dict_path(&self) -> PathBuf {
+    self.root.join(SYSTEM_DICT_FILE_NAME)
+  }
+  fn current_path(&self) -> PathBuf {
+    self.root.join(CURRENT_FILE_NAME)
+  }
+
+  /// Install a `.dic` file or every `.dic` file in a SudachiDict `.zip` archive
+  ///
+  /// The name defaults to the file stem without the `system_` prefix, so that
+  /// `system_core.dic` is installed as `core`. The first system dictionary installed
+  /// becomes the current one.
+  pub fn install<P: AsRef<Path>>(
+    &self,
+    path: P,
+    name: Option<&str>,
+    force: bool,
+  ) -> Result<Vec<InstalledDictionary>, DictionaryStoreErr> {
+    let path = path.as_ref();
+    create_dir_all(self.dicts_dir())?;
+    let is_zip = path
+      .extension()
+      .map(|e| e.eq_ignore_ascii_case("zip"))
+      .unwrap_or(false);
+    let mut names = vec![];
+    if is_zip {
+      let mut archive = ZipArchive::new(File::open(path)?)?;
+      let entries: Vec<String> = archive
+        .file_names()
+        .filter(|n| n.ends_with(&format!(".{}", DICT_EXTENSION)))
+        .map(|n| n.to_string())
+        .collect();
+      if entries.is_empty() {
+        return Err(DictionaryStoreErr::NotFoundDictionaryInArchiveErr(
+          path.display().to_string(),
+        ));
+      }
+      if name.is_some() && entries.len() > 1 {
+        return Err(DictionaryStoreErr::AmbiguousNameErr(
+          path.display().to_string(),
+        ));
+      }
+      for entry in entries {
+        let dict_name = match name {
+          Some(name) => name.to_string(),
+          None => default_name(Path::new(&entry)),
+        };
+        let mut reader = archive.by_name(&entry)?;
+        self.install_from_reader(&mut reader, &dict_name, &entry, force)?;
+        names.push(dict_name);
+      }
+    } else {
+      let dict_name = match name {
+        Some(name) => name.to_string(),
+        None => default_name(path),
+      };
+      let mut reader = File::open(path)?;
+      self.install_from_reader(&mut reader, &dict_name, &path.display().to_string(), force)?;
+      names.push(dict_name);
+    }
+
+    if self.current()?.is_none() {
+      for name in names.iter() {
+        if self.get(name)?.is_system_dictionary() {
+          self.use_dictionary(name)?;
+          break;
+        }
+      }
+    }
+    names.iter().map(|name| self.get(name)).collect()
+  }
+  fn install_from_reader<R: Read>(
+    &self,
+    reader: &mut R,
+    name: &str,
+    source: &str,
+    force: bool,
+  ) -> Result<(), DictionaryStoreErr> {
+    check_name(name)?;
+    let dst = self.dict_path(name);
+    if dst.exists() && !force {
+      return Err(DictionaryStoreErr::AlreadyInstalledErr(name.to_string()));
+    }
+    let mut head = vec![0u8; HEADER_SIZE];
+    reader
+      .read_exact(&mut head)
+      .map_err(|_| DictionaryStoreErr::InvalidDictionaryErr(source.to_string()))?;
+    let header = DictionaryHeader::from_reader(&mut Cursor::new(&head))?;
+    if !is_known_version(header.version) {
+      return Err(DictionaryStoreErr::InvalidDictionaryErr(source.to_string()));
+    }
+
+    let tmp = self.dicts_dir().join(format!(".{}.tmp", name));
+    let copied = (|| -> Result<(), IOError> {
+      let mut writer = File::create(&tmp)?;
+      writer.write_all(&head)?;
+      io_copy(reader, &mut writer)?;
+      writer.sync_all()?;
+      rename(&tmp, &dst)
+    })();
+    if let Err(e) = copied {
+      // the copy error is the one worth reporting
+      let _ = remove_file(&tmp);
+      return Err(e.into());
+    }
+    info!("installed {} as `{}`", source, name);
+    Ok(())
+  }
+
+  pub fn get(&self, name: &str) -> Result<InstalledDictionary, DictionaryStoreErr> {
+    check_name(name)?;
+    let path = self.dict_path(name);
+    if !path.is_file() {
+      return Err(DictionaryStoreErr::NotInstalledErr(name.to_string()));
+    }
+    let mut head = vec![0u8; HEADER_SIZE];
+    File::open(&path)?.read_exact(&mut head)?;
+    let header = DictionaryHeader::from_reader(&mut Cursor::new(&head))?;
+    let is_current = self.current()?.as_deref() == Some(name);
+    Ok(InstalledDictionary {
+      name: name.to_string(),
+      path,
+      header,
+      is_current,
+    })
+  }
+
+  /// Installed dictionaries sorted by name
+  pub fn list(&self) -> Result<Vec<InstalledDictionary>, DictionaryStoreErr> {
+    let dir = self.dicts_dir();
+    if !dir.is_dir() {
+      return Ok(vec![]);
+    }
+    let mut names = vec![];
+    for entry in read_dir(dir)? {
+      let path = entry?.path();
+      if path.extension().and_then(|e| e.to_str()) != Some(DICT_EXTENSION) {
+        continue;
+      }
+      if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+        names.push(name.to_string());
+      }
+    }
+    names.sort();
+    names.iter().map(|name| self.get(name)).collect()
+  }
+
+  /// Name of the current system dictionary
+  pub fn current(&self) -> Result<Option<String>, DictionaryStoreErr> {
+    let path = self.current_path();
+    if !path.is_file() {
+      return Ok(None);
+    }
+    let name = read_to_string(path)?.trim().to_string();
+    Ok(if name.is_empty() { None } else { Some(name) })
+  }
+
+  /// Make the system dictionary `name` the current one
+  pub fn use_dictionary(&self, name: &str) -> Result<(), DictionaryStoreErr> {
+    let dictionary = self.get(name)?;
+    if !dictionary.is_system_dictionary() {
+      return Err(DictionaryStoreErr::NotSystemDictionaryErr(name.to_string()));
+    }
+    self.unlink_system_dict()?;
+    link_file(&dictionary.path, &self.system_dict_path())?;
+    File::create(self.current_path())?.write_all(name.as_bytes())?;
+    info!("using `{}` as the system dictionary", name);
+    Ok(())
+  }
+
+  pub fn remove(&self, name: &str) -> Result<(), DictionaryStoreErr> {
+    let dictionary = self.get(name)?;
+    if dictionary.is_current {
+      self.unlink_system_dict()?;
+      remove_file(self.current_path())?;
+    }
+    remove_file(dictionary.path)?;
+    info!("removed `{}`", name);
+    Ok(())
+  }
+
+  fn unlink_system_dict(&self) -> Result<(), IOError> {
+    let path = self.system_dict_path();
+    if path.symlink_metadata().is_ok() {
+      remove_file(path)?;
+    }
+    Ok(())
+  }
+}
+
+fn is_known_version(version: u64) -> bool {
+  version == SYSTEM_DICT_VERSION || version == USER_DICT_VERSION_1 || version == USER_DICT_VERSION_2
+}
+
+fn default_name(path: &Path) -> String {
+  let stem = path
+    .file_stem()
+    .and_then(|s| s.to_str())
+    .unwrap_or_default();
+  stem.trim_start_matches("system_").to_string()
+}
+
+fn check_name(name: &str) -> Result<(), DictionaryStoreErr> {
+  if name.is_empty()
+    || name.starts_with('.')
+    || !name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+  {
+    return Err(DictionaryStoreErr::InvalidNameErr(name.to_string()));
+  }
+  Ok(())
+}
+
+#[cfg(any(target_os = "redox", unix, windows))]
+fn link_file(src: &Path, dst: &Path) -> Result<(), IOError> {
+  symlink_file(src, dst).or_else(|_| copy(src, dst).map(|_| ()))
+}
+
+#[cfg(not(any(target_os = "redox", unix, windows)))]
+fn link_file(src: &Path, dst: &Path) -> Result<(), IOError> {
+  copy(src, dst).map(|_| ())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::dictionary_locator::{DictionaryLocator, DictionarySource};
+  use std::env::temp_dir;
+  use std::fs::remove_dir_all;
+  use zip::write::FileOptions;
+  use zip::{CompressionMethod, ZipWriter};
+
+  fn resources_test_dir() -> PathBuf {
+    PathBuf::from(file!())
+      .parent()
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("resources/test")
+  }
+
+  fn setup_store(name: &str) -> DictionaryStore {
+    let data_dir = temp_dir().join(format!("sudachiclone_dictionary_store_{}", name));
+    if data_dir.exists() {
+      remove_dir_all(&data_dir).unwrap();
+    }
+    DictionaryStore::new(data_dir.join(APP_DIR_NAME))
+  }
+
+  #[test]
+  fn test_install_and_use() {
+    let store = setup_store("install");
+    let installed = store
+      .install(resources_test_dir().join("system.dic"), None, false)
+      .unwrap();
+    assert_eq!(1, installed.len());
+    assert_eq!("system", installed[0].name);
+    assert!(installed[0].is_current);
+    assert_eq!(
+      "the system dictionary for the unit tests",
+      installed[0].header.description
+    );
+
+    let installed = store
+      .install(resources_test_dir().join("user.dic"), Some("custom"), false)
+      .unwrap();
+    assert!(!installed[0].is_system_dictionary());
+    assert_eq!("user (v2)", installed[0].version_name());
+    assert!(!installed[0].is_current);
+    assert!(store.use_dictionary("custom").is_err());
+
+    let names: Vec<String> = store.list().unwrap().into_iter().map(|d| d.name).collect();
+    assert_eq!(vec!["custom", "system"], names);
+
+    let (source, path) = DictionaryLocator::default()
+      .env_path(None::<PathBuf>)
+      .data_dirs(vec![store.root().parent().unwrap().to_path_buf()])
+      .cache_dir(None::<PathBuf>)
+      .use_python(false)
+      .locate()
+      .unwrap();
+    assert_eq!(DictionarySource::DataDir, source);
+    assert_eq!(store.root().join(SYSTEM_DICT_FILE_NAME), path);
+  }
+
+  #[test]
+  fn test_install_twice() {
+    let store = setup_store("twice");
+    let path = resources_test_dir().join("system.dic");
+    store.install(&path, Some("core"), false).unwrap();
+    assert!(store.install(&path, Some("core"), false).is_err());
+    assert!(store.install(&path, Some("core"), true).is_ok());
+    assert!(store.install(&path, Some("../core"), false).is_err());
+  }
+
+  #[test]
+  fn test_install_invalid_file() {
+    let store = setup_store("invalid");
+    assert!(store
+      .install(resources_test_dir().join("char.def"), None, false)
+      .is_err());
+    assert!(store.list().unwrap().is_empty());
+  }
+
+  struct BrokenReader;
+
+  impl Read for BrokenReader {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, IOError> {
+      Err(IOError::other("broken"))
+    }
+  }
+
+  #[test]
+  fn test_install_broken_copy() {
+    let store = setup_store("broken");
+    create_dir_all(store.dicts_dir()).unwrap();
+    let bytes = include_bytes!("../resources/test/system.dic");
+    let mut reader = Cursor::new(&bytes[..HEADER_SIZE]).chain(BrokenReader);
+    assert!(store
+      .install_from_reader(&mut reader, "core", "broken.dic", false)
+      .is_err());
+    assert_eq!(0, read_dir(store.dicts_dir()).unwrap().count());
+  }
+
+  #[test]
+  fn test_install_zip() {
+    let store = setup_store("zip");
+    create_dir_all(store.root()).unwrap();
+    let zip_path = store.root().join("sudachi-dictionary-20200127-core.zip");
+    let mut writer = ZipWriter::new(File::create(&zip_path).unwrap());
+    writer
+      .start_file(
+        "sudachi-dictionary-20200127/system_core.dic",
+        FileOptions::default().compression_method(CompressionMethod::Deflated),
+      )
+      .unwrap();
+    writer
+      .write_all(include_bytes!("../resources/test/system.dic"))
+      .unwrap();
+    writer
+      .start_file("sudachi-dictionary-20200127/LEGAL", FileOptions::default())
+      .unwrap();
+    writer.finish().unwrap();
+
+    let installed = store.install(&zip_path, None, false).unwrap();
+    assert_eq!(1, installed.len());
+    assert_eq!("core", installed[0].name);
+    assert_eq!(Some(String::from("core")), store.current().unwrap());
+    assert!(store.root().join(SYSTEM_DICT_FILE_NAME).is_file());
+
+    store.remove("core").unwrap();
+    assert_eq!(None, store.current().unwrap());
+    assert!(!store.root().join(SYSTEM_DICT_FILE_NAME).exists());
+    assert!(store.remove("core").is_err());
+  }
+}
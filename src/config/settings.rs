@@ -0,0 +1,655 @@
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+const DEFAULT_INPUT_TEXT_PLUGIN_CLASS: &str = "sudachipy.plugin.input_text.DefaultInputTextPlugin";
+const PROLONGED_SOUND_MARK_INPUT_TEXT_PLUGIN_CLASS: &str =
+  "sudachipy.plugin.input_text.ProlongedSoundMarkInputTextPlugin";
+const MECAB_OOV_PROVIDER_PLUGIN_CLASS: &str = "sudachipy.plugin.oov.MeCabOovProviderPlugin";
+const SIMPLE_OOV_PROVIDER_PLUGIN_CLASS: &str = "sudachipy.plugin.oov.SimpleOovProviderPlugin";
+const JOIN_NUMERIC_PLUGIN_CLASS: &str = "sudachipy.plugin.path_rewrite.JoinNumericPlugin";
+const JOIN_KATAKANA_OOV_PLUGIN_CLASS: &str = "sudachipy.plugin.path_rewrite.JoinKatakanaOovPlugin";
+
+/// Keys whose arrays are appended to, not replaced, when settings are merged
+const APPENDED_KEYS: &[&str] = &["userDict"];
+
+const DEFAULT_CHARACTER_DEFINITION_FILE: &str = "char.def";
+const DEFAULT_REPLACEMENT_SYMBOL: &str = "ー";
+
+/// A problem found in a setting file, with the JSON path of the offending value
+#[derive(Error, Debug, PartialEq)]
+pub enum SettingErr {
+  #[error("invalid JSON: {0}")]
+  InvalidJsonErr(String),
+  #[error("`{0}` is required")]
+  MissingKeyErr(String),
+  #[error("`{0}` must be {1}")]
+  InvalidTypeErr(String, &'static str),
+  #[error("`{0}` must be between {1} and {2}")]
+  OutOfRangeErr(String, i64, i64),
+  #[error("`{0}` is an unknown key")]
+  UnknownKeyErr(String),
+  #[error("`{0}` is an unknown plugin class `{1}`")]
+  UnknownClassErr(String, String),
+}
+
+/// Every problem found in a setting file
+#[derive(Error, Debug)]
+#[error("invalid settings:\n  {}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n  "))]
+pub struct SettingsErr(pub Vec<SettingErr>);
+
+/// Content of a setting file (`sudachi.json`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+  pub system_dict: Option<String>,
+  pub user_dict: Vec<String>,
+  pub character_definition_file: String,
+  pub input_text_plugin: Vec<InputTextPluginSettings>,
+  pub oov_provider_plugin: Vec<OovProviderPluginSettings>,
+  pub path_rewrite_plugin: Vec<PathRewritePluginSettings>,
+}
+
+impl Default for Settings {
+  fn default() -> Settings {
+    Settings {
+      system_dict: None,
+      user_dict: vec![],
+      character_definition_file: String::from(DEFAULT_CHARACTER_DEFINITION_FILE),
+      input_text_plugin: vec![],
+      oov_provider_plugin: vec![],
+      path_rewrite_plugin: vec![],
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputTextPluginSettings {
+  DefaultInputTextPlugin,
+  ProlongedSoundMarkInputTextPlugin(ProlongedSoundMarkSettings),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProlongedSoundMarkSettings {
+  pub prolonged_sound_marks: Vec<char>,
+  pub replacement_symbol: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OovProviderPluginSettings {
+  MecabOovPlugin(MecabOovSettings),
+  SimpleOovPlugin(SimpleOovSettings),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MecabOovSettings {
+  pub char_def: String,
+  pub unk_def: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleOovSettings {
+  pub oov_pos: Vec<String>,
+  pub left_id: i16,
+  pub right_id: i16,
+  pub cost: i16,
+}
+
+/// Path rewrite plugins are validated but not applied yet
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathRewritePluginSettings {
+  JoinNumericPlugin {
+    enable_normalize: bool,
+  },
+  JoinKatakanaOovPlugin {
+    oov_pos: Vec<String>,
+    min_length: u64,
+  },
+}
+
+impl FromStr for Settings {
+  type Err = SettingsErr;
+  fn from_str(s: &str) -> Result<Settings, SettingsErr> {
+    match serde_json::from_str(s) {
+      Ok(value) => Settings::from_value(&value),
+      Err(e) => Err(SettingsErr(vec![SettingErr::InvalidJsonErr(e.to_string())])),
+    }
+  }
+}
+
+impl Settings {
+  /// Read settings from a JSON value, collecting every problem instead of stopping at the first
+  pub fn from_value(value: &Value) -> Result<Settings, SettingsErr> {
+    let mut errors = vec![];
+    let settings = read_settings(value, &mut errors);
+    match settings {
+      Some(settings) if errors.is_empty() => Ok(settings),
+      _ => Err(SettingsErr(errors)),
+    }
+  }
+}
+
+/// Merge a layer of settings over `base`
+///
+/// `userDict` is appended to; every other key, including the plugin lists, is replaced.
+pub fn merge_settings(base: &mut Value, layer: Value) {
+  let (base_obj, layer_obj) = match (base.as_object_mut(), layer) {
+    (Some(base_obj), Value::Object(layer_obj)) => (base_obj, layer_obj),
+    (_, layer) => {
+      *base = layer;
+      return;
+    }
+  };
+  for (key, value) in layer_obj {
+    match (base_obj.get_mut(&key), value) {
+      (Some(Value::Array(base_arr)), Value::Array(arr))
+        if APPENDED_KEYS.contains(&key.as_str()) =>
+      {
+        base_arr.extend(arr)
+      }
+      (_, value) => {
+        base_obj.insert(key, value);
+      }
+    }
+  }
+}
+
+/// Resolve the relative file paths in a layer of settings against `dir`
+///
+/// Used for a setting file so that its paths are relative to the file itself.
+pub fn resolve_paths(layer: &mut Value, dir: &Path) {
+  let resolve = |value: &mut Value| {
+    if let Value::String(path) = value {
+      if Path::new(path.as_str()).is_relative() {
+        *path = dir.join(path.as_str()).to_string_lossy().to_string();
+      }
+    }
+  };
+  let obj = match layer.as_object_mut() {
+    Some(obj) => obj,
+    None => return,
+  };
+  for key in &["systemDict", "characterDefinitionFile"] {
+    if let Some(value) = obj.get_mut(*key) {
+      resolve(value);
+    }
+  }
+  if let Some(Value::Array(arr)) = obj.get_mut("userDict") {
+    arr.iter_mut().for_each(resolve);
+  }
+  if let Some(Value::Array(plugins)) = obj.get_mut("oovProviderPlugin") {
+    for plugin in plugins.iter_mut().filter_map(|p| p.as_object_mut()) {
+      for key in &["charDef", "unkDef"] {
+        if let Some(value) = plugin.get_mut(*key) {
+          resolve(value);
+        }
+      }
+    }
+  }
+}
+
+fn read_settings(value: &Value, errors: &mut Vec<SettingErr>) -> Option<Settings> {
+  let mut obj = ObjectReader::new(String::new(), value, errors)?;
+  let defaults = Settings::default();
+  let system_dict = obj.string("systemDict");
+  let user_dict = obj.strings("userDict").unwrap_or_default();
+  let character_definition_file = obj
+    .string("characterDefinitionFile")
+    .unwrap_or(defaults.character_definition_file);
+  let input_text_plugin = obj.plugins("inputTextPlugin", read_input_text_plugin);
+  let oov_provider_plugin = obj.plugins("oovProviderPlugin", read_oov_provider_plugin);
+  let path_rewrite_plugin = obj.plugins("pathRewritePlugin", read_path_rewrite_plugin);
+  obj.finish();
+  Some(Settings {
+    system_dict,
+    user_dict,
+    character_definition_file,
+    input_text_plugin,
+    oov_provider_plugin,
+    path_rewrite_plugin,
+  })
+}
+
+fn read_input_text_plugin(
+  class: &str,
+  obj: &mut ObjectReader,
+) -> Option<Option<InputTextPluginSettings>> {
+  match class {
+    DEFAULT_INPUT_TEXT_PLUGIN_CLASS => Some(Some(InputTextPluginSettings::DefaultInputTextPlugin)),
+    PROLONGED_SOUND_MARK_INPUT_TEXT_PLUGIN_CLASS => {
+      let prolonged_sound_marks = obj.chars("prolongedSoundMarks").unwrap_or_default();
+      let replacement_symbol = obj
+        .string("replacementSymbol")
+        .unwrap_or_else(|| String::from(DEFAULT_REPLACEMENT_SYMBOL));
+      Some(Some(
+        InputTextPluginSettings::ProlongedSoundMarkInputTextPlugin(ProlongedSoundMarkSettings {
+          prolonged_sound_marks,
+          replacement_symbol,
+        }),
+      ))
+    }
+    _ => None,
+  }
+}
+
+fn read_oov_provider_plugin(
+  class: &str,
+  obj: &mut ObjectReader,
+) -> Option<Option<OovProviderPluginSettings>> {
+  match class {
+    MECAB_OOV_PROVIDER_PLUGIN_CLASS => {
+      let char_def = obj.required_string("charDef");
+      let unk_def = obj.required_string("unkDef");
+      Some(char_def.zip(unk_def).map(|(char_def, unk_def)| {
+        OovProviderPluginSettings::MecabOovPlugin(MecabOovSettings { char_def, unk_def })
+      }))
+    }
+    SIMPLE_OOV_PROVIDER_PLUGIN_CLASS => {
+      let oov_pos = obj.required_strings("oovPOS");
+      let left_id = obj.required_integer("leftId", 0, i16::MAX as i64);
+      let right_id = obj.required_integer("rightId", 0, i16::MAX as i64);
+      let cost = obj.required_integer("cost", i16::MIN as i64, i16::MAX as i64);
+      Some(match (oov_pos, left_id, right_id, cost) {
+        (Some(oov_pos), Some(left_id), Some(right_id), Some(cost)) => Some(
+          OovProviderPluginSettings::SimpleOovPlugin(SimpleOovSettings {
+            oov_pos,
+            left_id: left_id as i16,
+            right_id: right_id as i16,
+            cost: cost as i16,
+          }),
+        ),
+        _ => None,
+      })
+    }
+    _ => None,
+  }
+}
+
+fn read_path_rewrite_plugin(
+  class: &str,
+  obj: &mut ObjectReader,
+) -> Option<Option<PathRewritePluginSettings>> {
+  match class {
+    JOIN_NUMERIC_PLUGIN_CLASS => {
+      let enable_normalize = obj.boolean("enableNormalize").unwrap_or(true);
+      Some(Some(PathRewritePluginSettings::JoinNumericPlugin {
+        enable_normalize,
+      }))
+    }
+    JOIN_KATAKANA_OOV_PLUGIN_CLASS => {
+      let oov_pos = obj.required_strings("oovPOS");
+      let min_length = obj.integer("minLength", 0, i64::MAX).unwrap_or(1);
+      Some(
+        oov_pos.map(|oov_pos| PathRewritePluginSettings::JoinKatakanaOovPlugin {
+          oov_pos,
+          min_length: min_length as u64,
+        }),
+      )
+    }
+    _ => None,
+  }
+}
+
+fn join_path<D: Display>(parent: &str, child: D) -> String {
+  if parent.is_empty() {
+    child.to_string()
+  } else {
+    format!("{}.{}", parent, child)
+  }
+}
+
+/// Reads keys of a JSON object and remembers them to report unknown ones
+struct ObjectReader<'a, 'e> {
+  path: String,
+  obj: &'a Map<String, Value>,
+  known_keys: Vec<&'static str>,
+  errors: &'e mut Vec<SettingErr>,
+}
+
+impl<'a, 'e> ObjectReader<'a, 'e> {
+  fn new(
+    path: String,
+    value: &'a Value,
+    errors: &'e mut Vec<SettingErr>,
+  ) -> Option<ObjectReader<'a, 'e>> {
+    match value.as_object() {
+      Some(obj) => Some(ObjectReader {
+        path,
+        obj,
+        known_keys: vec![],
+        errors,
+      }),
+      None => {
+        let path = if path.is_empty() {
+          String::from("$")
+        } else {
+          path
+        };
+        errors.push(SettingErr::InvalidTypeErr(path, "an object"));
+        None
+      }
+    }
+  }
+  fn key_path(&self, key: &str) -> String {
+    join_path(&self.path, key)
+  }
+  /// The value of `key`, where `null` counts as absent
+  fn get(&mut self, key: &'static str) -> Option<&'a Value> {
+    self.known_keys.push(key);
+    self.obj.get(key).filter(|value| !value.is_null())
+  }
+  fn required(&mut self, key: &'static str) -> Option<&'a Value> {
+    let value = self.get(key);
+    if value.is_none() {
+      let path = self.key_path(key);
+      self.errors.push(SettingErr::MissingKeyErr(path));
+    }
+    value
+  }
+  fn invalid_type<T>(&mut self, path: String, expected: &'static str) -> Option<T> {
+    self.errors.push(SettingErr::InvalidTypeErr(path, expected));
+    None
+  }
+  fn as_string(&mut self, path: String, value: &Value) -> Option<String> {
+    match value {
+      Value::String(s) => Some(s.to_string()),
+      _ => self.invalid_type(path, "a string"),
+    }
+  }
+  fn as_strings(&mut self, path: String, value: &Value) -> Option<Vec<String>> {
+    match value {
+      Value::Array(arr) => {
+        let strings: Vec<Option<String>> = arr
+          .iter()
+          .enumerate()
+          .map(|(i, v)| self.as_string(format!("{}[{}]", path, i), v))
+          .collect();
+        strings.into_iter().collect()
+      }
+      _ => self.invalid_type(path, "an array of strings"),
+    }
+  }
+  fn as_integer(&mut self, path: String, value: &Value, min: i64, max: i64) -> Option<i64> {
+    match value.as_i64() {
+      Some(i) if min <= i && i <= max => Some(i),
+      Some(_) => {
+        self.errors.push(SettingErr::OutOfRangeErr(path, min, max));
+        None
+      }
+      None if value.is_u64() => {
+        self.errors.push(SettingErr::OutOfRangeErr(path, min, max));
+        None
+      }
+      None => self.invalid_type(path, "an integer"),
+    }
+  }
+  fn string(&mut self, key: &'static str) -> Option<String> {
+    let value = self.get(key)?;
+    self.as_string(self.key_path(key), value)
+  }
+  fn required_string(&mut self, key: &'static str) -> Option<String> {
+    let value = self.required(key)?;
+    self.as_string(self.key_path(key), value)
+  }
+  fn strings(&mut self, key: &'static str) -> Option<Vec<String>> {
+    let value = self.get(key)?;
+    self.as_strings(self.key_path(key), value)
+  }
+  fn required_strings(&mut self, key: &'static str) -> Option<Vec<String>> {
+    let value = self.required(key)?;
+    self.as_strings(self.key_path(key), value)
+  }
+  fn chars(&mut self, key: &'static str) -> Option<Vec<char>> {
+    let path = self.key_path(key);
+    let strings = self.strings(key)?;
+    let chars: Vec<Option<char>> = strings
+      .iter()
+      .enumerate()
+      .map(|(i, s)| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+          (Some(c), None) => Some(c),
+          _ => self.invalid_type(format!("{}[{}]", path, i), "a single character"),
+        }
+      })
+      .collect();
+    chars.into_iter().collect()
+  }
+  fn integer(&mut self, key: &'static str, min: i64, max: i64) -> Option<i64> {
+    let value = self.get(key)?;
+    self.as_integer(self.key_path(key), value, min, max)
+  }
+  fn required_integer(&mut self, key: &'static str, min: i64, max: i64) -> Option<i64> {
+    let value = self.required(key)?;
+    self.as_integer(self.key_path(key), value, min, max)
+  }
+  fn boolean(&mut self, key: &'static str) -> Option<bool> {
+    match self.get(key)? {
+      Value::Bool(b) => Some(*b),
+      _ => self.invalid_type(self.key_path(key), "a boolean"),
+    }
+  }
+  /// Read an array of plugin objects, each dispatched by its `class`
+  ///
+  /// `read` returns `None` for an unknown class and `Some(None)` for a known class with
+  /// invalid settings.
+  fn plugins<T, F>(&mut self, key: &'static str, read: F) -> Vec<T>
+  where
+    F: Fn(&str, &mut ObjectReader) -> Option<Option<T>>,
+  {
+    let path = self.key_path(key);
+    let arr = match self.get(key) {
+      Some(Value::Array(arr)) => arr,
+      Some(_) => {
+        self
+          .errors
+          .push(SettingErr::InvalidTypeErr(path, "an array"));
+        return vec![];
+      }
+      None => return vec![],
+    };
+    let mut plugins = vec![];
+    for (i, value) in arr.iter().enumerate() {
+      let mut obj = match ObjectReader::new(format!("{}[{}]", path, i), value, self.errors) {
+        Some(obj) => obj,
+        None => continue,
+      };
+      let class = match obj.required_string("class") {
+        Some(class) => class,
+        None => continue,
+      };
+      match read(&class, &mut obj) {
+        Some(Some(plugin)) => plugins.push(plugin),
+        Some(None) => {}
+        None => {
+          let path = obj.key_path("class");
+          obj.errors.push(SettingErr::UnknownClassErr(path, class));
+          continue;
+        }
+      }
+      obj.finish();
+    }
+    plugins
+  }
+  fn finish(self) {
+    let mut unknown_keys: Vec<&String> = self
+      .obj
+      .keys()
+      .filter(|k| !self.known_keys.contains(&k.as_str()))
+      .collect();
+    unknown_keys.sort();
+    for key in unknown_keys {
+      self
+        .errors
+        .push(SettingErr::UnknownKeyErr(join_path(&self.path, key)));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::resources::SUDACHI_JSON;
+  use serde_json::json;
+
+  #[test]
+  fn test_bundled_settings() {
+    let settings = Settings::from_str(SUDACHI_JSON).unwrap();
+    assert_eq!(None, settings.system_dict);
+    assert_eq!("char.def", settings.character_definition_file);
+    assert_eq!(2, settings.input_text_plugin.len());
+    assert_eq!(
+      InputTextPluginSettings::ProlongedSoundMarkInputTextPlugin(ProlongedSoundMarkSettings {
+        prolonged_sound_marks: vec!['ー', '-', '⁓', '〜', '〰'],
+        replacement_symbol: String::from("ー"),
+      }),
+      settings.input_text_plugin[1]
+    );
+    assert_eq!(
+      OovProviderPluginSettings::SimpleOovPlugin(SimpleOovSettings {
+        oov_pos: vec!["補助記号", "一般", "*", "*", "*", "*"]
+          .into_iter()
+          .map(String::from)
+          .collect(),
+        left_id: 5968,
+        right_id: 5968,
+        cost: 3857,
+      }),
+      settings.oov_provider_plugin[0]
+    );
+    assert!(settings.path_rewrite_plugin.is_empty());
+  }
+
+  #[test]
+  fn test_defaults() {
+    let settings = Settings::from_value(&json!({})).unwrap();
+    assert_eq!(Settings::default(), settings);
+    // null is as good as absent
+    let settings = Settings::from_value(
+      &json!({"systemDict": null, "userDict": null, "characterDefinitionFile": null}),
+    )
+    .unwrap();
+    assert_eq!(Settings::default(), settings);
+
+    let settings = Settings::from_value(&json!({"inputTextPlugin": [
+      { "class": PROLONGED_SOUND_MARK_INPUT_TEXT_PLUGIN_CLASS }
+    ]}))
+    .unwrap();
+    assert_eq!(
+      InputTextPluginSettings::ProlongedSoundMarkInputTextPlugin(ProlongedSoundMarkSettings {
+        prolonged_sound_marks: vec![],
+        replacement_symbol: String::from("ー"),
+      }),
+      settings.input_text_plugin[0]
+    );
+  }
+
+  #[test]
+  fn test_all_errors_are_reported() {
+    let err = Settings::from_value(&json!({
+      "userDict": "user.dic",
+      "systemDic": "system.dic",
+      "oovProviderPlugin": [
+        { "class": SIMPLE_OOV_PROVIDER_PLUGIN_CLASS,
+          "leftId": -1,
+          "rightId": "8",
+          "cost": 40000 },
+        { "class": MECAB_OOV_PROVIDER_PLUGIN_CLASS, "charDef": "char.def", "unkdef": "unk.def" },
+        { "class": "sudachipy.plugin.oov.UnknownPlugin" }
+      ],
+      "inputTextPlugin": [
+        { "class": PROLONGED_SOUND_MARK_INPUT_TEXT_PLUGIN_CLASS,
+          "prolongedSoundMarks": ["ー", "〜〜"] }
+      ]
+    }))
+    .unwrap_err();
+    assert_eq!(
+      vec![
+        SettingErr::InvalidTypeErr(String::from("userDict"), "an array of strings"),
+        SettingErr::InvalidTypeErr(
+          String::from("inputTextPlugin[0].prolongedSoundMarks[1]"),
+          "a single character"
+        ),
+        SettingErr::MissingKeyErr(String::from("oovProviderPlugin[0].oovPOS")),
+        SettingErr::OutOfRangeErr(String::from("oovProviderPlugin[0].leftId"), 0, 32767),
+        SettingErr::InvalidTypeErr(String::from("oovProviderPlugin[0].rightId"), "an integer"),
+        SettingErr::OutOfRangeErr(String::from("oovProviderPlugin[0].cost"), -32768, 32767),
+        SettingErr::MissingKeyErr(String::from("oovProviderPlugin[1].unkDef")),
+        SettingErr::UnknownKeyErr(String::from("oovProviderPlugin[1].unkdef")),
+        SettingErr::UnknownClassErr(
+          String::from("oovProviderPlugin[2].class"),
+          String::from("sudachipy.plugin.oov.UnknownPlugin")
+        ),
+        SettingErr::UnknownKeyErr(String::from("systemDic")),
+      ],
+      err.0
+    );
+  }
+
+  #[test]
+  fn test_merge_settings() {
+    let mut settings: Value = serde_json::from_str(SUDACHI_JSON).unwrap();
+    merge_settings(&mut settings, json!({"userDict": ["a.dic"]}));
+    merge_settings(
+      &mut settings,
+      json!({
+        "userDict": ["b.dic"],
+        "oovProviderPlugin": [
+          { "class": MECAB_OOV_PROVIDER_PLUGIN_CLASS, "charDef": "char.def", "unkDef": "unk.def" }
+        ]
+      }),
+    );
+    let settings = Settings::from_value(&settings).unwrap();
+    assert_eq!(vec!["a.dic", "b.dic"], settings.user_dict);
+    assert_eq!(2, settings.input_text_plugin.len());
+    assert_eq!(
+      vec![OovProviderPluginSettings::MecabOovPlugin(
+        MecabOovSettings {
+          char_def: String::from("char.def"),
+          unk_def: String::from("unk.def"),
+        }
+      )],
+      settings.oov_provider_plugin
+    );
+  }
+
+  #[test]
+  fn test_resolve_paths() {
+    let dir = Path::new("/etc/sudachi");
+    let mut layer = json!({
+      "systemDict": "system.dic",
+      "userDict": ["user.dic", "/usr/share/user.dic"],
+      "oovProviderPlugin": [
+        { "class": MECAB_OOV_PROVIDER_PLUGIN_CLASS, "charDef": "char.def", "unkDef": "mecab/unk.def" }
+      ]
+    });
+    resolve_paths(&mut layer, dir);
+    let settings = Settings::from_value(&layer).unwrap();
+    let path = |p: &str| dir.join(p).to_string_lossy().to_string();
+    assert_eq!(Some(path("system.dic")), settings.system_dict);
+    assert_eq!(
+      vec![path("user.dic"), String::from("/usr/share/user.dic")],
+      settings.user_dict
+    );
+    assert_eq!(String::from("char.def"), settings.character_definition_file);
+    assert_eq!(
+      OovProviderPluginSettings::MecabOovPlugin(MecabOovSettings {
+        char_def: path("char.def"),
+        unk_def: path("mecab/unk.def"),
+      }),
+      settings.oov_provider_plugin[0]
+    );
+  }
+
+  #[test]
+  fn test_not_an_object() {
+    let err = Settings::from_value(&json!([])).unwrap_err();
+    assert_eq!(
+      vec![SettingErr::InvalidTypeErr(String::from("$"), "an object")],
+      err.0
+    );
+    assert!(Settings::from_str("{").is_err());
+  }
+}
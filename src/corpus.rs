@@ -0,0 +1,234 @@
+use std::io::{BufRead, Error as IOError};
+use std::path::Path;
+
+use thiserror::Error;
+
+use super::dictionary_lib::grammar::POS_DEPTH;
+
+#[derive(Debug, Error)]
+pub enum CorpusErr {
+  #[error("invalid corpus at line {0}: {1}")]
+  InvalidLineErr(usize, String),
+  #[error("{0}")]
+  IOError(#[from] IOError),
+}
+
+/// A token of a segmented corpus
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusToken {
+  pub surface: String,
+  /// up to 6 levels, empty if the corpus is not POS tagged
+  pub pos: Vec<String>,
+}
+
+impl CorpusToken {
+  /// Whether `pos` agrees with every level of POS this token has
+  pub fn matches_pos(&self, pos: &[String]) -> bool {
+    self
+      .pos
+      .iter()
+      .zip(pos)
+      .all(|(level, other)| level == other)
+  }
+}
+
+/// How the tokens of a corpus are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CorpusFormat {
+  /// one token by line, see `read_corpus`
+  Tokens,
+  /// CoNLL-U, see `read_conllu`
+  Conllu,
+}
+
+impl CorpusFormat {
+  /// CoNLL-U for files named `*.conllu`, one token by line otherwise
+  pub fn from_path<P: AsRef<Path>>(path: P) -> CorpusFormat {
+    match path.as_ref().extension() {
+      Some(extension) if extension == "conllu" => CorpusFormat::Conllu,
+      _ => CorpusFormat::Tokens,
+    }
+  }
+  pub fn read<R: BufRead>(self, reader: R) -> Result<Vec<Vec<CorpusToken>>, CorpusErr> {
+    match self {
+      CorpusFormat::Tokens => read_corpus(reader),
+      CorpusFormat::Conllu => read_conllu(reader),
+    }
+  }
+}
+
+/// The text a sentence of tokens was segmented from
+pub fn sentence_text(tokens: &[CorpusToken]) -> String {
+  tokens.iter().map(|token| token.surface.as_str()).collect()
+}
+
+/// Read sentences of one token by line, as MeCab and `tokenize` write them: the surface, a tab
+/// and the POS levels separated by commas, with any features after the 6th level or the next
+/// tab ignored. A line of `EOS` or an empty line ends a sentence, and a line without a tab is a
+/// token without POS.
+pub fn read_corpus<R: BufRead>(reader: R) -> Result<Vec<Vec<CorpusToken>>, CorpusErr> {
+  let mut sentences = vec![];
+  let mut tokens = vec![];
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    let line = line.trim_end_matches('\r');
+    if line == "EOS" || line.is_empty() {
+      if !tokens.is_empty() {
+        sentences.push(tokens);
+        tokens = vec![];
+      }
+      continue;
+    }
+    let mut fields = line.split('\t');
+    let surface = fields.next().unwrap();
+    if surface.is_empty() {
+      return Err(CorpusErr::InvalidLineErr(
+        i + 1,
+        String::from("empty surface"),
+      ));
+    }
+    let pos = fields.next().map_or(vec![], |features| {
+      features
+        .split(',')
+        .take(POS_DEPTH)
+        .map(String::from)
+        .collect()
+    });
+    tokens.push(CorpusToken {
+      surface: surface.to_string(),
+      pos,
+    });
+  }
+  if !tokens.is_empty() {
+    sentences.push(tokens);
+  }
+  Ok(sentences)
+}
+
+/// Read sentences of CoNLL-U, taking the FORM of every word as its surface, and the XPOS split
+/// by `-` as its POS, or the UPOS if there is no XPOS. Comments, multiword tokens and empty nodes
+/// are skipped, and the spaces between words are not part of the text of a sentence.
+pub fn read_conllu<R: BufRead>(reader: R) -> Result<Vec<Vec<CorpusToken>>, CorpusErr> {
+  let mut sentences = vec![];
+  let mut tokens = vec![];
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    let line = line.trim_end_matches('\r');
+    if line.is_empty() {
+      if !tokens.is_empty() {
+        sentences.push(tokens);
+        tokens = vec![];
+      }
+      continue;
+    }
+    if line.starts_with('#') {
+      continue;
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 5 {
+      return Err(CorpusErr::InvalidLineErr(
+        i + 1,
+        format!("{} columns, not 10", fields.len()),
+      ));
+    }
+    if fields[0].contains('-') || fields[0].contains('.') {
+      continue;
+    }
+    if fields[1].is_empty() {
+      return Err(CorpusErr::InvalidLineErr(
+        i + 1,
+        String::from("empty surface"),
+      ));
+    }
+    let pos = match (fields[3], fields[4]) {
+      (_, xpos) if xpos != "_" => xpos.split('-').take(POS_DEPTH).map(String::from).collect(),
+      (upos, _) if upos != "_" => vec![upos.to_string()],
+      _ => vec![],
+    };
+    tokens.push(CorpusToken {
+      surface: fields[1].to_string(),
+      pos,
+    });
+  }
+  if !tokens.is_empty() {
+    sentences.push(tokens);
+  }
+  Ok(sentences)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_corpus() {
+    let sentences = read_corpus(
+      "東京\t名詞,固有名詞,地名,一般,*,*,トウキョウ\n\
+       都\t名詞,普通名詞\n\
+       EOS\n\
+       \n\
+       行っ\n\
+       た\t助動詞,*,*,*,助動詞-タ,終止形-一般\tた\tた\tタ\r\n"
+        .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(2, sentences.len());
+    assert_eq!("東京都", sentence_text(&sentences[0]));
+    assert_eq!(
+      vec!["名詞", "固有名詞", "地名", "一般", "*", "*"],
+      sentences[0][0].pos
+    );
+    assert!(sentences[1][0].pos.is_empty());
+    assert_eq!("終止形-一般", sentences[1][1].pos[5]);
+
+    assert!(read_corpus("\t名詞\n".as_bytes()).is_err());
+  }
+
+  #[test]
+  fn test_read_conllu() {
+    let sentences = read_conllu(
+      "# sent_id = 1\n\
+       # text = 東京都に行った\n\
+       1\t東京\t東京\tPROPN\t名詞-固有名詞-地名-一般\t_\t3\tcompound\t_\tSpaceAfter=No\n\
+       2\t都\t都\tNOUN\t_\t_\t5\tobl\t_\tSpaceAfter=No\n\
+       3-4\tに行っ\t_\t_\t_\t_\t_\t_\t_\t_\n\
+       3\tに\tに\tADP\t助詞-格助詞\t_\t2\tcase\t_\tSpaceAfter=No\n\
+       4\t行っ\t行く\tVERB\t動詞-非自立可能\t_\t0\troot\t_\tSpaceAfter=No\n\
+       4.1\t_\t_\t_\t_\t_\t_\t_\t_\t_\n\
+       5\tた\tた\tAUX\t_\t_\t4\taux\t_\t_\n\
+       \n\
+       1\t京都\t京都\tPROPN\t名詞-固有名詞\t_\t0\troot\t_\t_\n"
+        .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(2, sentences.len());
+    assert_eq!("東京都に行った", sentence_text(&sentences[0]));
+    assert_eq!(
+      vec!["名詞", "固有名詞", "地名", "一般"],
+      sentences[0][0].pos
+    );
+    assert_eq!(vec!["NOUN"], sentences[0][1].pos);
+    assert_eq!(vec!["AUX"], sentences[0][4].pos);
+
+    assert!(read_conllu("1\t東京\n".as_bytes()).is_err());
+    assert_eq!(
+      CorpusFormat::Conllu,
+      CorpusFormat::from_path("ja_gsd-ud-test.conllu")
+    );
+    assert_eq!(CorpusFormat::Tokens, CorpusFormat::from_path("corpus.txt"));
+  }
+
+  #[test]
+  fn test_matches_pos() {
+    let token = CorpusToken {
+      surface: String::from("都"),
+      pos: vec![String::from("名詞"), String::from("普通名詞")],
+    };
+    let pos: Vec<String> = vec!["名詞", "普通名詞", "一般", "*", "*", "*"]
+      .into_iter()
+      .map(String::from)
+      .collect();
+    assert!(token.matches_pos(&pos));
+    assert!(!token.matches_pos(&pos.iter().rev().cloned().collect::<Vec<String>>()));
+  }
+}
@@ -0,0 +1,407 @@
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::corpus::{sentence_text, CorpusToken};
+use super::dictionary::Dictionary;
+use super::dictionary_lib::binary_dictionary::BinaryDictionary;
+use super::dictionary_lib::double_array_lexicon::{SIGNED_SHORT_MAX, SIGNED_SHORT_MIN};
+use super::dictionary_lib::grammar::{Grammar, INHIBITED_CONNECTION};
+use super::dictionary_lib::lexicon::Lexicon;
+use super::tokenizer::Tokenizer;
+
+/// A cost the training adjusts
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Feature {
+  /// a word of the system dictionary
+  Word(usize),
+  /// the right id of the left node and the left id of the right one
+  Connection(usize, usize),
+}
+
+/// How much a cost moved, and the sum of its moves weighted by when they happened, to average
+/// them over the training
+#[derive(Clone, Copy, Debug, Default)]
+struct Weight {
+  delta: f64,
+  weighted_sum: f64,
+}
+
+struct TrainingNode {
+  start: usize,
+  end: usize,
+  left_id: usize,
+  right_id: usize,
+  cost: i32,
+  /// `None` for OOV and user words, whose costs are not learned
+  word_id: Option<usize>,
+  /// whether the corpus has a token of the same span and POS
+  is_gold: bool,
+}
+
+struct TrainingSentence {
+  /// in the order of their start
+  nodes: Vec<TrainingNode>,
+  length: usize,
+}
+
+/// How the tokenizer did on the training sentences in an epoch, before learning from them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpochResult {
+  pub sentences: usize,
+  /// sentences whose best path was not the one of the corpus
+  pub errors: usize,
+}
+
+impl fmt::Display for EpochResult {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} of {} sentences segmented differently from the corpus",
+      self.errors, self.sentences
+    )
+  }
+}
+
+/// Learns word costs and connection costs from a segmented, POS tagged corpus with the averaged
+/// structured perceptron.
+///
+/// Each sentence is put in the lattice the tokenizer builds for it, and whenever the best path
+/// differs from the path of the corpus tokens, the costs along the corpus path are lowered and
+/// the ones along the best path raised by the learning rate. The costs of the dictionary are the
+/// starting point, so only the ones along the paths of the corpus move. OOV and user words keep
+/// their costs, while the connections they take part in are learned.
+pub struct CostTrainer {
+  tokenizer: Tokenizer,
+  grammar: Arc<Mutex<Grammar>>,
+  sentences: Vec<TrainingSentence>,
+  weights: HashMap<Feature, Weight>,
+  learning_rate: f64,
+  /// sentences learned from, counting from 1
+  steps: usize,
+}
+
+impl CostTrainer {
+  pub fn new(dictionary: &Dictionary) -> CostTrainer {
+    CostTrainer {
+      tokenizer: dictionary.create(),
+      grammar: dictionary.get_grammar(),
+      sentences: vec![],
+      weights: HashMap::new(),
+      learning_rate: 100.0,
+      steps: 1,
+    }
+  }
+  /// How much a cost moves by a mistake (default: 100)
+  pub fn set_learning_rate(&mut self, learning_rate: f64) {
+    self.learning_rate = learning_rate;
+  }
+  /// Add a sentence to learn from, unless no path of its lattice has the spans and POS of
+  /// `tokens`, e.g. because a word is missing from the dictionary
+  pub fn add_sentence(&mut self, tokens: &[CorpusToken]) -> bool {
+    let text = sentence_text(tokens);
+    let input = match self.tokenizer.build_input(&text) {
+      Some(input) if !text.is_empty() => input,
+      _ => return false,
+    };
+    let lattice = self.tokenizer.build_lattice(&input);
+    // in characters of the original text, as the input maps its offsets
+    let mut spans = HashMap::new();
+    let mut offset = 0;
+    for token in tokens {
+      let length = token.surface.chars().count();
+      spans.insert((offset, offset + length), token);
+      offset += length;
+    }
+
+    let mut nodes: Vec<TrainingNode> = {
+      let grammar = self.grammar.lock().unwrap();
+      lattice
+        .get_nodes()
+        .iter()
+        .map(|node| {
+          let node = node.lock().unwrap();
+          let span = (
+            input.get_original_index(node.start),
+            input.get_original_index(node.end),
+          );
+          let pos_id = node.get_word_info().pos_id;
+          let is_gold = spans.get(&span).is_some_and(|token| {
+            pos_id >= 0 && token.matches_pos(grammar.get_part_of_speech_string(pos_id as usize))
+          });
+          let word_id = match node.get_dictionary_id() {
+            Some(0) if !node.is_oov() => Some(node.word_id),
+            _ => None,
+          };
+          TrainingNode {
+            start: node.start,
+            end: node.end,
+            left_id: node.left_id as usize,
+            right_id: node.right_id as usize,
+            cost: node.cost,
+            word_id,
+            is_gold,
+          }
+        })
+        .collect()
+    };
+    nodes.sort_by_key(|node| node.start);
+    let sentence = TrainingSentence {
+      nodes,
+      length: input.get_byte_text().len(),
+    };
+    if self.best_path(&sentence, true).is_none() {
+      return false;
+    }
+    self.sentences.push(sentence);
+    true
+  }
+  /// Go through every sentence once, learning from the ones the current costs get wrong
+  pub fn train_epoch(&mut self) -> EpochResult {
+    let mut errors = 0;
+    let sentences = std::mem::take(&mut self.sentences);
+    for sentence in sentences.iter() {
+      let gold = self.best_path(sentence, true).unwrap();
+      let best = self.best_path(sentence, false).unwrap_or_default();
+      if gold != best {
+        errors += 1;
+        for feature in self.features(sentence, &gold) {
+          self.update(feature, -self.learning_rate);
+        }
+        for feature in self.features(sentence, &best) {
+          self.update(feature, self.learning_rate);
+        }
+      }
+      self.steps += 1;
+    }
+    self.sentences = sentences;
+    EpochResult {
+      sentences: self.sentences.len(),
+      errors,
+    }
+  }
+  /// Write the averaged costs into the system dictionary the training started from, returning
+  /// how many word costs and connection costs changed
+  pub fn apply(&self, dictionary: &mut BinaryDictionary) -> (usize, usize) {
+    let (mut words, mut connections) = (0, 0);
+    for (feature, weight) in self.weights.iter() {
+      let delta = (weight.delta - weight.weighted_sum / self.steps as f64).round() as i32;
+      if delta == 0 {
+        continue;
+      }
+      match *feature {
+        Feature::Word(word_id) => {
+          let cost = dictionary.lexicon.get_cost(word_id) as i32 + delta;
+          // the minimum is left for costs computed on loading
+          let cost = min(
+            max(cost, SIGNED_SHORT_MIN as i32 + 1),
+            SIGNED_SHORT_MAX as i32,
+          );
+          dictionary.lexicon.set_cost(word_id, cost as i16);
+          words += 1;
+        }
+        Feature::Connection(left, right) => {
+          let cost = dictionary.grammar.get_connect_cost(left, right) as i32 + delta;
+          let cost = min(max(cost, i16::MIN as i32), INHIBITED_CONNECTION as i32 - 1);
+          dictionary
+            .grammar
+            .set_connect_cost(left, right, cost as i16);
+          connections += 1;
+        }
+      }
+    }
+    (words, connections)
+  }
+  fn delta(&self, feature: Feature) -> f64 {
+    self
+      .weights
+      .get(&feature)
+      .map_or(0.0, |weight| weight.delta)
+  }
+  fn update(&mut self, feature: Feature, delta: f64) {
+    let steps = self.steps as f64;
+    let weight = self.weights.entry(feature).or_default();
+    weight.delta += delta;
+    weight.weighted_sum += steps * delta;
+  }
+  fn word_cost(&self, node: &TrainingNode) -> f64 {
+    node.cost as f64
+      + node
+        .word_id
+        .map_or(0.0, |word_id| self.delta(Feature::Word(word_id)))
+  }
+  fn connect_cost(&self, grammar: &Grammar, left: usize, right: usize) -> Option<f64> {
+    match grammar.get_connect_cost(left, right) {
+      INHIBITED_CONNECTION => None,
+      cost => Some(cost as f64 + self.delta(Feature::Connection(left, right))),
+    }
+  }
+  /// The nodes of the path of the lowest cost with the current costs, only through the nodes
+  /// of the corpus if `gold`
+  fn best_path(&self, sentence: &TrainingSentence, gold: bool) -> Option<Vec<usize>> {
+    let grammar = self.grammar.lock().unwrap();
+    let bos_right_id = grammar.get_bos_parameter()[1] as usize;
+    let eos_left_id = grammar.get_eos_parameter()[0] as usize;
+    let nodes = &sentence.nodes;
+    // the cost of the best path to each node and the node before it
+    let mut best: Vec<Option<(f64, Option<usize>)>> = vec![None; nodes.len()];
+    let mut ends: Vec<Vec<usize>> = vec![vec![]; sentence.length + 1];
+    for (i, node) in nodes.iter().enumerate() {
+      if gold && !node.is_gold {
+        continue;
+      }
+      let mut candidates = vec![];
+      if node.start == 0 {
+        if let Some(cost) = self.connect_cost(&grammar, bos_right_id, node.left_id) {
+          candidates.push((cost, None));
+        }
+      }
+      for &j in ends[node.start].iter() {
+        if let (Some((cost, _)), Some(connect_cost)) = (
+          best[j],
+          self.connect_cost(&grammar, nodes[j].right_id, node.left_id),
+        ) {
+          candidates.push((cost + connect_cost, Some(j)));
+        }
+      }
+      let previous = candidates.into_iter().fold(
+        None,
+        |best: Option<(f64, Option<usize>)>, candidate| match best {
+          Some(best) if best.0 <= candidate.0 => Some(best),
+          _ => Some(candidate),
+        },
+      );
+      if let Some((cost, previous)) = previous {
+        best[i] = Some((cost + self.word_cost(node), previous));
+        ends[node.end].push(i);
+      }
+    }
+
+    let mut last: Option<(f64, usize)> = None;
+    for &j in ends[sentence.length].iter() {
+      if let (Some((cost, _)), Some(connect_cost)) = (
+        best[j],
+        self.connect_cost(&grammar, nodes[j].right_id, eos_left_id),
+      ) {
+        if last.is_none_or(|(best_cost, _)| cost + connect_cost < best_cost) {
+          last = Some((cost + connect_cost, j));
+        }
+      }
+    }
+    let mut path = vec![];
+    let mut node = last.map(|(_, j)| j);
+    while let Some(i) = node {
+      path.push(i);
+      node = best[i].unwrap().1;
+    }
+    path.reverse();
+    last.map(|_| path)
+  }
+  /// The costs along a path, as many times as they are used
+  fn features(&self, sentence: &TrainingSentence, path: &[usize]) -> Vec<Feature> {
+    let grammar = self.grammar.lock().unwrap();
+    let mut right_id = grammar.get_bos_parameter()[1] as usize;
+    let mut features = vec![];
+    for &i in path {
+      let node = &sentence.nodes[i];
+      features.push(Feature::Connection(right_id, node.left_id));
+      if let Some(word_id) = node.word_id {
+        features.push(Feature::Word(word_id));
+      }
+      right_id = node.right_id;
+    }
+    features.push(Feature::Connection(
+      right_id,
+      grammar.get_eos_parameter()[0] as usize,
+    ));
+    features
+  }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::corpus::read_corpus;
+  use std::path::PathBuf;
+  use std::str::FromStr;
+
+  fn build_dictionary() -> Dictionary {
+    let resource_dir = PathBuf::from_str(file!())
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("resources/test");
+    let config_path = resource_dir.join("sudachi.json");
+    Dictionary::setup(
+      Some(config_path.to_str().unwrap()),
+      Some(resource_dir.to_str().unwrap()),
+      None,
+    )
+    .unwrap()
+  }
+
+  const CORPUS: &str = "\
+東京\t名詞,固有名詞,地名,一般,*,*
+都\t名詞,普通名詞,一般,*,*,*
+に\t助詞,格助詞
+行っ\t動詞,非自立可能,*,*,五段-カ行,連用形-促音便
+た\t助動詞
+EOS
+";
+
+  #[test]
+  fn test_add_sentence() {
+    let dictionary = build_dictionary();
+    let mut trainer = CostTrainer::new(&dictionary);
+    let sentences = read_corpus(CORPUS.as_bytes()).unwrap();
+    assert!(trainer.add_sentence(&sentences[0]));
+    // no word of the dictionary spans 京都に
+    let sentences = read_corpus("東\n京都に\n行った\n".as_bytes()).unwrap();
+    assert!(!trainer.add_sentence(&sentences[0]));
+    // nor is 東京都 a verb
+    let sentences = read_corpus("東京都\t動詞\n".as_bytes()).unwrap();
+    assert!(!trainer.add_sentence(&sentences[0]));
+    assert!(!trainer.add_sentence(&[]));
+  }
+
+  #[test]
+  fn test_train() {
+    let dictionary = build_dictionary();
+    let mut trainer = CostTrainer::new(&dictionary);
+    for sentence in read_corpus(CORPUS.as_bytes()).unwrap() {
+      assert!(trainer.add_sentence(&sentence));
+    }
+    assert_eq!(
+      EpochResult {
+        sentences: 1,
+        errors: 1
+      },
+      trainer.train_epoch()
+    );
+    let mut errors = 1;
+    for _ in 0..20 {
+      errors = trainer.train_epoch().errors;
+      if errors == 0 {
+        break;
+      }
+    }
+    assert_eq!(0, errors);
+
+    let mut system_dictionary =
+      BinaryDictionary::from_system_dictionary_bytes(include_bytes!("resources/test/system.dic"))
+        .unwrap();
+    // 東京都 is word 6, and 東京 and 都 are 5 and 9
+    let costs: Vec<i16> = [5, 6, 9]
+      .iter()
+      .map(|&word_id| system_dictionary.lexicon.get_cost(word_id))
+      .collect();
+    let (words, connections) = trainer.apply(&mut system_dictionary);
+    assert!(words > 0);
+    assert!(connections > 0);
+    assert!(system_dictionary.lexicon.get_cost(5) < costs[0]);
+    assert!(system_dictionary.lexicon.get_cost(6) > costs[1]);
+    assert!(system_dictionary.lexicon.get_cost(9) < costs[2]);
+  }
+}
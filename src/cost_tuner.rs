@@ -0,0 +1,635 @@
+use std::cmp::{max, min};
+use std::fmt;
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use super::corpus::CorpusErr;
+use super::dictionary::{Dictionary, DictionaryErr};
+use super::dictionary_lib::double_array_lexicon::{
+  estimate_cost, SIGNED_SHORT_MAX, SIGNED_SHORT_MIN,
+};
+use super::dictionary_lib::grammar::{Grammar, INHIBITED_CONNECTION};
+use super::tokenizer::Tokenizer;
+
+#[derive(Error, Debug)]
+pub enum TuningErr {
+  #[error("no connection ids to tune with")]
+  NoConnectionIdsErr,
+  #[error(
+    "connection ids {0} {1} out of range, the left id must be below {2} and the right id below {3}"
+  )]
+  InvalidConnectionIdsErr(i16, i16, usize, usize),
+}
+
+/// The lowest cost a word can be given, the minimum is left for costs computed on loading
+const MIN_COST: i64 = SIGNED_SHORT_MIN as i64 + 1;
+const MAX_COST: i64 = SIGNED_SHORT_MAX as i64;
+
+/// A sentence and where the word should be segmented as a unit in it, anywhere else it occurs
+/// it should not
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuningExample {
+  pub text: String,
+  /// in characters
+  pub spans: Vec<(usize, usize)>,
+}
+
+/// Read one example by line, where `[surface]` marks an occurrence of the word that should be
+/// segmented as a unit, e.g. `[東京都]に行った` and `東京都庁に行った` for `東京都`. Empty lines
+/// and lines starting with `#` are skipped.
+pub fn read_examples<R: BufRead>(
+  reader: R,
+  surface: &str,
+) -> Result<Vec<TuningExample>, CorpusErr> {
+  let mark = format!("[{}]", surface);
+  let mut examples = vec![];
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    let line = line.trim_end_matches('\r');
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let mut text = String::new();
+    let mut spans = vec![];
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+      if rest.starts_with(&mark) {
+        let start = text.chars().count();
+        text.push_str(surface);
+        spans.push((start, start + surface.chars().count()));
+        rest = &rest[mark.len()..];
+      } else {
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+      }
+    }
+    if !text.contains(surface) {
+      return Err(CorpusErr::InvalidLineErr(
+        i + 1,
+        format!("no occurrence of {}", surface),
+      ));
+    }
+    examples.push(TuningExample { text, spans });
+  }
+  Ok(examples)
+}
+
+/// The costs with which the best path of an example segments the word as marked, if any
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostRange {
+  pub min: i16,
+  pub max: i16,
+}
+
+impl CostRange {
+  fn contains(&self, cost: i64) -> bool {
+    self.min as i64 <= cost && cost <= self.max as i64
+  }
+}
+
+impl fmt::Display for CostRange {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match (self.min as i64, self.max as i64) {
+      (MIN_COST, MAX_COST) => write!(f, "any cost"),
+      (MIN_COST, max) => write!(f, "a cost of at most {}", max),
+      (min, MAX_COST) => write!(f, "a cost of at least {}", min),
+      (min, max) => write!(f, "a cost between {} and {}", min, max),
+    }
+  }
+}
+
+/// An example the tuned word does not segment as marked
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuningConflict {
+  pub text: String,
+  /// `None` if no cost does with these connection ids
+  pub range: Option<CostRange>,
+}
+
+impl fmt::Display for TuningConflict {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.range {
+      Some(range) => write!(f, "{}: needs {}", self.text, range),
+      None => write!(f, "{}: no cost segments it as marked", self.text),
+    }
+  }
+}
+
+/// The connection ids and cost that segment the most examples as marked
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuningResult {
+  pub left_id: i16,
+  pub right_id: i16,
+  pub cost: i16,
+  /// the costs that segment the same examples as marked
+  pub range: CostRange,
+  /// the cost `calculate_cost` would give the word
+  pub estimated_cost: Option<i16>,
+  pub examples: usize,
+  pub conflicts: Vec<TuningConflict>,
+}
+
+impl fmt::Display for TuningResult {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(
+      f,
+      "connection ids {} {}, cost {}: {} of {} examples segmented as marked, with {}",
+      self.left_id,
+      self.right_id,
+      self.cost,
+      self.examples - self.conflicts.len(),
+      self.examples,
+      self.range
+    )?;
+    if let Some(estimated_cost) = self.estimated_cost {
+      writeln!(f, "estimated cost {}", estimated_cost)?;
+    }
+    for conflict in &self.conflicts {
+      writeln!(f, "conflict: {}", conflict)?;
+    }
+    Ok(())
+  }
+}
+
+struct TuningNode {
+  start: usize,
+  end: usize,
+  left_id: usize,
+  right_id: usize,
+  cost: i64,
+  /// `Some(marked)` for an occurrence of the tuned word, whose cost is left out
+  occurrence: Option<bool>,
+}
+
+struct TuningSentence {
+  text: String,
+  /// in the order of their start
+  nodes: Vec<TuningNode>,
+  length: usize,
+  occurrences: usize,
+  marked: usize,
+}
+
+/// Searches the cost, and the connection ids, of a word for which the tokenizer segments it as a
+/// unit in some example sentences and not in others.
+///
+/// Every path of the lattice of an example costs its other nodes and connections plus the cost
+/// of the word once per occurrence of the word it goes through, so the best path of an example
+/// segments it as marked for a range of costs of the word, computed exactly for each pair of
+/// connection ids. Path rewriting and the splitting of modes are not taken into account.
+pub struct CostTuner {
+  tokenizer: Tokenizer,
+  grammar: Arc<Mutex<Grammar>>,
+  surface: String,
+  pos_id: i16,
+  connection_ids: Vec<(i16, i16)>,
+  sentences: Vec<TuningSentence>,
+}
+
+impl CostTuner {
+  /// A tuner of the word `surface` of `pos`, a POS some system words have
+  pub fn new(
+    dictionary: &Dictionary,
+    surface: &str,
+    pos: &[&str],
+  ) -> Result<CostTuner, DictionaryErr> {
+    if surface.is_empty() {
+      return Err(DictionaryErr::EmptySurfaceErr);
+    }
+    let (pos_id, connection_ids) = dictionary.get_connection_ids(pos)?;
+    Ok(CostTuner {
+      tokenizer: dictionary.create(),
+      grammar: dictionary.get_grammar(),
+      surface: surface.to_string(),
+      pos_id,
+      connection_ids,
+      sentences: vec![],
+    })
+  }
+  /// The (left id, right id) system words of the POS have, the most frequent first
+  pub fn connection_ids(&self) -> &[(i16, i16)] {
+    &self.connection_ids
+  }
+  /// Add an example, unless the input text plugins fail on it
+  pub fn add_example(&mut self, example: &TuningExample) -> bool {
+    let (input, headword) = match (
+      self.tokenizer.build_input(&example.text),
+      self.tokenizer.build_input(&self.surface),
+    ) {
+      (Some(input), Some(headword)) if !example.text.is_empty() => (input, headword),
+      _ => return false,
+    };
+    let lattice = self.tokenizer.build_lattice(&input);
+    let mut nodes: Vec<TuningNode> = lattice
+      .get_nodes()
+      .iter()
+      .filter_map(|node| {
+        let node = node.lock().unwrap();
+        // the word itself, if a dictionary already has it
+        let word_info = node.get_word_info();
+        if word_info.surface == self.surface && word_info.pos_id == self.pos_id {
+          return None;
+        }
+        Some(TuningNode {
+          start: node.start,
+          end: node.end,
+          left_id: node.left_id as usize,
+          right_id: node.right_id as usize,
+          cost: node.cost as i64,
+          occurrence: None,
+        })
+      })
+      .collect();
+
+    let (text, headword) = (input.get_text(), headword.get_text());
+    let length = input.get_byte_text().len();
+    let (mut occurrences, mut marked) = (0, 0);
+    // occurrences may overlap, as the two of 京京 in 京京京
+    let starts = text
+      .char_indices()
+      .map(|(start, _)| start)
+      .filter(|start| text[*start..].starts_with(headword.as_str()));
+    for start in starts {
+      let end = start + headword.len();
+      if !input.can_bow(start) || (end < length && !input.can_bow(end)) {
+        continue;
+      }
+      let span = (
+        input.get_original_index(start),
+        input.get_original_index(end),
+      );
+      let is_marked = example.spans.contains(&span);
+      occurrences += 1;
+      if is_marked {
+        marked += 1;
+      }
+      nodes.push(TuningNode {
+        start,
+        end,
+        left_id: 0,
+        right_id: 0,
+        cost: 0,
+        occurrence: Some(is_marked),
+      });
+    }
+    nodes.sort_by_key(|node| node.start);
+    self.sentences.push(TuningSentence {
+      text: example.text.clone(),
+      nodes,
+      length,
+      occurrences,
+      // a mark where the word cannot be found is never segmented as marked
+      marked: if marked < example.spans.len() {
+        occurrences + 1
+      } else {
+        marked
+      },
+    });
+    true
+  }
+  /// The connection ids among `connection_ids` and the cost that segment the most examples as
+  /// marked, the first connection ids and the cost nearest to the estimated one if several do
+  ///
+  /// Fails if there are no connection ids or some are out of the connection matrix.
+  pub fn tune(&self, connection_ids: &[(i16, i16)]) -> Result<TuningResult, TuningErr> {
+    {
+      let grammar = self.grammar.lock().unwrap();
+      // the left id of a word is the right id of a connection and vice versa
+      let (left_id_size, right_id_size) = (grammar.get_right_id_size(), grammar.get_left_id_size());
+      for &(left_id, right_id) in connection_ids {
+        if left_id < 0
+          || left_id as usize >= left_id_size
+          || right_id < 0
+          || right_id as usize >= right_id_size
+        {
+          return Err(TuningErr::InvalidConnectionIdsErr(
+            left_id,
+            right_id,
+            left_id_size,
+            right_id_size,
+          ));
+        }
+      }
+    }
+    let estimated_cost = estimate_cost(&self.tokenizer, &self.surface);
+    let mut best: Option<TuningResult> = None;
+    for &(left_id, right_id) in connection_ids {
+      let ranges: Vec<Option<CostRange>> = self
+        .sentences
+        .iter()
+        .map(|sentence| self.cost_range(sentence, left_id as usize, right_id as usize))
+        .collect();
+      let (cost, satisfied) =
+        choose_cost(&ranges, max(estimated_cost.unwrap_or(0) as i64, MIN_COST));
+      if best
+        .as_ref()
+        .is_some_and(|best| best.examples - best.conflicts.len() >= satisfied)
+      {
+        continue;
+      }
+      let mut range = CostRange {
+        min: MIN_COST as i16,
+        max: MAX_COST as i16,
+      };
+      let mut conflicts = vec![];
+      for (sentence, example_range) in self.sentences.iter().zip(ranges) {
+        match example_range {
+          Some(example_range) if example_range.contains(cost) => {
+            range.min = max(range.min, example_range.min);
+            range.max = min(range.max, example_range.max);
+          }
+          _ => conflicts.push(TuningConflict {
+            text: sentence.text.clone(),
+            range: example_range,
+          }),
+        }
+      }
+      best = Some(TuningResult {
+        left_id,
+        right_id,
+        cost: cost as i16,
+        range,
+        estimated_cost,
+        examples: self.sentences.len(),
+        conflicts,
+      });
+    }
+    best.ok_or(TuningErr::NoConnectionIdsErr)
+  }
+  /// The costs of the word for which the best path goes through the marked occurrences and no
+  /// other one, with the word taking `left_id` and `right_id`
+  fn cost_range(
+    &self,
+    sentence: &TuningSentence,
+    left_id: usize,
+    right_id: usize,
+  ) -> Option<CostRange> {
+    let grammar = self.grammar.lock().unwrap();
+    let connect_cost = |left: usize, right: usize| match grammar.get_connect_cost(left, right) {
+      INHIBITED_CONNECTION => None,
+      cost => Some(cost as i64),
+    };
+    let bos_right_id = grammar.get_bos_parameter()[1] as usize;
+    let eos_left_id = grammar.get_eos_parameter()[0] as usize;
+    // the lowest cost without the word of the paths to each node, by the occurrences and the
+    // marked occurrences they go through
+    let width = sentence.occurrences + 1;
+    let states = width * width;
+    let nodes = &sentence.nodes;
+    let ids = |node: &TuningNode| match node.occurrence {
+      Some(_) => (left_id, right_id),
+      None => (node.left_id, node.right_id),
+    };
+    let mut best: Vec<Vec<Option<i64>>> = vec![vec![None; states]; nodes.len()];
+    let mut ends: Vec<Vec<usize>> = vec![vec![]; sentence.length + 1];
+    for (i, node) in nodes.iter().enumerate() {
+      let (node_left_id, _) = ids(node);
+      let mut previous: Vec<Option<i64>> = vec![None; states];
+      if node.start == 0 {
+        previous[0] = connect_cost(bos_right_id, node_left_id);
+      }
+      for &j in ends[node.start].iter() {
+        let connect_cost = match connect_cost(ids(&nodes[j]).1, node_left_id) {
+          Some(cost) => cost,
+          None => continue,
+        };
+        for (state, cost) in best[j].iter().enumerate() {
+          if let Some(cost) = cost {
+            let cost = cost + connect_cost;
+            if previous[state].is_none_or(|previous| cost < previous) {
+              previous[state] = Some(cost);
+            }
+          }
+        }
+      }
+      // go through the node
+      let shift = match node.occurrence {
+        Some(true) => width + 1,
+        Some(false) => width,
+        None => 0,
+      };
+      for (state, cost) in previous.into_iter().enumerate() {
+        if let Some(cost) = cost {
+          if state + shift < states {
+            best[i][state + shift] = Some(cost + node.cost);
+          }
+        }
+      }
+      if best[i].iter().any(Option::is_some) {
+        ends[node.end].push(i);
+      }
+    }
+
+    let mut last: Vec<Option<i64>> = vec![None; states];
+    for &j in ends[sentence.length].iter() {
+      let connect_cost = match connect_cost(ids(&nodes[j]).1, eos_left_id) {
+        Some(cost) => cost,
+        None => continue,
+      };
+      for (state, cost) in best[j].iter().enumerate() {
+        if let Some(cost) = cost {
+          let cost = cost + connect_cost;
+          if last[state].is_none_or(|last| cost < last) {
+            last[state] = Some(cost);
+          }
+        }
+      }
+    }
+
+    // C + m * c must be lower than W + k * c for the best wrong path through k occurrences
+    let m = sentence.marked;
+    let correct = if m < width {
+      last[m * width + m]?
+    } else {
+      return None;
+    };
+    let (mut lower, mut upper) = (MIN_COST, MAX_COST);
+    for (state, wrong) in last.iter().enumerate() {
+      let (k, j) = (state / width, state % width);
+      let wrong = match wrong {
+        Some(wrong) if (k, j) != (m, m) => *wrong,
+        _ => continue,
+      };
+      if k < m {
+        let d = (m - k) as i64;
+        upper = min(upper, (wrong - correct - 1).div_euclid(d));
+      } else if k > m {
+        let d = (k - m) as i64;
+        lower = max(lower, (correct - wrong).div_euclid(d) + 1);
+      } else if correct >= wrong {
+        return None;
+      }
+    }
+    if lower > upper {
+      return None;
+    }
+    Some(CostRange {
+      min: lower as i16,
+      max: upper as i16,
+    })
+  }
+}
+
+/// The cost in the most of `ranges`, the nearest to `estimated_cost` if several are, and in how
+/// many ranges it is
+fn choose_cost(ranges: &[Option<CostRange>], estimated_cost: i64) -> (i64, usize) {
+  let mut candidates = vec![estimated_cost];
+  for range in ranges.iter().flatten() {
+    candidates.push(range.min as i64);
+    candidates.push(range.max as i64);
+  }
+  candidates
+    .into_iter()
+    .map(|cost| {
+      let satisfied = ranges
+        .iter()
+        .flatten()
+        .filter(|range| range.contains(cost))
+        .count();
+      (cost, satisfied)
+    })
+    .min_by_key(|&(cost, satisfied)| {
+      (
+        std::cmp::Reverse(satisfied),
+        (cost - estimated_cost).abs(),
+        cost,
+      )
+    })
+    .unwrap()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tokenizer::CanTokenize;
+  use std::path::PathBuf;
+  use std::str::FromStr;
+
+  fn build_dictionary() -> Dictionary {
+    let resource_dir = PathBuf::from_str(file!())
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("resources/test");
+    let config_path = resource_dir.join("sudachi.json");
+    Dictionary::setup(
+      Some(config_path.to_str().unwrap()),
+      Some(resource_dir.to_str().unwrap()),
+      None,
+    )
+    .unwrap()
+  }
+
+  const POS: [&str; 6] = ["名詞", "普通名詞", "一般", "*", "*", "*"];
+
+  #[test]
+  fn test_read_examples() {
+    let examples = read_examples(
+      "# 京都\n[京都]に行った\n\n東京都に[行った]\n".as_bytes(),
+      "京都",
+    )
+    .unwrap();
+    assert_eq!(
+      vec![
+        TuningExample {
+          text: String::from("京都に行った"),
+          spans: vec![(0, 2)],
+        },
+        TuningExample {
+          text: String::from("東京都に[行った]"),
+          spans: vec![],
+        },
+      ],
+      examples
+    );
+    assert!(read_examples("東に行った\n".as_bytes(), "京都").is_err());
+  }
+
+  /// Whether the tokenizer segments 京都 as the word in `text`, with `cost`
+  fn segments(dictionary: &Dictionary, text: &str, cost: i16) -> bool {
+    let word_id = dictionary
+      .add_word("京都", &POS, "キョウト", "京都", Some(cost))
+      .unwrap();
+    let morphemes = dictionary.create().tokenize(text, None, None).unwrap();
+    assert!(dictionary.remove_word(word_id));
+    morphemes
+      .iter()
+      .any(|m| m.surface() == "京都" && m.part_of_speech()[1] == "普通名詞")
+  }
+
+  #[test]
+  fn test_tune() {
+    let dictionary = build_dictionary();
+    let mut tuner = CostTuner::new(&dictionary, "京都", &POS).unwrap();
+    assert_eq!(&[(7, 7), (8, 8)], tuner.connection_ids());
+    for example in read_examples("[京都]に行った\n東京都に行った\n".as_bytes(), "京都").unwrap()
+    {
+      assert!(tuner.add_example(&example));
+    }
+    let result = tuner.tune(&[(7, 7)]).unwrap();
+    assert_eq!(
+      CostRange {
+        min: -2119,
+        max: 5406
+      },
+      result.range
+    );
+    assert_eq!(Some(-20), result.estimated_cost);
+    assert_eq!(-20, result.cost);
+    assert!(result.conflicts.is_empty());
+    for ids in &[(-1, -1), (7, 999), (999, 7)] {
+      match tuner.tune(&[*ids]) {
+        Err(TuningErr::InvalidConnectionIdsErr(..)) => (),
+        _ => panic!("{:?} should be out of range", ids),
+      }
+    }
+    assert!(tuner.tune(&[]).is_err());
+    // a cost one past the range ties with the best path without the word
+    for &(cost, expected) in &[(-2121, true), (-2119, false), (5406, false), (5408, false)] {
+      assert_eq!(expected, segments(&dictionary, "東京都に行った", cost));
+    }
+    for &(cost, expected) in &[(-2121, true), (5406, true), (5408, false)] {
+      assert_eq!(expected, segments(&dictionary, "京都に行った", cost));
+    }
+  }
+
+  #[test]
+  fn test_add_overlapping_occurrences() {
+    let dictionary = build_dictionary();
+    let mut tuner = CostTuner::new(&dictionary, "京京", &POS).unwrap();
+    for example in read_examples("京[京京]\n".as_bytes(), "京京").unwrap() {
+      assert!(tuner.add_example(&example));
+    }
+    let sentence = &tuner.sentences[0];
+    assert_eq!(2, sentence.occurrences);
+    assert_eq!(1, sentence.marked);
+  }
+
+  #[test]
+  fn test_tune_conflicts() {
+    let dictionary = build_dictionary();
+    let mut tuner = CostTuner::new(&dictionary, "京都", &POS).unwrap();
+    let examples = "[京都]に行った\n東京都に行った\n京都に行った\n東京都に行った[京都]\n";
+    for example in read_examples(examples.as_bytes(), "京都").unwrap() {
+      assert!(tuner.add_example(&example));
+    }
+    let result = tuner.tune(tuner.connection_ids()).unwrap();
+    assert_eq!((7, 7), (result.left_id, result.right_id));
+    assert_eq!(4, result.examples);
+    assert_eq!(1, result.conflicts.len());
+    assert_eq!("京都に行った", result.conflicts[0].text);
+    assert_eq!(
+      Some(CostRange {
+        min: 5408,
+        max: SIGNED_SHORT_MAX
+      }),
+      result.conflicts[0].range
+    );
+    assert!(result
+      .to_string()
+      .contains("conflict: 京都に行った: needs a cost of at least 5408"));
+  }
+}
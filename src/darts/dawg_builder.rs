@@ -67,7 +67,7 @@ impl DawgBuilder {
     self.units[id].value()
   }
   pub fn is_leaf(&self, id: usize) -> bool {
-    self.label(id) != 0
+    self.label(id) == 0
   }
   pub fn label(&self, id: usize) -> u8 {
     self.labels[id]
@@ -119,7 +119,7 @@ impl DawgBuilder {
       if child_id == 0 {
         break;
       }
-      let key_label = key[key_pos as usize];
+      let key_label = if key_pos < length { key[key_pos] } else { 0 };
       if key_pos < length && key_label == 0 {
         panic!("failed to insert key: invalid null character");
       }
@@ -319,4 +319,25 @@ mod tests {
     assert_eq!(3_399_731_875, hash(0));
     assert_eq!(316_017_654, hash(1));
   }
+
+  #[test]
+  fn test_insert() {
+    let mut dawg = DawgBuilder::new();
+    dawg.init();
+    dawg.insert(b"a", 1, 1);
+    dawg.insert(b"a", 1, 1);
+    dawg.insert(b"ab", 2, 2);
+    dawg.finish();
+
+    let a = dawg.child(dawg.root());
+    assert_eq!(b'a', dawg.label(a));
+    assert!(!dawg.is_leaf(a));
+    let leaf = dawg.child(a);
+    assert!(dawg.is_leaf(leaf));
+    assert_eq!(1, dawg.value(leaf));
+    let b = dawg.sibling(leaf);
+    assert_eq!(b'b', dawg.label(b));
+    assert!(dawg.is_leaf(dawg.child(b)));
+    assert_eq!(2, dawg.value(dawg.child(b)));
+  }
 }
@@ -143,7 +143,9 @@ impl DoubleArrayBuilder {
       num_units <<= 1;
     }
     self.units.reserve(num_units);
-    // self.extras.reset();
+    self.extras = (0..NUM_EXTRAS)
+      .map(|_| DoubleArrayBuilderExtraUnit::new())
+      .collect();
     self.reserve_id(0);
     self.extras[0].is_used = true;
     self.units[0].set_offset(1);
@@ -270,7 +272,7 @@ impl DoubleArrayBuilder {
       return false;
     }
     let rel_offset = id ^ offset;
-    if rel_offset ^ LOWER_MASK > 0 && rel_offset & UPPER_MASK > 0 {
+    if rel_offset & LOWER_MASK != 0 && rel_offset & UPPER_MASK != 0 {
       return false;
     }
     for i in 1..self.labels.len() {
@@ -294,6 +296,7 @@ impl DoubleArrayBuilder {
     let next = self.get_extra(id).next;
     self.get_extra(prev).next = next;
     self.get_extra(next).prev = prev;
+    self.get_extra(id).is_fixed = true;
   }
   fn expand_units(&mut self) {
     let src_num_units = self.units.len();
@@ -307,7 +310,7 @@ impl DoubleArrayBuilder {
     self.units.resize_with(dest_num_units, Default::default);
 
     if dest_num_blocks > NUM_EXTRA_BLOCKS {
-      for id in src_num_units..dest_num_blocks {
+      for id in src_num_units..dest_num_units {
         let extra = self.get_extra(id);
         extra.is_used = false;
         extra.is_fixed = false;
@@ -341,7 +344,7 @@ impl DoubleArrayBuilder {
   }
   fn fix_block(&mut self, block_id: usize) {
     let start = block_id * BLOCK_SIZE;
-    let end = start * BLOCK_SIZE;
+    let end = start + BLOCK_SIZE;
     let mut unused_offset = 0;
     for offset in start..end {
       if !self.get_extra(offset).is_used {
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::io::Cursor;
 use std::mem::size_of;
 
@@ -48,16 +49,19 @@ impl DoubleArrayTrie {
     let max_num_results = length as u64;
     let mut num_results = 0;
     let mut node_pos: usize = 0;
-    let mut unit = &self.array[node_pos];
-    node_pos ^= unit.offset();
     let mut results = vec![];
+    let mut unit = match self.array.get(node_pos) {
+      Some(unit) => unit,
+      None => return results,
+    };
+    node_pos ^= unit.offset();
     for i in 0..length {
       let i_usize = i as usize;
       node_pos ^= key[i_usize] as usize;
-      unit = &self.array[node_pos];
-      if unit.label() != key[i_usize] {
-        return results;
-      }
+      unit = match self.array.get(node_pos) {
+        Some(unit) if unit.label() == key[i_usize] as u32 => unit,
+        _ => return results,
+      };
       node_pos ^= unit.offset();
       if unit.has_leaf() {
         if num_results < max_num_results {
@@ -68,4 +72,195 @@ impl DoubleArrayTrie {
     }
     results
   }
+  /// Position of the child of `node_pos` labeled `label`
+  fn child(&self, node_pos: usize, label: u8) -> Option<usize> {
+    let child_pos = node_pos ^ self.array[node_pos].offset() ^ label as usize;
+    match self.array.get(child_pos) {
+      Some(unit) if unit.label() == label as u32 => Some(child_pos),
+      _ => None,
+    }
+  }
+  /// Every key under `node_pos` following `prefix`, with its value, in byte order, up to
+  /// `limit` keys
+  fn entries_from(
+    &self,
+    node_pos: usize,
+    prefix: Vec<u8>,
+    limit: Option<usize>,
+  ) -> Vec<(Vec<u8>, i32)> {
+    let mut results = vec![];
+    // a corrupted array may link nodes in a cycle
+    let mut visited = HashSet::new();
+    let mut stack = vec![(node_pos, prefix)];
+    while let Some((node_pos, key)) = stack.pop() {
+      if limit.is_some_and(|limit| results.len() >= limit) {
+        break;
+      }
+      if !visited.insert(node_pos) {
+        continue;
+      }
+      let unit = self.array[node_pos];
+      // empty keys cannot be stored, so the root never holds a value
+      if !key.is_empty() && unit.has_leaf() {
+        results.push((key.clone(), self.array[node_pos ^ unit.offset()].value()));
+      }
+      for label in (1..=255u8).rev() {
+        if let Some(child_pos) = self.child(node_pos, label) {
+          let mut child_key = key.clone();
+          child_key.push(label);
+          stack.push((child_pos, child_key));
+        }
+      }
+    }
+    results
+  }
+  /// Position of the node reached from the root by `key`
+  fn traverse(&self, key: &[u8]) -> Option<usize> {
+    if self.array.is_empty() {
+      return None;
+    }
+    key
+      .iter()
+      .try_fold(0, |node_pos, label| self.child(node_pos, *label))
+  }
+  /// Value of `key` if it is stored
+  pub fn exact_match_search(&self, key: &[u8]) -> Option<i32> {
+    if key.is_empty() {
+      return None;
+    }
+    let node_pos = self.traverse(key)?;
+    let unit = self.array[node_pos];
+    if unit.has_leaf() {
+      Some(self.array[node_pos ^ unit.offset()].value())
+    } else {
+      None
+    }
+  }
+  /// Every key starting with `prefix`, with its value, in byte order
+  ///
+  /// The search stops after the first `limit` keys.
+  pub fn predictive_search(&self, prefix: &[u8], limit: Option<usize>) -> Vec<(Vec<u8>, i32)> {
+    match self.traverse(prefix) {
+      Some(node_pos) => self.entries_from(node_pos, prefix.to_vec(), limit),
+      None => vec![],
+    }
+  }
+  /// Every key with its value, in byte order
+  pub fn entries(&self) -> Vec<(Vec<u8>, i32)> {
+    self.predictive_search(&[], None)
+  }
+  /// The first node holding a value whose value unit is missing or rejected by `is_valid`,
+  /// with the position of that unit
+  ///
+  /// Children are looked up with bounds checks, so values are all the searches rely on.
+  pub fn find_invalid_leaf<F: Fn(i32) -> bool>(&self, is_valid: F) -> Option<(usize, usize)> {
+    self
+      .array
+      .iter()
+      .enumerate()
+      // value units have the top bit set and are no nodes
+      .filter(|(_, unit)| *unit & (1 << 31) == 0 && unit.has_leaf())
+      .map(|(node_pos, unit)| (node_pos, node_pos ^ unit.offset()))
+      .find(|(_, leaf_pos)| match self.array.get(*leaf_pos) {
+        Some(leaf) => leaf & (1 << 31) == 0 || !is_valid(leaf.value()),
+        None => true,
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_build_many_keys() {
+    let keys: Vec<String> = (0..2000).map(|i| format!("{}", i * 7)).collect();
+    let mut keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+    keys.sort();
+    let values: Vec<u32> = (0..keys.len() as u32).collect();
+    let mut trie = DoubleArrayTrie::default();
+    trie.build(&keys, &values);
+    for (i, key) in keys.iter().enumerate() {
+      assert_eq!(
+        Some(&(i as i32, key.len())),
+        trie.common_prefix_search(key).last()
+      );
+    }
+  }
+
+  #[test]
+  fn test_entries() {
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bcd"];
+    let mut trie = DoubleArrayTrie::default();
+    trie.build(&keys, &[1, 2, 3, 4, 5]);
+    let entries = trie.entries();
+    assert_eq!(
+      vec![
+        (b"a".to_vec(), 1),
+        (b"ab".to_vec(), 2),
+        (b"abc".to_vec(), 3),
+        (b"b".to_vec(), 4),
+        (b"bcd".to_vec(), 5),
+      ],
+      entries
+    );
+    assert!(DoubleArrayTrie::default().entries().is_empty());
+  }
+
+  #[test]
+  fn test_exact_match_search() {
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bcd"];
+    let mut trie = DoubleArrayTrie::default();
+    trie.build(&keys, &[1, 2, 3, 4, 5]);
+    assert_eq!(Some(2), trie.exact_match_search(b"ab"));
+    assert_eq!(Some(5), trie.exact_match_search(b"bcd"));
+    assert_eq!(None, trie.exact_match_search(b"bc"));
+    assert_eq!(None, trie.exact_match_search(b"abcd"));
+    assert_eq!(None, trie.exact_match_search(b""));
+    assert_eq!(None, DoubleArrayTrie::default().exact_match_search(b"a"));
+  }
+
+  #[test]
+  fn test_predictive_search() {
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bcd"];
+    let mut trie = DoubleArrayTrie::default();
+    trie.build(&keys, &[1, 2, 3, 4, 5]);
+    assert_eq!(
+      vec![(b"ab".to_vec(), 2), (b"abc".to_vec(), 3)],
+      trie.predictive_search(b"ab", None)
+    );
+    assert_eq!(
+      vec![(b"bcd".to_vec(), 5)],
+      trie.predictive_search(b"bc", None)
+    );
+    assert!(trie.predictive_search(b"c", None).is_empty());
+    assert_eq!(5, trie.predictive_search(b"", None).len());
+    assert_eq!(
+      vec![(b"a".to_vec(), 1), (b"ab".to_vec(), 2)],
+      trie.predictive_search(b"", Some(2))
+    );
+    assert!(trie.predictive_search(b"a", Some(0)).is_empty());
+  }
+
+  #[test]
+  fn test_predictive_search_cycle() {
+    // two nodes labeled 1 that are each other's child
+    let mut trie = DoubleArrayTrie::default();
+    trie.set_array(&[1, 0, 0, 0, 1, 0, 0, 0], 2);
+    assert!(trie.predictive_search(b"", None).is_empty());
+    assert!(trie.predictive_search(&[1, 1, 1], None).is_empty());
+  }
+
+  #[test]
+  fn test_common_prefix_search() {
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bcd"];
+    let mut trie = DoubleArrayTrie::default();
+    trie.build(&keys, &[1, 2, 3, 4, 5]);
+    assert_eq!(
+      vec![(1, 1), (2, 2), (3, 3)],
+      trie.common_prefix_search(b"abcd")
+    );
+    assert_eq!(vec![(4, 1)], trie.common_prefix_search(b"bc"));
+    assert!(trie.common_prefix_search(b"c").is_empty());
+  }
 }
@@ -1,6 +1,6 @@
 pub trait DoubleArrayUnit {
   fn offset(&self) -> usize;
-  fn label(&self) -> u8;
+  fn label(&self) -> u32;
   fn has_leaf(&self) -> bool;
   fn value(&self) -> i32;
 }
@@ -9,8 +9,8 @@ impl DoubleArrayUnit for u32 {
   fn offset(&self) -> usize {
     ((self >> 10) << ((self & (1 << 9)) >> 6)) as usize
   }
-  fn label(&self) -> u8 {
-    (self & ((1 << 31) | 0xFF)) as u8
+  fn label(&self) -> u32 {
+    self & ((1 << 31) | 0xFF)
   }
   fn has_leaf(&self) -> bool {
     ((self >> 8) & 1u32) == 1u32
@@ -19,3 +19,18 @@ impl DoubleArrayUnit for u32 {
     (self & ((1 << 31) - 1)) as i32
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_label() {
+    let unit: u32 = (1 << 10) | b'a' as u32;
+    assert_eq!(b'a' as u32, unit.label());
+    // a value unit never matches a key label
+    let value_unit: u32 = (1 << 31) | b'a' as u32;
+    assert_ne!(b'a' as u32, value_unit.label());
+    assert_eq!(b'a' as i32, value_unit.value());
+  }
+}
@@ -1,28 +1,49 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::io::Error as IOError;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::fs;
+use std::io::{Cursor, Error as IOError};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
 
 use thiserror::Error;
 
+use super::config::settings::Settings;
 use super::config::{Config, ConfigErr, SudachiDictErr};
 use super::dictionary_lib::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
 use super::dictionary_lib::character_category::{CharacterCategory, ReadCharacterDefinitionErr};
-use super::dictionary_lib::grammar::{Grammar, SetCharacterCategory};
+use super::dictionary_lib::dictionary_trailer::SystemDictionaryIdentity;
+use super::dictionary_lib::double_array_lexicon::estimate_cost;
+use super::dictionary_lib::grammar::{GetPartOfSpeech, Grammar, SetCharacterCategory};
+use super::dictionary_lib::lexicon::Size;
 use super::dictionary_lib::lexicon_set::LexiconSet;
+use super::dictionary_lib::memory_lexicon::MemoryLexicon;
+use super::dictionary_lib::word_info::WordInfo;
 use super::plugin::input_text_plugin::{
-  get_input_text_plugins, InputTextPlugin, InputTextPluginGetErr,
+  get_input_text_plugins, InputTextPlugin, InputTextPluginGetErr, InputTextPluginReplaceErr,
+  RewriteInputText,
 };
 use super::plugin::oov_provider_plugin::{
   get_oov_provider_plugins, OovProviderPlugin, OovProviderPluginGetErr,
 };
 use super::plugin::path_rewrite_plugin::PathRewritePlugin;
+use super::resources;
 use super::tokenizer::Tokenizer;
+use super::utf8_input_text_builder::UTF8InputTextBuilder;
 
 #[derive(Error, Debug)]
 pub enum DictionaryErr {
   #[error("too many dictionaries")]
   TooManyDictionariesErr,
+  #[error("empty surface")]
+  EmptySurfaceErr,
+  #[error("unknown POS: {0}")]
+  UnknownPosErr(String),
+  #[error("no word of the system dictionary has the POS {0} to infer connection ids from")]
+  UninferablePosErr(String),
+  #[error("the cost of {0} cannot be computed, give one")]
+  UncomputableCostErr(String),
   #[error("{0}")]
   IOError(#[from] IOError),
   #[error("{0}")]
@@ -37,18 +58,102 @@ pub enum DictionaryErr {
   OovProviderPluginGetErr(#[from] OovProviderPluginGetErr),
   #[error("{0}")]
   ReadCharacterDefinitionErr(#[from] ReadCharacterDefinitionErr),
+  #[error("{0}")]
+  InputTextPluginReplaceErr(#[from] InputTextPluginReplaceErr),
 }
 
 type InputTextPlugins = Arc<Vec<InputTextPlugin>>;
 type OovProviderPlugins = Arc<Vec<OovProviderPlugin>>;
 type PathRewritePlugins = Arc<Vec<PathRewritePlugin>>;
 
-pub struct Dictionary {
+/// The grammar and lexicons tokenizers are created with, replaced as a whole on reloading
+#[derive(Clone)]
+struct Snapshot {
   grammar: Arc<Mutex<Grammar>>,
   lexicon_set: Arc<Mutex<LexiconSet>>,
+  /// what user dictionaries are checked to be built against, if known
+  system_dictionary: Option<SystemDictionaryIdentity>,
+}
+
+struct UserDictionaryFile {
+  path: PathBuf,
+  modified: Option<SystemTime>,
+}
+
+impl UserDictionaryFile {
+  fn read(path: PathBuf) -> Result<(UserDictionaryFile, BinaryDictionary), DictionaryErr> {
+    // taken before reading so that a write during it is seen by the next check
+    let modified = modified_time(&path);
+    let user_dictionary = BinaryDictionary::from_user_dictionary(&path)?;
+    Ok((UserDictionaryFile { path, modified }, user_dictionary))
+  }
+  fn is_modified(&self) -> bool {
+    modified_time(&self.path) != self.modified
+  }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path)
+    .and_then(|metadata| metadata.modified())
+    .ok()
+}
+
+pub struct Dictionary {
+  snapshot: RwLock<Snapshot>,
   input_text_plugins: InputTextPlugins,
   oov_provider_plugins: OovProviderPlugins,
   path_rewrite_plugins: PathRewritePlugins,
+  /// connection ids of the words added at runtime, by POS
+  connection_ids: OnceLock<HashMap<u16, Vec<(i16, i16)>>>,
+  /// also held while reloading so that reloads do not interleave
+  user_dictionary_files: Mutex<Vec<UserDictionaryFile>>,
+}
+
+impl Snapshot {
+  fn add_user_dictionaries(
+    &self,
+    user_dictionaries: Vec<BinaryDictionary>,
+    input_text_plugins: &InputTextPlugins,
+    oov_provider_plugins: &OovProviderPlugins,
+  ) -> Result<(), DictionaryErr> {
+    for user_dictionary in user_dictionaries {
+      {
+        let lexicon_set = self.lexicon_set.lock().unwrap();
+        if lexicon_set.is_full() {
+          return Err(DictionaryErr::TooManyDictionariesErr);
+        }
+        let grammar = self.grammar.lock().unwrap();
+        let system_pos_size = lexicon_set
+          .get_system_pos_size()
+          .unwrap_or_else(|| grammar.get_part_of_speech_size());
+        user_dictionary.validate_user_dictionary(
+          self.system_dictionary.as_ref(),
+          &grammar,
+          system_pos_size,
+          lexicon_set.first().size(),
+        )?;
+      }
+      let mut user_lexicon = user_dictionary.lexicon;
+      let tokenizer = Tokenizer::new(
+        Arc::clone(&self.grammar),
+        Arc::clone(&self.lexicon_set),
+        Arc::clone(input_text_plugins),
+        Arc::clone(oov_provider_plugins),
+        Arc::new(vec![]),
+      );
+      user_lexicon.calculate_cost(&tokenizer);
+      self.lexicon_set.lock().unwrap().add(
+        user_lexicon,
+        self.grammar.lock().unwrap().get_part_of_speech_size(),
+      );
+      self
+        .grammar
+        .lock()
+        .unwrap()
+        .add_pos_list(&user_dictionary.grammar);
+    }
+    Ok(())
+  }
 }
 
 impl Dictionary {
@@ -60,75 +165,358 @@ impl Dictionary {
     path_rewrite_plugins: &PathRewritePlugins,
   ) -> Dictionary {
     Dictionary {
-      grammar: Arc::clone(grammar),
-      lexicon_set: Arc::clone(lexicon_set),
+      snapshot: RwLock::new(Snapshot {
+        grammar: Arc::clone(grammar),
+        lexicon_set: Arc::clone(lexicon_set),
+        system_dictionary: None,
+      }),
       input_text_plugins: Arc::clone(input_text_plugins),
       oov_provider_plugins: Arc::clone(oov_provider_plugins),
       path_rewrite_plugins: Arc::clone(path_rewrite_plugins),
+      connection_ids: OnceLock::new(),
+      user_dictionary_files: Mutex::new(vec![]),
     }
   }
+  fn snapshot(&self) -> Snapshot {
+    self.snapshot.read().unwrap().clone()
+  }
+  fn lexicon_set(&self) -> Arc<Mutex<LexiconSet>> {
+    self.snapshot().lexicon_set
+  }
+  /// The grammar of the current user dictionaries
   pub fn get_grammar(&self) -> Arc<Mutex<Grammar>> {
-    Arc::clone(&self.grammar)
+    self.snapshot().grammar
   }
   pub fn setup(
     config_path: Option<&str>,
     resource_dir: Option<&str>,
     python_exe: Option<&OsStr>,
   ) -> Result<Dictionary, DictionaryErr> {
-    let mut config = Config::setup(config_path, resource_dir)?;
-    let mut system_dictionary =
+    Dictionary::from_config(Config::setup(config_path, resource_dir)?, python_exe)
+  }
+
+  /// Set up a dictionary from a `Config`, e.g. one layered with `Config::builder()`
+  pub fn from_config(
+    mut config: Config,
+    python_exe: Option<&OsStr>,
+  ) -> Result<Dictionary, DictionaryErr> {
+    let system_dictionary =
       Dictionary::read_system_dictionary(config.system_dict_path(python_exe)?)?;
+    let char_category =
+      CharacterCategory::read_character_definition_from_reader(&mut config.open_char_def()?)?;
+    let mut user_dictionary_files = vec![];
+    let mut user_dictionaries = vec![];
+    for user_dict_path in config.user_dict_paths() {
+      let (file, user_dictionary) = UserDictionaryFile::read(user_dict_path)?;
+      user_dictionary_files.push(file);
+      user_dictionaries.push(user_dictionary);
+    }
+    let dictionary =
+      Dictionary::from_parts(&config, system_dictionary, char_category, user_dictionaries)?;
+    *dictionary.user_dictionary_files.lock().unwrap() = user_dictionary_files;
+    Ok(dictionary)
+  }
+
+  /// Build a dictionary from in-memory resources without touching the filesystem.
+  ///
+  /// `settings` is the content of a setting file in JSON format; the bundled one is used if
+  /// `None`. Its `systemDict`, `userDict` and `characterDefinitionFile` entries are ignored
+  /// in favor of the given bytes.
+  pub fn from_bytes(
+    system_dic: &[u8],
+    char_def: &[u8],
+    unk_def: &[u8],
+    rewrite_def: &[u8],
+    user_dics: &[&[u8]],
+    settings: Option<&str>,
+  ) -> Result<Dictionary, DictionaryErr> {
+    let settings =
+      Settings::from_str(settings.unwrap_or(resources::SUDACHI_JSON)).map_err(ConfigErr::from)?;
+    let mut resources = HashMap::new();
+    resources.insert(String::from("char.def"), char_def.to_vec());
+    resources.insert(String::from("unk.def"), unk_def.to_vec());
+    resources.insert(String::from("rewrite.def"), rewrite_def.to_vec());
+    let config = Config::from_memory(settings, resources);
 
-    let char_category = Dictionary::read_character_definition(config.char_def_path()?)?;
+    let system_dictionary = BinaryDictionary::from_system_dictionary_bytes(system_dic)?;
+    let char_category =
+      CharacterCategory::read_character_definition_from_reader(&mut Cursor::new(char_def))?;
+    let mut user_dictionaries = vec![];
+    for user_dic in user_dics {
+      user_dictionaries.push(BinaryDictionary::from_user_dictionary_bytes(user_dic)?);
+    }
+    Dictionary::from_parts(&config, system_dictionary, char_category, user_dictionaries)
+  }
+
+  fn from_parts(
+    config: &Config,
+    mut system_dictionary: BinaryDictionary,
+    char_category: CharacterCategory,
+    user_dictionaries: Vec<BinaryDictionary>,
+  ) -> Result<Dictionary, DictionaryErr> {
     system_dictionary
       .grammar
       .set_character_category(Some(char_category));
+    let system_identity = SystemDictionaryIdentity::of(&system_dictionary);
 
     let lexicon_set = Arc::new(Mutex::new(LexiconSet::new(system_dictionary.lexicon)));
     let grammar = Arc::new(Mutex::new(system_dictionary.grammar));
 
-    let input_text_plugins = Arc::new(get_input_text_plugins(&config)?);
+    let input_text_plugins = Arc::new(get_input_text_plugins(config)?);
 
-    let oov_provider_plugins = Arc::new(get_oov_provider_plugins(&config, Arc::clone(&grammar))?);
+    let oov_provider_plugins = Arc::new(get_oov_provider_plugins(config, Arc::clone(&grammar))?);
 
     let path_rewrite_plugins: Vec<PathRewritePlugin> = vec![];
     let path_rewrite_plugins = Arc::new(path_rewrite_plugins);
 
-    for user_dict_path in config.user_dict_paths() {
-      let user_dictionary = Dictionary::read_user_dictionary(user_dict_path, &lexicon_set)?;
-
-      let mut user_lexicon = user_dictionary.lexicon;
-      let tokenizer = Tokenizer::new(
-        Arc::clone(&grammar),
-        Arc::clone(&lexicon_set),
-        Arc::clone(&input_text_plugins),
-        Arc::clone(&oov_provider_plugins),
-        Arc::new(vec![]),
-      );
-      user_lexicon.calculate_cost(&tokenizer);
-      lexicon_set.lock().unwrap().add(
-        user_lexicon,
-        grammar.lock().unwrap().get_part_of_speech_size(),
-      );
-      grammar
-        .lock()
-        .unwrap()
-        .add_pos_list(&user_dictionary.grammar);
-    }
+    let snapshot = Snapshot {
+      grammar,
+      lexicon_set,
+      system_dictionary: Some(system_identity),
+    };
+    snapshot.add_user_dictionaries(
+      user_dictionaries,
+      &input_text_plugins,
+      &oov_provider_plugins,
+    )?;
 
-    Ok(Dictionary::new(
-      &grammar,
-      &lexicon_set,
+    let mut dictionary = Dictionary::new(
+      &snapshot.grammar,
+      &snapshot.lexicon_set,
       &input_text_plugins,
       &oov_provider_plugins,
       &path_rewrite_plugins,
-    ))
+    );
+    *dictionary.snapshot.get_mut().unwrap() = snapshot;
+    Ok(dictionary)
+  }
+
+  /// Read the user dictionary files again.
+  ///
+  /// Tokenizers created from now on use the new words, while the ones created before keep the
+  /// dictionaries they were created with. Words added with `add_word` are kept with their word
+  /// ids.
+  pub fn reload_user_dictionaries(&self) -> Result<(), DictionaryErr> {
+    let mut files = self.user_dictionary_files.lock().unwrap();
+    let paths = files.iter().map(|file| file.path.clone()).collect();
+    self.read_user_dictionary_files(&mut files, paths)
+  }
+  /// Reload the user dictionaries if any of their files changed since they were read,
+  /// returning whether they were
+  pub fn reload_if_modified(&self) -> Result<bool, DictionaryErr> {
+    let mut files = self.user_dictionary_files.lock().unwrap();
+    if !files.iter().any(|file| file.is_modified()) {
+      return Ok(false);
+    }
+    let paths = files.iter().map(|file| file.path.clone()).collect();
+    self.read_user_dictionary_files(&mut files, paths)?;
+    Ok(true)
+  }
+  /// Replace the user dictionaries with the files at `paths`, as `reload_user_dictionaries`
+  pub fn set_user_dictionary_paths<P: AsRef<Path>>(
+    &self,
+    paths: &[P],
+  ) -> Result<(), DictionaryErr> {
+    let mut files = self.user_dictionary_files.lock().unwrap();
+    let paths = paths
+      .iter()
+      .map(|path| path.as_ref().to_path_buf())
+      .collect();
+    self.read_user_dictionary_files(&mut files, paths)
+  }
+  /// Replace the user dictionaries with in-memory ones, as `reload_user_dictionaries`
+  pub fn set_user_dictionary_bytes(&self, user_dics: &[&[u8]]) -> Result<(), DictionaryErr> {
+    let mut files = self.user_dictionary_files.lock().unwrap();
+    let mut user_dictionaries = vec![];
+    for user_dic in user_dics {
+      user_dictionaries.push(BinaryDictionary::from_user_dictionary_bytes(user_dic)?);
+    }
+    self.replace_user_dictionaries(user_dictionaries)?;
+    files.clear();
+    Ok(())
+  }
+  fn read_user_dictionary_files(
+    &self,
+    files: &mut Vec<UserDictionaryFile>,
+    paths: Vec<PathBuf>,
+  ) -> Result<(), DictionaryErr> {
+    let mut new_files = vec![];
+    let mut user_dictionaries = vec![];
+    for path in paths {
+      let (file, user_dictionary) = UserDictionaryFile::read(path)?;
+      new_files.push(file);
+      user_dictionaries.push(user_dictionary);
+    }
+    self.replace_user_dictionaries(user_dictionaries)?;
+    *files = new_files;
+    Ok(())
+  }
+  fn replace_user_dictionaries(
+    &self,
+    user_dictionaries: Vec<BinaryDictionary>,
+  ) -> Result<(), DictionaryErr> {
+    let snapshot = {
+      let current = self.snapshot();
+      let lexicon_set = current.lexicon_set.lock().unwrap();
+      let mut grammar = current.grammar.lock().unwrap().clone();
+      let system_pos_size = lexicon_set
+        .get_system_pos_size()
+        .unwrap_or_else(|| grammar.get_part_of_speech_size());
+      grammar.truncate_pos_list(system_pos_size);
+      Snapshot {
+        grammar: Arc::new(Mutex::new(grammar)),
+        lexicon_set: Arc::new(Mutex::new(lexicon_set.system_only())),
+        system_dictionary: current.system_dictionary.clone(),
+      }
+    };
+    snapshot.add_user_dictionaries(
+      user_dictionaries,
+      &self.input_text_plugins,
+      &self.oov_provider_plugins,
+    )?;
+
+    let mut current = self.snapshot.write().unwrap();
+    if let Some(memory_lexicon) = current.lexicon_set.lock().unwrap().get_memory_lexicon() {
+      let mut lexicon_set = snapshot.lexicon_set.lock().unwrap();
+      if lexicon_set.is_full() {
+        return Err(DictionaryErr::TooManyDictionariesErr);
+      }
+      lexicon_set.add_memory_lexicon(memory_lexicon.clone());
+    }
+    *current = snapshot;
+    Ok(())
+  }
+
+  /// Every word whose headword is exactly `headword`, with its word id
+  pub fn exact_lookup(&self, headword: &str) -> Vec<(usize, WordInfo)> {
+    self.lexicon_set().lock().unwrap().exact_lookup(headword)
+  }
+  /// Every word read as `reading_form`, with its word id
+  pub fn lookup_by_reading_form(&self, reading_form: &str) -> Vec<(usize, WordInfo)> {
+    self
+      .lexicon_set()
+      .lock()
+      .unwrap()
+      .lookup_by_reading_form(reading_form)
+  }
+  /// Every word normalized to `normalized_form`, with its word id
+  pub fn lookup_by_normalized_form(&self, normalized_form: &str) -> Vec<(usize, WordInfo)> {
+    self
+      .lexicon_set()
+      .lock()
+      .unwrap()
+      .lookup_by_normalized_form(normalized_form)
   }
+  /// The word `word_id` and every inflected entry whose dictionary form it is
+  pub fn lookup_by_dictionary_form(&self, word_id: usize) -> Vec<(usize, WordInfo)> {
+    self
+      .lexicon_set()
+      .lock()
+      .unwrap()
+      .lookup_by_dictionary_form(word_id)
+  }
+  /// Every headword starting with `prefix` with each of its word ids, in byte order, up to
+  /// `limit` words
+  pub fn predictive_lookup(&self, prefix: &str, limit: Option<usize>) -> Vec<(String, usize)> {
+    self
+      .lexicon_set()
+      .lock()
+      .unwrap()
+      .predictive_lookup(prefix, limit)
+  }
+
+  /// Add a word that every tokenizer of this dictionary finds from now on and return its
+  /// word id.
+  ///
+  /// Its connection ids are the ones most system words of `pos` have. Its cost is computed
+  /// as for user dictionaries if `None`, and an error if that fails. The word is not split in
+  /// any mode.
+  pub fn add_word(
+    &self,
+    surface: &str,
+    pos: &[&str],
+    reading_form: &str,
+    normalized_form: &str,
+    cost: Option<i16>,
+  ) -> Result<usize, DictionaryErr> {
+    if surface.is_empty() {
+      return Err(DictionaryErr::EmptySurfaceErr);
+    }
+    let snapshot = self.snapshot();
+    let (pos_id, connection_ids) = self.get_connection_ids(pos)?;
+    let (left_id, right_id) = connection_ids[0];
+    let mut builder = UTF8InputTextBuilder::new(surface, Arc::clone(&snapshot.grammar));
+    for plugin in self.input_text_plugins.iter() {
+      plugin.rewrite(&mut builder)?;
+    }
+    let headword = builder.get_text();
+    let cost = match cost {
+      Some(cost) => cost,
+      None => estimate_cost(&self.create(), surface)
+        .ok_or_else(|| DictionaryErr::UncomputableCostErr(surface.to_string()))?,
+    };
+    let word_info = WordInfo {
+      surface: surface.to_string(),
+      head_word_length: headword.len(),
+      pos_id,
+      normalized_form: normalized_form.to_string(),
+      dictionary_form_word_id: -1,
+      dictionary_form: surface.to_string(),
+      reading_form: reading_form.to_string(),
+      a_unit_split: vec![],
+      b_unit_split: vec![],
+      word_structure: vec![],
+    };
 
+    // the read lock keeps a reload from copying the words before this one is added
+    let current = self.snapshot.read().unwrap();
+    let mut lexicon_set = current.lexicon_set.lock().unwrap();
+    if !lexicon_set.has_memory_lexicon() {
+      if lexicon_set.is_full() {
+        return Err(DictionaryErr::TooManyDictionariesErr);
+      }
+      lexicon_set.add_memory_lexicon(MemoryLexicon::new());
+    }
+    Ok(
+      lexicon_set
+        .add_word(&headword, left_id, right_id, cost, word_info)
+        .unwrap(),
+    )
+  }
+  /// The id of `pos` and every (left id, right id) system words of `pos` have, the most
+  /// frequent first
+  pub fn get_connection_ids(&self, pos: &[&str]) -> Result<(i16, Vec<(i16, i16)>), DictionaryErr> {
+    let snapshot = self.snapshot();
+    let pos_id = snapshot
+      .grammar
+      .lock()
+      .unwrap()
+      .get_part_of_speech_id(pos)
+      .ok_or_else(|| DictionaryErr::UnknownPosErr(pos.join(",")))?;
+    let connection_ids = self
+      .connection_ids
+      .get_or_init(|| {
+        snapshot
+          .lexicon_set
+          .lock()
+          .unwrap()
+          .first()
+          .get_connection_ids_by_frequency()
+      })
+      .get(&(pos_id as u16))
+      .cloned()
+      .ok_or_else(|| DictionaryErr::UninferablePosErr(pos.join(",")))?;
+    Ok((pos_id as i16, connection_ids))
+  }
+  /// Remove a word added by `add_word`, returning whether it was there
+  pub fn remove_word(&self, word_id: usize) -> bool {
+    self.lexicon_set().lock().unwrap().remove_word(word_id)
+  }
   pub fn create(&self) -> Tokenizer {
+    let snapshot = self.snapshot();
     Tokenizer::new(
-      Arc::clone(&self.grammar),
-      Arc::clone(&self.lexicon_set),
+      snapshot.grammar,
+      snapshot.lexicon_set,
       Arc::clone(&self.input_text_plugins),
       Arc::clone(&self.oov_provider_plugins),
       Arc::clone(&self.path_rewrite_plugins),
@@ -159,3 +547,376 @@ impl Dictionary {
     Ok(char_category)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::dictionary_trailer::DictionaryTrailer;
+  use crate::tokenizer::CanTokenize;
+
+  fn build_dictionary_from_bytes() -> Dictionary {
+    Dictionary::from_bytes(
+      include_bytes!("resources/test/system.dic"),
+      include_bytes!("resources/test/char.def"),
+      include_bytes!("resources/test/unk.def"),
+      include_bytes!("resources/test/rewrite.def"),
+      &[include_bytes!("resources/test/user.dic")],
+      Some(include_str!("resources/test/sudachi.json")),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_from_bytes() {
+    let dictionary = build_dictionary_from_bytes();
+    let tokenizer = dictionary.create();
+
+    let morpheme_list = tokenizer.tokenize("京都", None, None).unwrap();
+    assert_eq!(1, morpheme_list.len());
+    assert_eq!(Some(0), morpheme_list.get(0).unwrap().dictionary_id());
+
+    let morpheme_list = tokenizer.tokenize("ぴらる", None, None).unwrap();
+    assert_eq!(1, morpheme_list.len());
+    assert_eq!(Some(1), morpheme_list.get(0).unwrap().dictionary_id());
+  }
+
+  #[test]
+  fn test_exact_lookup() {
+    let dictionary = build_dictionary_from_bytes();
+    let words = dictionary.exact_lookup("東京府");
+    assert_eq!(1, words.len());
+    assert_eq!(1, words[0].0 >> 28);
+    assert_eq!("東京府", words[0].1.surface);
+
+    let words = dictionary.exact_lookup("に");
+    assert_eq!(
+      vec![1, 2],
+      words.iter().map(|(id, _)| *id).collect::<Vec<usize>>()
+    );
+    assert!(dictionary.exact_lookup("東京府に").is_empty());
+  }
+
+  #[test]
+  fn test_reverse_lookup() {
+    let dictionary = build_dictionary_from_bytes();
+    let surfaces = |words: Vec<(usize, WordInfo)>| {
+      words
+        .into_iter()
+        .map(|(_, word_info)| word_info.surface)
+        .collect::<Vec<String>>()
+    };
+    assert_eq!(
+      vec!["東京都"],
+      surfaces(dictionary.lookup_by_reading_form("トウキョウト"))
+    );
+    assert_eq!(
+      vec!["行く", "行っ", "いく", "いっ"],
+      surfaces(dictionary.lookup_by_normalized_form("行く"))
+    );
+    assert_eq!(
+      vec!["行く", "行っ"],
+      surfaces(dictionary.lookup_by_dictionary_form(7))
+    );
+    assert!(dictionary
+      .lookup_by_dictionary_form(1 << 28 | 0x0FFF_FFFF)
+      .is_empty());
+  }
+
+  #[test]
+  fn test_predictive_lookup() {
+    let dictionary = build_dictionary_from_bytes();
+    let headwords: Vec<String> = dictionary
+      .predictive_lookup("東京", None)
+      .into_iter()
+      .map(|(headword, _)| headword)
+      .collect();
+    assert_eq!(vec!["東京", "東京府", "東京都"], headwords);
+    let headwords: Vec<String> = dictionary
+      .predictive_lookup("東京", Some(2))
+      .into_iter()
+      .map(|(headword, _)| headword)
+      .collect();
+    assert_eq!(vec!["東京", "東京府"], headwords);
+    assert!(dictionary.predictive_lookup("あれ", None).is_empty());
+  }
+
+  #[test]
+  fn test_add_and_remove_word() {
+    let dictionary = build_dictionary_from_bytes();
+    let tokenizer = dictionary.create();
+    let tokenize = |text: &str| {
+      tokenizer
+        .tokenize(text, None, None)
+        .unwrap()
+        .iter()
+        .map(|m| (m.surface(), m.dictionary_id()))
+        .collect::<Vec<(String, Option<usize>)>>()
+    };
+    let unknown: Vec<(String, Option<usize>)> = vec![
+      (String::from("ぽ"), None),
+      (String::from("ん"), None),
+      (String::from("ず"), None),
+      (String::from("に"), Some(0)),
+    ];
+    assert_eq!(unknown, tokenize("ぽんずに"));
+
+    let pos = dictionary.exact_lookup("京都")[0].1.pos_id as usize;
+    let pos = dictionary
+      .get_grammar()
+      .lock()
+      .unwrap()
+      .get_part_of_speech_string(pos)
+      .clone();
+    let pos: Vec<&str> = pos.iter().map(|p| p.as_str()).collect();
+    let word_id = dictionary
+      .add_word("ぽんず", &pos, "ポンズ", "ポン酢", None)
+      .unwrap();
+    assert_eq!(15, word_id >> 28);
+    assert_eq!(
+      vec![
+        (String::from("ぽんず"), Some(15)),
+        (String::from("に"), Some(0))
+      ],
+      tokenize("ぽんずに")
+    );
+    let morpheme_list = tokenizer.tokenize("ぽんず", None, None).unwrap();
+    let morpheme = morpheme_list.get(0).unwrap();
+    assert_eq!(word_id, morpheme.get_word_id());
+    assert_eq!("ポン酢", morpheme.normalized_form());
+    assert_eq!(pos, morpheme.part_of_speech());
+    assert_eq!(
+      vec![word_id],
+      dictionary
+        .lookup_by_reading_form("ポンズ")
+        .into_iter()
+        .map(|(word_id, _)| word_id)
+        .collect::<Vec<usize>>()
+    );
+
+    assert!(dictionary.remove_word(word_id));
+    assert!(!dictionary.remove_word(word_id));
+    assert!(!dictionary.remove_word(7));
+    assert_eq!(unknown, tokenize("ぽんずに"));
+    assert!(dictionary.exact_lookup("ぽんず").is_empty());
+    // morphemes tokenized before the removal keep their word
+    assert_eq!("ポン酢", morpheme.normalized_form());
+  }
+
+  fn dictionary_id(tokenizer: &Tokenizer, text: &str) -> Option<usize> {
+    tokenizer
+      .tokenize(text, None, None)
+      .unwrap()
+      .get(0)
+      .unwrap()
+      .dictionary_id()
+  }
+
+  #[test]
+  fn test_set_user_dictionary_bytes() {
+    let dictionary = build_dictionary_from_bytes();
+    let pos_size = dictionary
+      .get_grammar()
+      .lock()
+      .unwrap()
+      .get_part_of_speech_size();
+    let pos = dictionary.exact_lookup("京都")[0].1.pos_id as usize;
+    let pos = dictionary
+      .get_grammar()
+      .lock()
+      .unwrap()
+      .get_part_of_speech_string(pos)
+      .clone();
+    let pos: Vec<&str> = pos.iter().map(|p| p.as_str()).collect();
+    let word_id = dictionary
+      .add_word("ぽんず", &pos, "ポンズ", "ポン酢", Some(0))
+      .unwrap();
+    assert_eq!(15, word_id >> 28);
+    let before = dictionary.create();
+
+    dictionary.set_user_dictionary_bytes(&[]).unwrap();
+    let after = dictionary.create();
+    assert_eq!(Some(1), dictionary_id(&before, "ぴらる"));
+    assert_ne!(Some(1), dictionary_id(&after, "ぴらる"));
+    assert!(dictionary.exact_lookup("東京府").is_empty());
+    assert!(
+      dictionary
+        .get_grammar()
+        .lock()
+        .unwrap()
+        .get_part_of_speech_size()
+        < pos_size
+    );
+    // words added at runtime are kept with their word ids
+    assert_eq!(Some(15), dictionary_id(&after, "ぽんず"));
+    assert_eq!(
+      vec![word_id],
+      dictionary
+        .exact_lookup("ぽんず")
+        .into_iter()
+        .map(|(word_id, _)| word_id)
+        .collect::<Vec<usize>>()
+    );
+
+    dictionary
+      .set_user_dictionary_bytes(&[include_bytes!("resources/test/user.dic")])
+      .unwrap();
+    let tokenizer = dictionary.create();
+    assert_eq!(Some(1), dictionary_id(&tokenizer, "ぴらる"));
+    assert_eq!(Some(15), dictionary_id(&tokenizer, "ぽんず"));
+    assert_eq!(
+      pos_size,
+      dictionary
+        .get_grammar()
+        .lock()
+        .unwrap()
+        .get_part_of_speech_size()
+    );
+
+    assert!(dictionary.remove_word(word_id));
+    assert!(dictionary.exact_lookup("ぽんず").is_empty());
+    assert_ne!(Some(15), dictionary_id(&dictionary.create(), "ぽんず"));
+  }
+
+  #[test]
+  fn test_reload_if_modified() {
+    let dir = std::env::temp_dir().join("test_dictionary_reload");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("user.dic");
+    fs::write(&path, &include_bytes!("resources/test/user.dic")[..]).unwrap();
+
+    let dictionary = Dictionary::from_bytes(
+      include_bytes!("resources/test/system.dic"),
+      include_bytes!("resources/test/char.def"),
+      include_bytes!("resources/test/unk.def"),
+      include_bytes!("resources/test/rewrite.def"),
+      &[],
+      Some(include_str!("resources/test/sudachi.json")),
+    )
+    .unwrap();
+    assert!(!dictionary.reload_if_modified().unwrap());
+    dictionary.set_user_dictionary_paths(&[&path]).unwrap();
+    assert_eq!(Some(1), dictionary_id(&dictionary.create(), "ぴらる"));
+    assert!(!dictionary.reload_if_modified().unwrap());
+
+    let modified = SystemTime::now() - std::time::Duration::from_secs(60);
+    fs::File::options()
+      .write(true)
+      .open(&path)
+      .unwrap()
+      .set_modified(modified)
+      .unwrap();
+    assert!(dictionary.reload_if_modified().unwrap());
+    assert!(!dictionary.reload_if_modified().unwrap());
+    assert_eq!(Some(1), dictionary_id(&dictionary.create(), "ぴらる"));
+
+    dictionary.reload_user_dictionaries().unwrap();
+    assert_eq!(Some(1), dictionary_id(&dictionary.create(), "ぴらる"));
+
+    fs::remove_file(&path).unwrap();
+    assert!(dictionary.reload_if_modified().is_err());
+    // the dictionaries read last are still used
+    assert_eq!(Some(1), dictionary_id(&dictionary.create(), "ぴらる"));
+  }
+
+  #[test]
+  fn test_add_word_errors() {
+    let dictionary = build_dictionary_from_bytes();
+    assert!(matches!(
+      dictionary.add_word("すだち", &["名詞"], "スダチ", "すだち", Some(0)),
+      Err(DictionaryErr::UnknownPosErr(_))
+    ));
+    let pos = dictionary.exact_lookup("京都")[0].1.pos_id as usize;
+    let pos = dictionary
+      .get_grammar()
+      .lock()
+      .unwrap()
+      .get_part_of_speech_string(pos)
+      .clone();
+    let pos: Vec<&str> = pos.iter().map(|p| p.as_str()).collect();
+    assert!(matches!(
+      dictionary.add_word("", &pos, "", "", Some(0)),
+      Err(DictionaryErr::EmptySurfaceErr)
+    ));
+  }
+
+  #[test]
+  fn test_from_bytes_with_default_settings() {
+    // the bundled settings need a part of speech the test dictionary doesn't have
+    let e = Dictionary::from_bytes(
+      include_bytes!("resources/test/system.dic"),
+      include_bytes!("resources/test/char.def"),
+      include_bytes!("resources/test/unk.def"),
+      include_bytes!("resources/test/rewrite.def"),
+      &[],
+      None,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+      "`oovProviderPlugin[0].oovPOS` 補助記号,一般,*,*,*,* is not a part of speech of the system dictionary",
+      e.to_string()
+    );
+  }
+
+  #[test]
+  fn test_from_bytes_invalid_system_dictionary() {
+    assert!(Dictionary::from_bytes(
+      include_bytes!("resources/test/user.dic"),
+      include_bytes!("resources/test/char.def"),
+      include_bytes!("resources/test/unk.def"),
+      include_bytes!("resources/test/rewrite.def"),
+      &[],
+      None,
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_from_bytes_user_dictionary_of_another_system_dictionary() {
+    let system_dic = include_bytes!("resources/test/system.dic");
+    let mut identity = SystemDictionaryIdentity::of(
+      &BinaryDictionary::from_system_dictionary_bytes(system_dic).unwrap(),
+    );
+    let from_bytes = |identity: &SystemDictionaryIdentity| {
+      let mut user_dic = Cursor::new(include_bytes!("resources/test/user.dic").to_vec());
+      DictionaryTrailer::append(&mut user_dic, vec![], Some(identity.clone())).unwrap();
+      Dictionary::from_bytes(
+        system_dic,
+        include_bytes!("resources/test/char.def"),
+        include_bytes!("resources/test/unk.def"),
+        include_bytes!("resources/test/rewrite.def"),
+        &[user_dic.get_ref()],
+        Some(include_str!("resources/test/sudachi.json")),
+      )
+    };
+    assert!(from_bytes(&identity).is_ok());
+
+    identity.create_time += 1;
+    match from_bytes(&identity) {
+      Err(DictionaryErr::ReadDictionaryErr(ReadDictionaryErr::SystemDictionaryMismatchErr(
+        built_against,
+        _,
+      ))) => assert_eq!(identity, built_against),
+      _ => panic!("a user dictionary of another system dictionary is loaded"),
+    }
+  }
+
+  #[test]
+  fn test_from_bytes_user_dictionary_version_1() {
+    let dictionary = Dictionary::from_bytes(
+      include_bytes!("resources/test/system.dic"),
+      include_bytes!("resources/test/char.def"),
+      include_bytes!("resources/test/unk.def"),
+      include_bytes!("resources/test/rewrite.def"),
+      &[include_bytes!("resources/test/user_v1.dic")],
+      Some(include_str!("resources/test/sudachi.json")),
+    )
+    .unwrap();
+    let tokenizer = dictionary.create();
+    let morpheme_list = tokenizer.tokenize("東京府", None, None).unwrap();
+    assert_eq!(1, morpheme_list.len());
+    let morpheme = morpheme_list.get(0).unwrap();
+    assert_eq!(Some(1), morpheme.dictionary_id());
+    assert_eq!("固有名詞", morpheme.part_of_speech()[1]);
+  }
+}
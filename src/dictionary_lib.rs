@@ -1,16 +1,26 @@
 pub mod binary_dictionary;
 pub mod category_type;
 pub mod character_category;
+pub mod corruption;
 pub mod dictionary_builder;
+pub mod dictionary_diff;
 pub mod dictionary_header;
+pub mod dictionary_merger;
+pub mod dictionary_printer;
+pub mod dictionary_stats;
+pub mod dictionary_trailer;
 pub mod double_array_lexicon;
 pub mod grammar;
 mod io;
 pub mod lexicon;
 pub mod lexicon_set;
+pub mod mecab_lexicon;
+pub mod memory_lexicon;
+pub mod simple_lexicon;
 pub mod system_dictionary_version;
 pub mod user_dictionary_builder;
 mod word_id_table;
+mod word_index;
 pub mod word_info;
 mod word_info_list;
 mod word_parameter_list;
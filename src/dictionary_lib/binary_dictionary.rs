@@ -1,13 +1,17 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader, Error as IOError, Seek};
+use std::io::{BufRead, BufReader, Cursor, Error as IOError, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+use byteorder::{LittleEndian, ReadBytesExt};
 use thiserror::Error;
 
+use super::corruption::CorruptionErr;
 use super::dictionary_header::{DictionaryHeader, DictionaryHeaderErr};
-use super::double_array_lexicon::DoubleArrayLexicon;
+use super::dictionary_trailer::{DictionaryTrailer, SystemDictionaryIdentity};
+use super::double_array_lexicon::{DoubleArrayLexicon, LexiconLimits};
 use super::grammar::Grammar;
-use super::lexicon::LexiconErr;
+use super::io::LittleEndianWrite;
+use super::lexicon::{LexiconErr, Size};
 use super::system_dictionary_version::{
   SYSTEM_DICT_VERSION, USER_DICT_VERSION_1, USER_DICT_VERSION_2,
 };
@@ -20,20 +24,28 @@ pub enum ReadDictionaryErr {
   InvalidSystemDictionaryErr,
   #[error("invalid user dictionary")]
   InvalidUserDictionaryErr,
-  #[error("not found grammar")]
-  NotFoundGrammarErr,
+  #[error("not a version 1 user dictionary")]
+  NotUserDictionaryVersion1Err,
   #[error("{0}")]
   IOError(#[from] IOError),
   #[error("{0}")]
   DictionaryHeaderErr(#[from] DictionaryHeaderErr),
   #[error("{0}")]
   LexiconErr(#[from] LexiconErr),
+  #[error("corrupted dictionary: {0}")]
+  CorruptedErr(#[from] CorruptionErr),
+  #[error(
+    "the user dictionary was built against another system dictionary ({0}) than this one ({1})"
+  )]
+  SystemDictionaryMismatchErr(SystemDictionaryIdentity, SystemDictionaryIdentity),
 }
 
 pub struct BinaryDictionary {
   pub grammar: Grammar,
-  header: DictionaryHeader,
+  pub header: DictionaryHeader,
   pub lexicon: DoubleArrayLexicon,
+  /// integrity and provenance, if the dictionary has them
+  pub trailer: Option<DictionaryTrailer>,
 }
 
 impl BinaryDictionary {
@@ -41,13 +53,19 @@ impl BinaryDictionary {
     grammar: Grammar,
     header: DictionaryHeader,
     lexicon: DoubleArrayLexicon,
+    trailer: Option<DictionaryTrailer>,
   ) -> BinaryDictionary {
     BinaryDictionary {
       grammar,
       header,
       lexicon,
+      trailer,
     }
   }
+  /// Read a dictionary, checking that it is complete and consistent
+  ///
+  /// The ids of a user dictionary that refer to its system dictionary are left to
+  /// `validate_user_dictionary`.
   pub fn read_dictionary_from_reader<R: Seek + BufRead>(
     reader: &mut R,
   ) -> Result<BinaryDictionary, ReadDictionaryErr> {
@@ -59,19 +77,154 @@ impl BinaryDictionary {
     {
       return Err(ReadDictionaryErr::InvalidDictionaryVersionErr);
     }
-    if header.version == USER_DICT_VERSION_1 {
-      return Err(ReadDictionaryErr::NotFoundGrammarErr);
+    // a version 1 user dictionary has no grammar, and only POS of the system dictionary
+    let grammar = if header.version == USER_DICT_VERSION_1 {
+      Grammar::empty()
+    } else {
+      Grammar::from_reader(reader)?
+    };
+
+    let (trailer, end) = match DictionaryTrailer::from_reader(reader)? {
+      Some((trailer, start)) => (Some(trailer), Some(start)),
+      None => (None, None),
+    };
+    let lexicon = DoubleArrayLexicon::from_reader_until(reader, end)?;
+    if header.version == SYSTEM_DICT_VERSION {
+      let limits = LexiconLimits::new(&grammar, grammar.get_part_of_speech_size(), None);
+      lexicon.validate(Some(&limits))?;
+    } else {
+      lexicon.validate(None)?;
+    }
+    let dictionary = BinaryDictionary::new(grammar, header, lexicon, trailer);
+    if let Some(trailer) = &dictionary.trailer {
+      trailer.check_counts(&dictionary)?;
+    }
+    Ok(dictionary)
+  }
+  /// Fail if this user dictionary records that it was built against another system
+  /// dictionary than `system_dictionary`
+  pub fn check_system_dictionary(
+    &self,
+    system_dictionary: &SystemDictionaryIdentity,
+  ) -> Result<(), ReadDictionaryErr> {
+    match self
+      .trailer
+      .as_ref()
+      .and_then(|trailer| trailer.system_dictionary.as_ref())
+    {
+      Some(built_against) if !built_against.matches(system_dictionary) => {
+        Err(ReadDictionaryErr::SystemDictionaryMismatchErr(
+          built_against.clone(),
+          system_dictionary.clone(),
+        ))
+      }
+      _ => Ok(()),
+    }
+  }
+  /// Check this user dictionary against its system dictionary, given the identity, the
+  /// grammar and the number of words of the latter
+  pub fn validate_user_dictionary(
+    &self,
+    system_dictionary: Option<&SystemDictionaryIdentity>,
+    system_grammar: &Grammar,
+    system_pos_size: usize,
+    system_word_size: usize,
+  ) -> Result<(), ReadDictionaryErr> {
+    if let Some(system_dictionary) = system_dictionary {
+      self.check_system_dictionary(system_dictionary)?;
+    }
+    let limits = LexiconLimits::new(
+      system_grammar,
+      system_pos_size + self.grammar.get_part_of_speech_size(),
+      Some(system_word_size),
+    );
+    self.lexicon.validate(Some(&limits))?;
+    Ok(())
+  }
+  /// Check this user dictionary against the system dictionary `system_dictionary`
+  pub fn validate_against(
+    &self,
+    system_dictionary: &BinaryDictionary,
+  ) -> Result<(), ReadDictionaryErr> {
+    self.validate_user_dictionary(
+      Some(&SystemDictionaryIdentity::of(system_dictionary)),
+      &system_dictionary.grammar,
+      system_dictionary.grammar.get_part_of_speech_size(),
+      system_dictionary.lexicon.size(),
+    )
+  }
+  /// Rewrite the version 1 user dictionary in `reader` as version 2, with an empty grammar
+  ///
+  /// The lexicon is copied as is, but for the positions of the word infos, which move by the
+  /// size of the grammar. A trailer is not copied.
+  pub fn upgrade_user_dictionary<R: Seek + BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+  ) -> Result<(), ReadDictionaryErr> {
+    let start = reader.stream_position()?;
+    let dictionary = BinaryDictionary::read_dictionary_from_reader(reader)?;
+    if dictionary.header.version != USER_DICT_VERSION_1 {
+      return Err(ReadDictionaryErr::NotUserDictionaryVersion1Err);
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    DictionaryHeader::from_reader(reader)?;
+    let position = reader.stream_position()?;
+    let end = match DictionaryTrailer::from_reader(reader)? {
+      Some((_, trailer_start)) => trailer_start,
+      None => reader.seek(SeekFrom::End(0))?,
+    };
+    reader.seek(SeekFrom::Start(position))?;
+    let mut lexicon = vec![];
+    reader.take(end - position).read_to_end(&mut lexicon)?;
+
+    // no POS and an empty connection matrix
+    let grammar = [0u8; 6];
+    let header = DictionaryHeader::new(
+      USER_DICT_VERSION_2,
+      dictionary.header.create_time,
+      dictionary.header.description,
+    );
+    let mut cursor = Cursor::new(&mut lexicon[..]);
+    let trie_size = cursor.read_u32::<LittleEndian>()? as i64;
+    cursor.seek(SeekFrom::Current(4 * trie_size))?;
+    let word_id_table_size = cursor.read_u32::<LittleEndian>()? as i64;
+    cursor.seek(SeekFrom::Current(word_id_table_size))?;
+    let word_size = cursor.read_u32::<LittleEndian>()? as usize;
+    cursor.seek(SeekFrom::Current(6 * word_size as i64))?;
+    for _ in 0..word_size {
+      let offset = cursor.read_u32::<LittleEndian>()?;
+      cursor.seek(SeekFrom::Current(-4))?;
+      cursor.write_u32(offset + grammar.len() as u32)?;
     }
-    let grammar = Grammar::from_reader(reader)?;
 
-    let lexicon = DoubleArrayLexicon::from_reader(reader)?;
-    Ok(BinaryDictionary::new(grammar, header, lexicon))
+    writer.write_all(&header.to_bytes()?)?;
+    writer.write_all(&grammar)?;
+    writer.write_all(&lexicon)?;
+    Ok(())
+  }
+  /// Read a system or user dictionary
+  pub fn from_dictionary<P: AsRef<Path>>(
+    filename: P,
+  ) -> Result<BinaryDictionary, ReadDictionaryErr> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    BinaryDictionary::read_dictionary_from_reader(&mut reader)
+  }
+  pub fn is_user_dictionary(&self) -> bool {
+    self.header.version != SYSTEM_DICT_VERSION
   }
   pub fn from_system_dictionary<P: AsRef<Path>>(
     filename: P,
   ) -> Result<BinaryDictionary, ReadDictionaryErr> {
     let mut reader = BufReader::new(File::open(filename)?);
-    let dictionary = BinaryDictionary::read_dictionary_from_reader(&mut reader)?;
+    BinaryDictionary::read_system_dictionary_from_reader(&mut reader)
+  }
+  pub fn from_system_dictionary_bytes(bytes: &[u8]) -> Result<BinaryDictionary, ReadDictionaryErr> {
+    BinaryDictionary::read_system_dictionary_from_reader(&mut Cursor::new(bytes))
+  }
+  fn read_system_dictionary_from_reader<R: Seek + BufRead>(
+    reader: &mut R,
+  ) -> Result<BinaryDictionary, ReadDictionaryErr> {
+    let dictionary = BinaryDictionary::read_dictionary_from_reader(reader)?;
     if dictionary.header.version != SYSTEM_DICT_VERSION {
       return Err(ReadDictionaryErr::InvalidSystemDictionaryErr);
     }
@@ -81,7 +234,15 @@ impl BinaryDictionary {
     filename: P,
   ) -> Result<BinaryDictionary, ReadDictionaryErr> {
     let mut reader = BufReader::new(File::open(filename)?);
-    let dictionary = BinaryDictionary::read_dictionary_from_reader(&mut reader)?;
+    BinaryDictionary::read_user_dictionary_from_reader(&mut reader)
+  }
+  pub fn from_user_dictionary_bytes(bytes: &[u8]) -> Result<BinaryDictionary, ReadDictionaryErr> {
+    BinaryDictionary::read_user_dictionary_from_reader(&mut Cursor::new(bytes))
+  }
+  fn read_user_dictionary_from_reader<R: Seek + BufRead>(
+    reader: &mut R,
+  ) -> Result<BinaryDictionary, ReadDictionaryErr> {
+    let dictionary = BinaryDictionary::read_dictionary_from_reader(reader)?;
     if USER_DICT_VERSION_1 != dictionary.header.version
       && USER_DICT_VERSION_2 != dictionary.header.version
     {
@@ -90,3 +251,129 @@ impl BinaryDictionary {
     Ok(dictionary)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::lexicon::Lexicon;
+
+  const SYSTEM_DIC: &[u8] = include_bytes!("../resources/test/system.dic");
+  const USER_DIC: &[u8] = include_bytes!("../resources/test/user.dic");
+  const USER_V1_DIC: &[u8] = include_bytes!("../resources/test/user_v1.dic");
+
+  /// What a tokenizer does with a dictionary
+  fn use_dictionary(dictionary: &BinaryDictionary) {
+    let lexicon = &dictionary.lexicon;
+    for text in &["東京都に行った", "すだちぴらる", "0123456789"] {
+      for (word_id, _) in lexicon.lookup(text.as_bytes(), 0) {
+        lexicon.get_left_id(word_id);
+        lexicon.get_cost(word_id);
+      }
+      lexicon.exact_lookup(text.as_bytes());
+    }
+    lexicon.predictive_lookup("東".as_bytes(), None);
+    for word_id in 0..lexicon.size() {
+      lexicon.get_word_info(word_id);
+    }
+  }
+
+  #[test]
+  fn test_truncated_dictionary() {
+    for len in 0..SYSTEM_DIC.len() {
+      assert!(
+        BinaryDictionary::from_system_dictionary_bytes(&SYSTEM_DIC[..len]).is_err(),
+        "{} bytes",
+        len
+      );
+    }
+    for len in 0..USER_DIC.len() {
+      assert!(BinaryDictionary::from_user_dictionary_bytes(&USER_DIC[..len]).is_err());
+    }
+  }
+
+  #[test]
+  fn test_corrupted_dictionary() {
+    // a dictionary with any byte after the header changed either fails to load or is usable
+    for position in 272..SYSTEM_DIC.len() {
+      let mut bytes = SYSTEM_DIC.to_vec();
+      bytes[position] ^= 0xFF;
+      if let Ok(dictionary) = BinaryDictionary::from_system_dictionary_bytes(&bytes) {
+        use_dictionary(&dictionary);
+      }
+    }
+    for position in 272..USER_DIC.len() {
+      let mut bytes = USER_DIC.to_vec();
+      bytes[position] ^= 0xFF;
+      if let Ok(dictionary) = BinaryDictionary::from_user_dictionary_bytes(&bytes) {
+        use_dictionary(&dictionary);
+      }
+    }
+  }
+
+  #[test]
+  fn test_user_dictionary_out_of_system() {
+    let system = BinaryDictionary::from_system_dictionary_bytes(SYSTEM_DIC).unwrap();
+    let user = BinaryDictionary::from_user_dictionary_bytes(USER_DIC).unwrap();
+    let pos_size = system.grammar.get_part_of_speech_size();
+    assert!(user
+      .validate_user_dictionary(None, &system.grammar, pos_size, system.lexicon.size())
+      .is_ok());
+    // 東京府 is split into a word of the system dictionary
+    match user.validate_user_dictionary(None, &system.grammar, pos_size, 5) {
+      Err(ReadDictionaryErr::CorruptedErr(CorruptionErr::WordInfoErr(_, message))) => {
+        assert_eq!("its split refers to an unknown word 5", message)
+      }
+      _ => panic!("the split should be out of range"),
+    }
+    assert!(user
+      .validate_user_dictionary(None, &system.grammar, 0, system.lexicon.size())
+      .is_err());
+  }
+
+  #[test]
+  fn test_read_user_dictionary_version_1() {
+    let system = BinaryDictionary::from_system_dictionary_bytes(SYSTEM_DIC).unwrap();
+    let user = BinaryDictionary::from_user_dictionary_bytes(USER_V1_DIC).unwrap();
+    assert!(user.is_user_dictionary());
+    assert_eq!(0, user.grammar.get_part_of_speech_size());
+    assert_eq!(3, user.lexicon.size());
+    // the POS ids are those of the system dictionary
+    let word_info = user.lexicon.get_word_info(2);
+    assert_eq!("東京府", word_info.surface);
+    assert_eq!(
+      "固有名詞",
+      system
+        .grammar
+        .get_part_of_speech_string(word_info.pos_id as usize)[1]
+    );
+    let pos_size = system.grammar.get_part_of_speech_size();
+    assert!(user
+      .validate_user_dictionary(None, &system.grammar, pos_size, system.lexicon.size())
+      .is_ok());
+  }
+
+  #[test]
+  fn test_upgrade_user_dictionary() {
+    let mut upgraded = vec![];
+    BinaryDictionary::upgrade_user_dictionary(&mut Cursor::new(USER_V1_DIC), &mut upgraded)
+      .unwrap();
+    let user_v1 = BinaryDictionary::from_user_dictionary_bytes(USER_V1_DIC).unwrap();
+    let user = BinaryDictionary::from_user_dictionary_bytes(&upgraded).unwrap();
+    assert_eq!(USER_DICT_VERSION_2, user.header.version);
+    assert_eq!(user_v1.header.create_time, user.header.create_time);
+    assert_eq!(user_v1.header.description, user.header.description);
+    assert_eq!(0, user.grammar.get_part_of_speech_size());
+    assert_eq!(user_v1.lexicon.size(), user.lexicon.size());
+    for word_id in 0..user.lexicon.size() {
+      assert_eq!(
+        format!("{:?}", user_v1.lexicon.get_word_info(word_id)),
+        format!("{:?}", user.lexicon.get_word_info(word_id))
+      );
+    }
+
+    match BinaryDictionary::upgrade_user_dictionary(&mut Cursor::new(USER_DIC), &mut vec![]) {
+      Err(ReadDictionaryErr::NotUserDictionaryVersion1Err) => (),
+      _ => panic!("a version 2 user dictionary is upgraded"),
+    }
+  }
+}
@@ -73,6 +73,7 @@ fn parse_hex(t: &str) -> Result<u32, ParseIntError> {
   u32::from_str_radix(t.trim_start_matches("0x"), 16)
 }
 
+#[derive(Clone)]
 pub struct CharacterCategory {
   range_list: Vec<CharacterCategoryRange>,
 }
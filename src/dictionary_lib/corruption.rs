@@ -0,0 +1,42 @@
+use std::io::{Error as IOError, Seek, SeekFrom};
+
+use thiserror::Error;
+
+/// What is wrong with a truncated or corrupted binary dictionary
+#[derive(Error, Debug)]
+pub enum CorruptionErr {
+  #[error("{0} needs {1} bytes but {2} are left")]
+  TruncatedErr(&'static str, u64, u64),
+  #[error("{0} has a negative size {1}")]
+  NegativeSizeErr(&'static str, i64),
+  #[error("trie unit {0} refers to unit {1} of {2}")]
+  TrieUnitErr(usize, usize, usize),
+  #[error("trie unit {0} refers to byte {1} of the word id table, where no entry starts")]
+  TrieValueErr(usize, usize),
+  #[error("the entry at byte {0} of the word id table is cut off")]
+  WordIdTableErr(usize),
+  #[error("the word id table refers to word {0} of {1}")]
+  WordIdErr(usize, usize),
+  #[error("word {0}: {1}")]
+  WordInfoErr(usize, String),
+  #[error("invalid trailer: {0}")]
+  TrailerErr(String),
+  #[error("{0}")]
+  IOError(#[from] IOError),
+}
+
+/// Fail unless `reader` has `size` more bytes, before they are allocated
+pub fn check_remaining<R: Seek>(
+  reader: &mut R,
+  name: &'static str,
+  size: u64,
+) -> Result<(), CorruptionErr> {
+  let position = reader.stream_position()?;
+  let end = reader.seek(SeekFrom::End(0))?;
+  reader.seek(SeekFrom::Start(position))?;
+  let remaining = end.saturating_sub(position);
+  if size > remaining {
+    return Err(CorruptionErr::TruncatedErr(name, size, remaining));
+  }
+  Ok(())
+}
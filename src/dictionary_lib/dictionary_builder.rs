@@ -1,55 +1,198 @@
 use std::char::from_u32;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::io::{BufRead, Cursor, Error as IOError, Seek, SeekFrom, Write};
 use std::num::ParseIntError;
 use std::str::FromStr;
 
-use log::{error, info, warn};
-use regex::{Captures, Error as RegexError, Regex};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use regex::{Error as RegexError, Regex};
 use thiserror::Error;
 
+use super::double_array_lexicon::SIGNED_SHORT_MIN;
+use super::grammar::Grammar;
 use super::io::{CurrentPosition, LittleEndianWrite, Pipe};
 use super::lexicon::LexiconErr;
 use super::word_info::WordInfo;
 use crate::darts::DoubleArrayTrie;
+use crate::plugin::default_input_text_plugin::DefaultInputTextPluginSetupErr;
 
 const BYTE_MAX_VALUE: usize = 127;
 // const MAX_LENGTH: u64 = 255;
 const COLS_NUM: usize = 18;
 // const BUFFER_SIZE: u64 = 1024 * 1024;
-// const PATTERN_UNICODE_LITERAL: Regex = Regex::new(r"\\u([0-9a-fA-F]{4}|{[0-9a-fA-F]+})").unwrap();
 const ARRAY_MAX_LENGTH: usize = 127; // max value of byte in Java
 const STRING_MAX_LENGTH: usize = 32767; // max value of short in Java
 
+lazy_static! {
+  static ref PATTERN_UNICODE_LITERAL: Regex =
+    Regex::new(r"\\u([0-9a-fA-F]{4}|\{[0-9a-fA-F]+\})").unwrap();
+  static ref PATTERN_ID: Regex = Regex::new(r"^U?\d+$").unwrap();
+}
+
+const COLUMN_NAMES: [&str; COLS_NUM] = [
+  "headword",
+  "left id",
+  "right id",
+  "cost",
+  "surface",
+  "POS 1",
+  "POS 2",
+  "POS 3",
+  "POS 4",
+  "POS 5",
+  "POS 6",
+  "reading form",
+  "normalized form",
+  "dictionary form",
+  "split mode",
+  "A unit split",
+  "B unit split",
+  "word structure",
+];
+
 pub struct WordEntry {
   headword: Option<String>,
   parameters: [i16; 3],
+  /// which of the left id, right id and cost the row left to the builder
+  inferred: [bool; 3],
   word_info: WordInfo,
   aunit_split_string: String,
   bunit_split_string: String,
   cunit_split_string: String,
+  file_id: usize,
+  line: usize,
+}
+
+/// A problem with a row of a lexicon source file
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexiconDiagnostic {
+  pub file: String,
+  /// 1-based line number
+  pub line: usize,
+  /// `None` if the problem is with the whole row
+  pub column: Option<&'static str>,
+  pub value: String,
+  pub reason: String,
+}
+
+impl fmt::Display for LexiconDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}: ", self.file, self.line)?;
+    if let Some(column) = self.column {
+      write!(f, "{}: ", column)?;
+    }
+    write!(f, "{}", self.reason)?;
+    if !self.value.is_empty() {
+      // a value can be as long as 32767 characters
+      let value: String = self.value.chars().take(40).collect();
+      let ellipsis = if value.len() < self.value.len() {
+        "..."
+      } else {
+        ""
+      };
+      write!(f, " ({:?}{})", value, ellipsis)?;
+    }
+    Ok(())
+  }
+}
+
+/// The line number and lexicon columns of a row, or what is wrong with it
+pub type LexiconRow = (usize, Result<Vec<String>, LexiconDiagnostic>);
+
+/// Connection ids and cost the builder filled in for a row of a user dictionary
+#[derive(Clone, Debug, PartialEq)]
+pub struct InferredParameters {
+  pub file: String,
+  pub line: usize,
+  pub surface: String,
+  pub left_id: Option<i16>,
+  pub right_id: Option<i16>,
+  /// whether the cost is left to be computed when the dictionary is loaded
+  pub cost: bool,
+}
+
+impl fmt::Display for InferredParameters {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut values = vec![];
+    if let Some(left_id) = self.left_id {
+      values.push(format!("left id {}", left_id));
+    }
+    if let Some(right_id) = self.right_id {
+      values.push(format!("right id {}", right_id));
+    }
+    if self.cost {
+      values.push(String::from("cost (computed on loading)"));
+    }
+    write!(
+      f,
+      "{}:{}: {}: inferred {}",
+      self.file,
+      self.line,
+      self.surface,
+      values.join(", ")
+    )
+  }
+}
+
+fn format_diagnostics(diagnostics: &[LexiconDiagnostic]) -> String {
+  let mut lines = vec![match diagnostics.len() {
+    1 => String::from("1 error in the lexicon"),
+    n => format!("{} errors in the lexicon", n),
+  }];
+  lines.extend(diagnostics.iter().map(|d| d.to_string()));
+  lines.join("\n")
+}
+
+/// A problem with a row before its file and line are known
+#[derive(Debug)]
+struct ColumnErr {
+  column: Option<usize>,
+  value: String,
+  reason: String,
+}
+
+impl ColumnErr {
+  fn new<S: Into<String>>(column: Option<usize>, value: &str, reason: S) -> ColumnErr {
+    ColumnErr {
+      column,
+      value: value.to_string(),
+      reason: reason.into(),
+    }
+  }
+  fn at(self, file: &str, line: usize) -> LexiconDiagnostic {
+    LexiconDiagnostic {
+      file: file.to_string(),
+      line,
+      column: self.column.map(|column| COLUMN_NAMES[column]),
+      value: self.value,
+      reason: self.reason,
+    }
+  }
 }
 
 struct PosTable {
   table: Vec<String>,
+  ids: HashMap<String, usize>,
 }
 
 impl PosTable {
   fn new() -> PosTable {
-    PosTable { table: vec![] }
+    PosTable {
+      table: vec![],
+      ids: HashMap::new(),
+    }
   }
-  fn get_id(&self, text: &str) -> Result<usize, DictionaryBuilderErr> {
-    self
-      .table
-      .iter()
-      .position(|t| t == text)
-      .ok_or_else(|| DictionaryBuilderErr::InvalidFormatErr)
+  fn get_id(&self, text: &str) -> Option<usize> {
+    self.ids.get(text).copied()
   }
   fn mut_get_id(&mut self, text: &str) -> usize {
-    match self.table.iter().position(|t| t == text) {
-      Some(id) => id,
+    match self.ids.get(text) {
+      Some(id) => *id,
       None => {
         self.table.push(text.to_string());
+        self.ids.insert(text.to_string(), self.table.len() - 1);
         self.table.len() - 1
       }
     }
@@ -65,6 +208,16 @@ pub enum DictionaryBuilderErr {
   InvalidFormatErr,
   #[error("invalid word id")]
   InvalidWordIdErr,
+  #[error("unknown POS")]
+  UnknownPosErr,
+  #[error("word not found")]
+  NotFoundWordErr,
+  #[error("invalid unicode escape")]
+  InvalidUnicodeEscapeErr,
+  #[error("{}", format_diagnostics(.0))]
+  InvalidLexiconErr(Vec<LexiconDiagnostic>),
+  #[error("{0}")]
+  InputTextPluginErr(#[from] DefaultInputTextPluginSetupErr),
   #[error("{0}")]
   IOError(#[from] IOError),
   #[error("{0}")]
@@ -78,24 +231,62 @@ pub enum DictionaryBuilderErr {
 }
 
 pub struct DictionaryBuilder {
-  trie_keys: HashMap<String, Vec<usize>>,
+  /// headwords in the byte order the trie is built in
+  trie_keys: BTreeMap<String, Vec<usize>>,
   pub entries: Vec<WordEntry>,
+  /// the first entry of every (surface, POS, reading form), to resolve split references
+  word_ids: HashMap<(String, i16, String), usize>,
+  files: Vec<String>,
+  diagnostics: Vec<LexiconDiagnostic>,
+  max_errors: Option<usize>,
   _is_user_dictionary: bool,
+  system_pos_table: PosTable,
   pos_table: PosTable,
 }
 
 impl Default for DictionaryBuilder {
   fn default() -> DictionaryBuilder {
     DictionaryBuilder {
-      trie_keys: HashMap::new(),
+      trie_keys: BTreeMap::new(),
       entries: vec![],
+      word_ids: HashMap::new(),
+      files: vec![],
+      diagnostics: vec![],
+      max_errors: None,
       _is_user_dictionary: false,
+      system_pos_table: PosTable::new(),
       pos_table: PosTable::new(),
     }
   }
 }
 
 impl DictionaryBuilder {
+  /// A builder for a user dictionary on top of a system dictionary with `system_pos_list`.
+  ///
+  /// Parts of speech of the system dictionary keep their ids and only new ones are written,
+  /// numbered after them.
+  pub fn for_user_dictionary(system_pos_list: &[Vec<String>]) -> DictionaryBuilder {
+    let mut system_pos_table = PosTable::new();
+    for pos in system_pos_list {
+      system_pos_table.mut_get_id(&pos.join(","));
+    }
+    DictionaryBuilder {
+      _is_user_dictionary: true,
+      system_pos_table,
+      ..DictionaryBuilder::default()
+    }
+  }
+  /// A builder for a system dictionary whose parts of speech start with `pos_list`, in order
+  pub fn with_pos_list(pos_list: &[Vec<String>]) -> DictionaryBuilder {
+    let mut pos_table = PosTable::new();
+    for pos in pos_list {
+      pos_table.mut_get_id(&pos.join(","));
+    }
+    DictionaryBuilder {
+      pos_table,
+      ..DictionaryBuilder::default()
+    }
+  }
   pub fn build<R: BufRead, W: Write + Seek>(
     &mut self,
     lexicon_paths: &[&str],
@@ -107,87 +298,172 @@ impl DictionaryBuilder {
       self.build_lexicons(path)?;
     }
     info!("{} words", self.entries.len());
+    self.check_lexicon(self)?;
     self.write_grammar(matrix_reader, writer)?;
     self.write_lexicon(writer)?;
     Ok(())
   }
+  /// Stop reading and checking lexicons after `max_errors` problems
+  pub fn set_max_errors(&mut self, max_errors: usize) {
+    self.max_errors = Some(max_errors);
+  }
+  fn is_error_limit_reached(&self, errors: usize) -> bool {
+    self
+      .max_errors
+      .is_some_and(|max_errors| errors >= max_errors)
+  }
+  /// Read the rows of a lexicon, keeping what is wrong with them for `check_lexicon`
   pub fn build_lexicons(&mut self, path: &str) -> Result<(), DictionaryBuilderErr> {
-    for (i, record) in csv::ReaderBuilder::new()
+    let rows = csv::ReaderBuilder::new()
       .has_headers(false)
+      .flexible(true)
       .from_path(path)?
       .into_records()
       .enumerate()
-    {
-      match self.build_lexicon(record) {
-        Ok(_) => (),
-        Err(e) => {
-          error!("{} as line {} in {}", e, i + 1, path);
-          return Err(e);
+      .map(|(i, record)| {
+        let position = match &record {
+          Ok(r) => r.position(),
+          Err(e) => e.position(),
+        };
+        let line = position.map_or(i + 1, |p| p.line() as usize);
+        let row = record
+          .map(|r| r.into_iter().map(|c| c.to_string()).collect())
+          .map_err(|e| ColumnErr::new(None, "", e.to_string()).at(path, line));
+        (line, row)
+      });
+    self.build_rows(path, rows);
+    Ok(())
+  }
+  /// Add the lexicon columns of each row of `path` read some other way, with its line number
+  pub fn build_rows<I: IntoIterator<Item = LexiconRow>>(&mut self, path: &str, rows: I) {
+    let file_id = self.files.len();
+    self.files.push(path.to_string());
+    for (line, row) in rows {
+      if self.is_error_limit_reached(self.diagnostics.len()) {
+        break;
+      }
+      let diagnostics = match row.map(|cols| self.build_lexicon(cols, file_id, line)) {
+        Ok(Ok(())) => continue,
+        Ok(Err(errs)) => errs.into_iter().map(|err| err.at(path, line)).collect(),
+        Err(diagnostic) => vec![diagnostic],
+      };
+      for diagnostic in diagnostics {
+        if !self.is_error_limit_reached(self.diagnostics.len()) {
+          self.diagnostics.push(diagnostic);
         }
       }
     }
-    Ok(())
   }
   fn build_lexicon(
     &mut self,
-    record: Result<csv::StringRecord, csv::Error>,
-  ) -> Result<(), DictionaryBuilderErr> {
-    let row = match record {
-      Ok(r) => r.into_iter().map(|c| c.to_string()).collect(),
-      Err(e) => return Err(DictionaryBuilderErr::CSVError(e)),
-    };
-
-    let entry = match self.parse_line(row) {
-      Ok(e) => e,
-      Err(e) => return Err(e),
-    };
+    cols: Vec<String>,
+    file_id: usize,
+    line: usize,
+  ) -> Result<(), Vec<ColumnErr>> {
+    let mut entry = self.parse_line(cols)?;
+    entry.file_id = file_id;
+    entry.line = line;
     if let Some(headword) = entry.headword.as_ref() {
       self.add_to_trie(headword, self.entries.len());
     }
+    let word_info = &entry.word_info;
+    self
+      .word_ids
+      .entry((
+        word_info.surface.clone(),
+        word_info.pos_id,
+        word_info.reading_form.clone(),
+      ))
+      .or_insert(self.entries.len());
     self.entries.push(entry);
     Ok(())
   }
-  fn parse_line(&mut self, cols: Vec<String>) -> Result<WordEntry, DictionaryBuilderErr> {
+  /// Parse a row, reporting every problem with it
+  fn parse_line(&mut self, cols: Vec<String>) -> Result<WordEntry, Vec<ColumnErr>> {
     if cols.len() != COLS_NUM {
-      return Err(DictionaryBuilderErr::InvalidFormatErr);
+      return Err(vec![ColumnErr::new(
+        None,
+        "",
+        format!("{} columns, expected {}", cols.len(), COLS_NUM),
+      )]);
     }
+    let mut errs = vec![];
     let cols: Vec<String> = cols
       .iter()
-      .map(|col| DictionaryBuilder::decode(col))
+      .enumerate()
+      .map(|(column, col)| {
+        DictionaryBuilder::decode(col).unwrap_or_else(|| {
+          errs.push(ColumnErr::new(Some(column), col, "invalid unicode escape"));
+          col.clone()
+        })
+      })
       .collect();
-    if !DictionaryBuilder::is_length_valid(&cols) {
-      return Err(DictionaryBuilderErr::InvalidFormatErr);
-    }
     if cols[0].is_empty() {
-      return Err(DictionaryBuilderErr::InvalidFormatErr);
+      errs.push(ColumnErr::new(Some(0), "", "empty"));
+    }
+    for &column in &[0, 4, 11, 12] {
+      if cols[column].chars().count() > STRING_MAX_LENGTH {
+        errs.push(ColumnErr::new(
+          Some(column),
+          &cols[column],
+          format!("too long, the limit is {} characters", STRING_MAX_LENGTH),
+        ));
+      }
+    }
+    let mut parameters = [0; 3];
+    let mut inferred = [false; 3];
+    for (i, column) in (1..=3).enumerate() {
+      // a user dictionary can leave connection ids and cost to the builder
+      if self._is_user_dictionary && (cols[column].is_empty() || cols[column] == "*") {
+        inferred[i] = true;
+        continue;
+      }
+      match cols[column].parse::<i16>() {
+        Ok(value) => parameters[i] = value,
+        Err(e) => errs.push(ColumnErr::new(Some(column), &cols[column], e.to_string())),
+      }
+    }
+    if inferred[2] {
+      parameters[2] = SIGNED_SHORT_MIN;
+    }
+    for (column, info) in cols.iter().enumerate().skip(15) {
+      if info.split('/').count() > ARRAY_MAX_LENGTH {
+        errs.push(ColumnErr::new(
+          Some(column),
+          info,
+          format!("too many split units, the limit is {}", ARRAY_MAX_LENGTH),
+        ));
+      }
+    }
+    let dictionary_form_word_id = if cols[13] == "*" {
+      -1
+    } else {
+      match cols[13].parse::<i32>() {
+        Ok(word_id) => word_id,
+        Err(e) => {
+          errs.push(ColumnErr::new(Some(13), &cols[13], e.to_string()));
+          -1
+        }
+      }
+    };
+    if !errs.is_empty() {
+      return Err(errs);
     }
+
     let headword = if cols[1] != "-1" {
       Some(cols[0].clone())
     } else {
       None
     };
-    let parameters = [
-      cols[1].parse::<i16>()?,
-      cols[2].parse::<i16>()?,
-      cols[3].parse::<i16>()?,
-    ];
     let strs: Vec<&str> = cols.iter().map(|c| c.as_str()).collect();
     let pos_id = self.mut_get_pos_id(&strs[5..11]);
-    // 通らない
-    // if pos_id < 0 {
-    // }
-    let aunit_split_string = DictionaryBuilder::check_splitinfo_format(&cols[15])?.to_string();
-    let bunit_split_string = DictionaryBuilder::check_splitinfo_format(&cols[16])?.to_string();
-    let cunit_split_string = DictionaryBuilder::check_splitinfo_format(&cols[17])?.to_string();
-
-    let dictionary_form_word_id = if cols[13] == "*" {
-      -1
-    } else {
-      cols[13].parse::<i32>()?
-    };
+    let aunit_split_string = cols[15].clone();
+    let bunit_split_string = cols[16].clone();
+    let cunit_split_string = cols[17].clone();
     Ok(WordEntry {
       headword,
       parameters,
+      inferred,
       word_info: WordInfo {
         surface: cols[4].clone(),
         head_word_length: cols[0].len(),
@@ -203,8 +479,120 @@ impl DictionaryBuilder {
       aunit_split_string,
       bunit_split_string,
       cunit_split_string,
+      file_id: 0,
+      line: 0,
     })
   }
+  /// Whether some rows left their connection ids to `infer_connection_ids`
+  pub fn needs_connection_ids(&self) -> bool {
+    self
+      .entries
+      .iter()
+      .any(|entry| entry.inferred[0] || entry.inferred[1])
+  }
+  /// Fill in the connection ids rows left out with the (left id, right id) of their POS
+  pub fn infer_connection_ids<F: Fn(u16) -> Option<(i16, i16)>>(&mut self, connection_ids: F) {
+    for i in 0..self.entries.len() {
+      let entry = &self.entries[i];
+      if !entry.inferred[0] && !entry.inferred[1] {
+        continue;
+      }
+      match connection_ids(entry.word_info.pos_id as u16) {
+        Some((left_id, right_id)) => {
+          let entry = &mut self.entries[i];
+          if entry.inferred[0] {
+            entry.parameters[0] = left_id;
+          }
+          if entry.inferred[1] {
+            entry.parameters[1] = right_id;
+          }
+        }
+        None => {
+          let column = if entry.inferred[0] { 1 } else { 2 };
+          let diagnostic = ColumnErr::new(
+            Some(column),
+            self.pos_string(entry.word_info.pos_id as u16),
+            "no word of the system dictionary has this POS to infer from",
+          )
+          .at(&self.files[entry.file_id], entry.line);
+          if !self.is_error_limit_reached(self.diagnostics.len()) {
+            self.diagnostics.push(diagnostic);
+          }
+        }
+      }
+    }
+  }
+  /// Every row whose connection ids or cost were left to the builder
+  pub fn get_inferred_parameters(&self) -> Vec<InferredParameters> {
+    self
+      .entries
+      .iter()
+      .filter(|entry| entry.inferred.iter().any(|inferred| *inferred))
+      .map(|entry| InferredParameters {
+        file: self.files.get(entry.file_id).cloned().unwrap_or_default(),
+        line: entry.line,
+        surface: entry.word_info.surface.clone(),
+        left_id: Some(entry.parameters[0]).filter(|_| entry.inferred[0]),
+        right_id: Some(entry.parameters[1]).filter(|_| entry.inferred[1]),
+        cost: entry.inferred[2],
+      })
+      .collect()
+  }
+  /// Check what needs every row to be read, split info and dictionary forms, with `parser`.
+  ///
+  /// Fails with every problem found so far.
+  pub fn check_lexicon<P: SplitInfoParser>(&self, parser: &P) -> Result<(), DictionaryBuilderErr> {
+    let mut diagnostics = self.diagnostics.clone();
+    for entry in self.entries.iter() {
+      if self.is_error_limit_reached(diagnostics.len()) {
+        break;
+      }
+      let file = self.files.get(entry.file_id).map_or("", |f| f.as_str());
+      let dictionary_form_word_id = entry.word_info.dictionary_form_word_id;
+      // -1 is no dictionary form
+      if dictionary_form_word_id < -1 || dictionary_form_word_id >= self.entries.len() as i32 {
+        diagnostics.push(
+          ColumnErr::new(
+            Some(13),
+            &dictionary_form_word_id.to_string(),
+            "word id out of range",
+          )
+          .at(file, entry.line),
+        );
+      }
+      let splits = [
+        (15, &entry.aunit_split_string),
+        (16, &entry.bunit_split_string),
+        (17, &entry.cunit_split_string),
+      ];
+      for (column, info) in splits.iter() {
+        if *info == "*" {
+          continue;
+        }
+        for word in info.split('/') {
+          let word_id = if DictionaryBuilder::is_id(word) {
+            parser.parse_id(word)
+          } else {
+            parser.word_to_id(word)
+          };
+          if let Err(e) = word_id {
+            diagnostics.push(
+              ColumnErr::new(Some(*column), word, split_error_reason(&e)).at(file, entry.line),
+            );
+          }
+        }
+      }
+    }
+    if let Some(max_errors) = self.max_errors {
+      diagnostics.truncate(max_errors);
+    }
+    diagnostics.sort_by_key(|d| (self.files.iter().position(|f| *f == d.file), d.line));
+    if diagnostics.is_empty() {
+      Ok(())
+    } else {
+      Err(DictionaryBuilderErr::InvalidLexiconErr(diagnostics))
+    }
+  }
   fn add_to_trie(&mut self, headword: &str, word_id: usize) {
     match self.trie_keys.get_mut(headword) {
       Some(v) => v.push(word_id),
@@ -231,6 +619,33 @@ impl DictionaryBuilder {
     }
     Ok(())
   }
+  /// Write the POS table and the connection matrix of `grammar`
+  pub fn write_grammar_from<W: Write + Seek>(
+    &mut self,
+    grammar: &Grammar,
+    writer: &mut W,
+  ) -> Result<(), DictionaryBuilderErr> {
+    info!("writing the POS table...");
+    let start = writer.position()?;
+    DictionaryBuilder::convert_pos_table(writer, &self.pos_table.get_list().clone())?;
+    let end = writer.position()?;
+    DictionaryBuilder::logging_size(end - start);
+
+    info!("writing the connection matrix...");
+    let left_id_size = grammar.get_left_id_size();
+    let right_id_size = grammar.get_right_id_size();
+    writer.write_i16(left_id_size as i16)?;
+    writer.write_i16(right_id_size as i16)?;
+    let mut matrix = Vec::with_capacity(2 * left_id_size * right_id_size);
+    for right_id in 0..right_id_size {
+      for left_id in 0..left_id_size {
+        matrix.extend(&grammar.get_connect_cost(left_id, right_id).to_le_bytes());
+      }
+    }
+    writer.write_all(&matrix)?;
+    DictionaryBuilder::logging_size(matrix.len() + 4);
+    Ok(())
+  }
   fn write_matrix<R: BufRead, W: Write>(
     &mut self,
     matrix_reader: &mut R,
@@ -298,17 +713,20 @@ impl DictionaryBuilder {
     }
     Ok(())
   }
-  pub fn write_lexicon<W: Write + Seek>(
-    &mut self,
+  pub fn write_lexicon<W: Write + Seek>(&self, writer: &mut W) -> Result<(), DictionaryBuilderErr> {
+    self.write_lexicon_with(writer, self)
+  }
+  /// Write the lexicon resolving split info with `parser`
+  pub fn write_lexicon_with<W: Write + Seek, P: SplitInfoParser>(
+    &self,
     writer: &mut W,
+    parser: &P,
   ) -> Result<(), DictionaryBuilderErr> {
     let mut trie = DoubleArrayTrie::default();
     let mut cursor = Cursor::new(vec![]);
-    let mut items: Vec<_> = self.trie_keys.iter().collect();
-    let mut keys = Vec::with_capacity(items.len());
-    let mut vals = Vec::with_capacity(items.len());
-    items.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
-    for (key, word_ids) in items {
+    let mut keys = Vec::with_capacity(self.trie_keys.len());
+    let mut vals = Vec::with_capacity(self.trie_keys.len());
+    for (key, word_ids) in &self.trie_keys {
       keys.push(key.as_bytes());
       vals.push(cursor.position() as u32);
       cursor.write_u8(word_ids.len() as u8)?;
@@ -346,16 +764,17 @@ impl DictionaryBuilder {
       writer.write_i16(entry.parameters[2])?;
     }
     DictionaryBuilder::logging_size(self.entries.len() * 6 + 4);
-    self.write_word_info(writer)?;
+    self.write_word_info(writer, parser)?;
 
     Ok(())
   }
-  fn write_word_info<W: Write + Seek>(
-    &mut self,
+  fn write_word_info<W: Write + Seek, P: SplitInfoParser>(
+    &self,
     writer: &mut W,
+    parser: &P,
   ) -> Result<(), DictionaryBuilderErr> {
     let mark = writer.position()?;
-    let base = mark * 4 + self.entries.len();
+    let base = mark + 4 * self.entries.len();
     writer.seek(SeekFrom::Start(base as u64))?;
 
     let mut offsets = Cursor::new(Vec::with_capacity(self.entries.len() * 4));
@@ -388,13 +807,13 @@ impl DictionaryBuilder {
       };
       DictionaryBuilder::write_string_to_writer(writer, reading_form)?;
 
-      let a_unit_splitinfo = self.parse_splitinfo(&entry.aunit_split_string)?;
+      let a_unit_splitinfo = parse_splitinfo(parser, &entry.aunit_split_string)?;
       DictionaryBuilder::write_i32_vec_to_writer(writer, a_unit_splitinfo)?;
 
-      let bunit_splitinfo = self.parse_splitinfo(&entry.bunit_split_string)?;
+      let bunit_splitinfo = parse_splitinfo(parser, &entry.bunit_split_string)?;
       DictionaryBuilder::write_i32_vec_to_writer(writer, bunit_splitinfo)?;
 
-      let cunit_splitinfo = self.parse_splitinfo(&entry.cunit_split_string)?;
+      let cunit_splitinfo = parse_splitinfo(parser, &entry.cunit_split_string)?;
       DictionaryBuilder::write_i32_vec_to_writer(writer, cunit_splitinfo)?;
     }
     DictionaryBuilder::logging_size(writer.position()? - base);
@@ -407,31 +826,26 @@ impl DictionaryBuilder {
     DictionaryBuilder::logging_size(offsets.position() as usize);
     Ok(())
   }
-  fn decode(text: &str) -> String {
-    let re = Regex::new(r"\\u([0-9a-fA-F]{4}|\{[0-9a-fA-F]+\})").unwrap();
-    re.replace_all(text, |caps: &Captures| match caps.get(0) {
-      Some(uni_text) => {
-        let uni_text = uni_text.as_str().replace("{", "").replace("}", "");
-        from_u32(u32::from_str_radix(&uni_text[2..uni_text.len()], 16).unwrap())
-          .unwrap()
-          .to_string()
-      }
-      None => String::from(""),
-    })
-    .to_string()
+  /// Replace the `\uXXXX` and `\u{X...}` escapes of `text`, or `None` if one is no character
+  fn decode(text: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in PATTERN_UNICODE_LITERAL.captures_iter(text) {
+      let (escape, code) = (caps.get(0)?, caps.get(1)?.as_str());
+      let code = code.trim_start_matches('{').trim_end_matches('}');
+      let c = u32::from_str_radix(code, 16).ok().and_then(from_u32)?;
+      decoded.push_str(&text[last..escape.start()]);
+      decoded.push(c);
+      last = escape.end();
+    }
+    decoded.push_str(&text[last..]);
+    Some(decoded)
   }
   pub fn parse_splitinfo(&self, info: &str) -> Result<Vec<u32>, DictionaryBuilderErr> {
     parse_splitinfo(self, info)
   }
-  fn is_length_valid(cols: &[String]) -> bool {
-    let head_length = cols[0].chars().count();
-    head_length <= STRING_MAX_LENGTH
-      && cols[4].chars().count() <= STRING_MAX_LENGTH
-      && cols[11].chars().count() <= STRING_MAX_LENGTH
-      && cols[12].chars().count() <= STRING_MAX_LENGTH
-  }
   fn is_id(text: &str) -> bool {
-    Regex::new(r"U?\d+").unwrap().is_match(text)
+    PATTERN_ID.is_match(text)
   }
   pub fn check_word_id(&self, word_id: u32) -> Result<(), DictionaryBuilderErr> {
     if
@@ -441,14 +855,19 @@ impl DictionaryBuilder {
     }
     Ok(())
   }
-  fn mut_get_pos_id(&mut self, strs: &[&str]) -> u16 {
-    self.pos_table.mut_get_id(&strs.join(",")) as u16
+  fn pos_string(&self, pos_id: u16) -> &str {
+    let system_pos_list = self.system_pos_table.get_list();
+    match system_pos_list.get(pos_id as usize) {
+      Some(pos) => pos,
+      None => &self.pos_table.get_list()[pos_id as usize - system_pos_list.len()],
+    }
   }
-  fn check_splitinfo_format(text: &str) -> Result<&str, DictionaryBuilderErr> {
-    if text.split('/').count() > ARRAY_MAX_LENGTH {
-      return Err(DictionaryBuilderErr::InvalidFormatErr);
+  fn mut_get_pos_id(&mut self, strs: &[&str]) -> u16 {
+    let pos = strs.join(",");
+    match self.system_pos_table.get_id(&pos) {
+      Some(id) => id as u16,
+      None => (self.system_pos_table.get_list().len() + self.pos_table.mut_get_id(&pos)) as u16,
     }
-    Ok(text)
   }
   fn write_string_to_writer<W: Write>(
     writer: &mut W,
@@ -538,7 +957,15 @@ pub trait WordIdToIdConverter {
 
 impl WordIdToIdConverter for DictionaryBuilder {
   fn get_pos_id(&self, strs: &[&str]) -> Result<u16, DictionaryBuilderErr> {
-    Ok(self.pos_table.get_id(&strs.join(","))? as u16)
+    let pos = strs.join(",");
+    match (
+      self.system_pos_table.get_id(&pos),
+      self.pos_table.get_id(&pos),
+    ) {
+      (Some(id), _) => Ok(id as u16),
+      (None, Some(id)) => Ok((self.system_pos_table.get_list().len() + id) as u16),
+      (None, None) => Err(DictionaryBuilderErr::UnknownPosErr),
+    }
   }
   fn get_word_id(
     &self,
@@ -546,16 +973,15 @@ impl WordIdToIdConverter for DictionaryBuilder {
     pos_id: u16,
     reading_form: &str,
   ) -> Result<u32, DictionaryBuilderErr> {
-    for i in 0..self.entries.len() {
-      let info = &self.entries[i].word_info;
-      if info.surface == headword
-        && info.pos_id == pos_id as i16
-        && info.reading_form == reading_form
-      {
-        return Ok(i as u32);
-      }
-    }
-    Err(DictionaryBuilderErr::InvalidFormatErr)
+    self
+      .word_ids
+      .get(&(
+        headword.to_string(),
+        pos_id as i16,
+        reading_form.to_string(),
+      ))
+      .map(|id| *id as u32)
+      .ok_or(DictionaryBuilderErr::NotFoundWordErr)
   }
 }
 
@@ -567,12 +993,20 @@ pub fn word_to_id<T: WordIdToIdConverter>(
   if cols.len() < 8 {
     return Err(DictionaryBuilderErr::InvalidFormatErr);
   }
-  let headword = DictionaryBuilder::decode(cols[0]);
-  let pos_id = this.get_pos_id(&cols[1..7])?;
+  let decode = |text: &str| {
+    DictionaryBuilder::decode(text).ok_or(DictionaryBuilderErr::InvalidUnicodeEscapeErr)
+  };
+  let headword = decode(cols[0])?;
+  let pos = cols[1..7]
+    .iter()
+    .map(|c| decode(c))
+    .collect::<Result<Vec<String>, DictionaryBuilderErr>>()?;
+  let pos: Vec<&str> = pos.iter().map(|p| p.as_str()).collect();
+  let pos_id = this.get_pos_id(&pos)?;
   // if pos_id < 0 {
   //   return Err(Box::new(InvalidFormatErr::new()));
   // }
-  let reading_form = DictionaryBuilder::decode(cols[7]);
+  let reading_form = decode(cols[7])?;
   this.get_word_id(&headword, pos_id, &reading_form)
 }
 
@@ -590,6 +1024,17 @@ impl SplitInfoParser for DictionaryBuilder {
   }
 }
 
+fn split_error_reason(err: &DictionaryBuilderErr) -> String {
+  match err {
+    DictionaryBuilderErr::InvalidWordIdErr => String::from("word id out of range"),
+    DictionaryBuilderErr::ParseIntError(_) => String::from("not a word id"),
+    DictionaryBuilderErr::InvalidFormatErr => {
+      String::from("expected a word id or surface, 6 POS columns and reading form")
+    }
+    err => err.to_string(),
+  }
+}
+
 pub fn parse_splitinfo<T: SplitInfoParser>(
   this: &T,
   info: &str,
@@ -616,6 +1061,7 @@ pub fn build_empty_entry() -> WordEntry {
   WordEntry {
     headword: None,
     parameters: [0, 0, 0],
+    inferred: [false; 3],
     word_info: WordInfo {
       surface: String::from(""),
       head_word_length: 0,
@@ -631,6 +1077,8 @@ pub fn build_empty_entry() -> WordEntry {
     aunit_split_string: String::from(""),
     bunit_split_string: String::from(""),
     cunit_split_string: String::from(""),
+    file_id: 0,
+    line: 0,
   }
 }
 
@@ -640,6 +1088,8 @@ mod tests {
   #[cfg(not(target_arch = "wasm32"))]
   use crate::dictionary_lib::dictionary_header::DictionaryHeader;
   #[cfg(not(target_arch = "wasm32"))]
+  use crate::dictionary_lib::dictionary_trailer::{DictionaryTrailer, SourceFile};
+  #[cfg(not(target_arch = "wasm32"))]
   use crate::dictionary_lib::double_array_lexicon::DoubleArrayLexicon;
   #[cfg(not(target_arch = "wasm32"))]
   use crate::dictionary_lib::grammar::Grammar;
@@ -655,7 +1105,7 @@ mod tests {
   #[cfg(not(target_arch = "wasm32"))]
   use std::env::temp_dir;
   #[cfg(not(target_arch = "wasm32"))]
-  use std::fs::{create_dir, remove_dir_all, File};
+  use std::fs::{create_dir, create_dir_all, remove_dir_all, File};
   use std::io::Read;
   #[cfg(not(target_arch = "wasm32"))]
   use std::path::{Path, PathBuf};
@@ -811,9 +1261,21 @@ mod tests {
 
   #[test]
   fn test_decode() {
-    assert_eq!("a,c", DictionaryBuilder::decode("a\\u002cc"));
-    assert_eq!("a,c", DictionaryBuilder::decode("a\\u{002c}c"));
-    assert_eq!("a𠮟c", DictionaryBuilder::decode("a\\u{20b9f}c"));
+    assert_eq!(
+      Some("a,c"),
+      DictionaryBuilder::decode("a\\u002cc").as_deref()
+    );
+    assert_eq!(
+      Some("a,c"),
+      DictionaryBuilder::decode("a\\u{002c}c").as_deref()
+    );
+    assert_eq!(
+      Some("a𠮟c"),
+      DictionaryBuilder::decode("a\\u{20b9f}c").as_deref()
+    );
+    assert_eq!(None, DictionaryBuilder::decode("a\\uD800c"));
+    assert_eq!(None, DictionaryBuilder::decode("a\\u{110000}c"));
+    assert_eq!(None, DictionaryBuilder::decode("a\\u{100000000}c"));
   }
 
   #[test]
@@ -852,6 +1314,46 @@ mod tests {
           .unwrap()
       )
     );
+    assert_eq!(
+      "invalid unicode escape",
+      builder
+        .parse_splitinfo("東\\uD800,名詞,普通名詞,一般,*,*,*,ヒガシ")
+        .err()
+        .unwrap()
+        .to_string()
+    );
+  }
+
+  #[test]
+  fn test_is_id() {
+    assert!(DictionaryBuilder::is_id("12"));
+    assert!(DictionaryBuilder::is_id("U12"));
+    assert!(!DictionaryBuilder::is_id(
+      "2020年,名詞,普通名詞,一般,*,*,*,ニセンニジュウネン"
+    ));
+    assert!(!DictionaryBuilder::is_id("12U"));
+  }
+
+  #[test]
+  fn test_write_word_info() {
+    let mut builder = DictionaryBuilder::default();
+    for _ in 0..3 {
+      let mut entry = build_empty_entry();
+      entry.aunit_split_string = String::from("*");
+      entry.bunit_split_string = String::from("*");
+      entry.cunit_split_string = String::from("*");
+      builder.entries.push(entry);
+    }
+    let mut cursor = Cursor::new(vec![]);
+    builder.write_word_info(&mut cursor, &builder).unwrap();
+
+    // the offsets come first and each empty word info takes 13 bytes
+    let offsets: Vec<u32> = cursor.get_ref()[..12]
+      .chunks(4)
+      .map(|offset| u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]))
+      .collect();
+    assert_eq!(vec![12, 25, 38], offsets);
+    assert_eq!(51, cursor.get_ref().len());
   }
 
   #[test]
@@ -934,6 +1436,194 @@ mod tests {
     );
   }
 
+  #[cfg(not(target_arch = "wasm32"))]
+  fn write_lexicon_file(name: &str, lexicon: &str) -> PathBuf {
+    let dir = temp_dir().join("test_dictionary_builder");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    File::create(&path)
+      .unwrap()
+      .write_all(lexicon.as_bytes())
+      .unwrap();
+    path
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  fn build_errors(builder: &mut DictionaryBuilder, path: &Path) -> Vec<LexiconDiagnostic> {
+    let mut matrix_reader = Cursor::new(b"1 1\n0 0 200\n");
+    match builder.build(
+      &[path.to_str().unwrap()],
+      Some(&mut matrix_reader),
+      &mut Cursor::new(vec![]),
+    ) {
+      Err(DictionaryBuilderErr::InvalidLexiconErr(diagnostics)) => diagnostics,
+      _ => panic!("the lexicon should be invalid"),
+    }
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  const INVALID_LEXICON: &str = "\
+東京都,0,0,0,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都,*,B,0/5,*,*
+東,0,0,abc,東,名詞,普通名詞,一般,*,*,*,ヒガシ,東,*,A,*,*,*
+,0,0,0,京都,名詞,固有名詞,地名,一般,*,*,キョウト,京都,*,A,*,*,*
+京都,0,0,0,京都,名詞
+都,0,0,0,都,名詞,普通名詞,一般,*,*,*,ト,都,7,A,\"京都,名詞,固有名詞,地名,一般,*,*,キョウト\",*,*
+";
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_build_reports_invalid_escapes_and_dictionary_forms() {
+    let lexicon = "\
+東\\uD800,0,0,0,東,名詞,普通名詞,一般,*,*,*,ヒガシ,東,*,A,*,*,*
+京,0,0,0,京,名詞,普通名詞,一般,*,*,*,キョウ\\u{110000},京,*,A,*,*,*
+都,0,0,0,都,名詞,普通名詞,一般,*,*,*,ト,都,-2,A,*,*,*
+";
+    let path = write_lexicon_file("invalid_escape.csv", lexicon);
+    let diagnostics = build_errors(&mut DictionaryBuilder::default(), &path);
+    let summary: Vec<(usize, Option<&str>, &str, &str)> = diagnostics
+      .iter()
+      .map(|d| (d.line, d.column, d.value.as_str(), d.reason.as_str()))
+      .collect();
+    assert_eq!(
+      vec![
+        (1, Some("headword"), "東\\uD800", "invalid unicode escape"),
+        (
+          2,
+          Some("reading form"),
+          "キョウ\\u{110000}",
+          "invalid unicode escape"
+        ),
+        (3, Some("dictionary form"), "-2", "word id out of range"),
+      ],
+      summary
+    );
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_build_reports_every_error() {
+    let path = write_lexicon_file("every_error.csv", INVALID_LEXICON);
+    let diagnostics = build_errors(&mut DictionaryBuilder::default(), &path);
+    let summary: Vec<(usize, Option<&str>, &str, &str)> = diagnostics
+      .iter()
+      .map(|d| (d.line, d.column, d.value.as_str(), d.reason.as_str()))
+      .collect();
+    assert_eq!(
+      vec![
+        (1, Some("A unit split"), "5", "word id out of range"),
+        (2, Some("cost"), "abc", "invalid digit found in string"),
+        (3, Some("headword"), "", "empty"),
+        (4, None, "", "6 columns, expected 18"),
+        (5, Some("dictionary form"), "7", "word id out of range"),
+        (
+          5,
+          Some("A unit split"),
+          "京都,名詞,固有名詞,地名,一般,*,*,キョウト",
+          "word not found"
+        ),
+      ],
+      summary
+    );
+    assert_eq!(path.to_str().unwrap(), diagnostics[0].file);
+    assert_eq!(
+      format!(
+        "{}:2: cost: invalid digit found in string (\"abc\")",
+        path.to_str().unwrap()
+      ),
+      diagnostics[1].to_string()
+    );
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_build_max_errors() {
+    let path = write_lexicon_file("max_errors.csv", INVALID_LEXICON);
+    let mut builder = DictionaryBuilder::default();
+    builder.set_max_errors(2);
+    let diagnostics = build_errors(&mut builder, &path);
+    assert_eq!(
+      vec![2, 3],
+      diagnostics.iter().map(|d| d.line).collect::<Vec<usize>>()
+    );
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_infer_connection_ids() {
+    let path = write_lexicon_file(
+      "inferred.csv",
+      "\
+東京タワー,*,*,*,東京タワー,名詞,固有名詞,地名,一般,*,*,トウキョウタワー,東京タワー,*,A,*,*,*
+スカイ,3,,100,スカイ,名詞,普通名詞,一般,*,*,*,スカイ,スカイ,*,A,*,*,*
+",
+    );
+    let system_pos_list = vec![
+      vec!["名詞", "固有名詞", "地名", "一般", "*", "*"],
+      vec!["名詞", "普通名詞", "一般", "*", "*", "*"],
+    ]
+    .into_iter()
+    .map(|pos| pos.into_iter().map(String::from).collect())
+    .collect::<Vec<Vec<String>>>();
+    let mut builder = DictionaryBuilder::for_user_dictionary(&system_pos_list);
+    builder.build_lexicons(path.to_str().unwrap()).unwrap();
+    assert!(builder.needs_connection_ids());
+    builder.infer_connection_ids(|pos_id| match pos_id {
+      0 => Some((6, 8)),
+      1 => Some((7, 7)),
+      _ => None,
+    });
+    assert!(builder.check_lexicon(&builder).is_ok());
+    assert_eq!([6, 8, SIGNED_SHORT_MIN], builder.entries[0].parameters);
+    assert_eq!([3, 7, 100], builder.entries[1].parameters);
+
+    let inferred = builder.get_inferred_parameters();
+    assert_eq!(
+      (Some(6), Some(8), true),
+      (inferred[0].left_id, inferred[0].right_id, inferred[0].cost)
+    );
+    assert_eq!(
+      (None, Some(7), false),
+      (inferred[1].left_id, inferred[1].right_id, inferred[1].cost)
+    );
+    assert_eq!(
+      format!("{}:2: スカイ: inferred right id 7", path.to_str().unwrap()),
+      inferred[1].to_string()
+    );
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_infer_connection_ids_unknown_pos() {
+    let path = write_lexicon_file(
+      "inferred_unknown_pos.csv",
+      "スカイ,*,*,*,スカイ,名詞,普通名詞,一般,*,*,*,スカイ,スカイ,*,A,*,*,*\n",
+    );
+    let mut builder = DictionaryBuilder::for_user_dictionary(&[]);
+    builder.build_lexicons(path.to_str().unwrap()).unwrap();
+    builder.infer_connection_ids(|_| None);
+    match builder.check_lexicon(&builder) {
+      Err(DictionaryBuilderErr::InvalidLexiconErr(diagnostics)) => {
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Some("left id"), diagnostics[0].column);
+        assert_eq!("名詞,普通名詞,一般,*,*,*", diagnostics[0].value);
+      }
+      _ => panic!("the POS has no connection ids"),
+    }
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_system_dictionary_needs_connection_ids() {
+    let path = write_lexicon_file(
+      "system_inferred.csv",
+      "スカイ,*,*,*,スカイ,名詞,普通名詞,一般,*,*,*,スカイ,スカイ,*,A,*,*,*\n",
+    );
+    let mut builder = DictionaryBuilder::default();
+    builder.build_lexicons(path.to_str().unwrap()).unwrap();
+    assert!(!builder.needs_connection_ids());
+    assert!(builder.check_lexicon(&builder).is_err());
+  }
+
   #[cfg(not(target_arch = "wasm32"))]
   fn setup_input() -> (PathBuf, PathBuf) {
     let dir = temp_dir().join("test");
@@ -966,6 +1656,47 @@ mod tests {
     (header, grammar, lexicon_set)
   }
 
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_build_is_reproducible() {
+    let lexicon: String = [
+      "東京",
+      "京都",
+      "東",
+      "都",
+      "行く",
+      "いく",
+      "東京都",
+      "アイ",
+      "アイウ",
+    ]
+    .iter()
+    .map(|surface| {
+      format!(
+        "{},0,0,100,{},名詞,普通名詞,一般,*,*,*,ア,{},*,A,*,*,*\n",
+        surface, surface, surface
+      )
+    })
+    .collect();
+    let path = write_lexicon_file("reproducible.csv", &lexicon);
+    let build = || {
+      let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 951_782_400, String::from("test"));
+      let mut stream = Cursor::new(header.to_bytes().unwrap());
+      stream.seek(SeekFrom::End(0)).unwrap();
+      DictionaryBuilder::default()
+        .build(
+          &[path.to_str().unwrap()],
+          Some(&mut Cursor::new(b"1 1\n0 0 200\n")),
+          &mut stream,
+        )
+        .unwrap();
+      let sources = vec![SourceFile::from_path(&path).unwrap()];
+      DictionaryTrailer::append(&mut stream, sources, None).unwrap();
+      stream.into_inner()
+    };
+    assert_eq!(build(), build());
+  }
+
   #[cfg(not(target_arch = "wasm32"))]
   #[test]
   fn test_build() {
@@ -1018,6 +1749,9 @@ mod tests {
 
     // lexicon
     assert_eq!(3, lexicon.size());
+    assert_eq!(vec![(0, 9)], lexicon.lookup("東京都".as_bytes(), 0));
+    assert_eq!(vec![(2, 9)], lexicon.lookup("東京都".as_bytes(), 3));
+    assert!(lexicon.lookup("東".as_bytes(), 0).is_empty());
     assert_eq!(0, lexicon.get_cost(0));
     let word_info = lexicon.get_word_info(0);
     assert_eq!("東京都", word_info.surface);
@@ -0,0 +1,558 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use super::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
+use super::lexicon::{Lexicon, Size};
+
+const USER_WORD_ID_FLAG: u32 = 1 << 28;
+
+/// The identity of an entry across builds
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WordKey {
+  pub surface: String,
+  pub pos: Vec<String>,
+  pub reading_form: String,
+}
+
+impl WordKey {
+  fn to_json(&self) -> Value {
+    json!({
+      "surface": self.surface,
+      "pos": self.pos,
+      "reading": self.reading_form,
+    })
+  }
+}
+
+impl fmt::Display for WordKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{},{},{}",
+      self.surface,
+      self.pos.join(","),
+      self.reading_form
+    )
+  }
+}
+
+/// A field of an entry that differs between the two dictionaries
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordChange {
+  Cost(i16, i16),
+  ConnectionIds((i16, i16), (i16, i16)),
+  NormalizedForm(String, String),
+  /// split info of a mode (`A`, `B`) or `word structure`, as the keys of the words
+  Split(&'static str, Vec<String>, Vec<String>),
+}
+
+impl WordChange {
+  fn to_json(&self) -> Value {
+    match self {
+      WordChange::Cost(old, new) => json!({"field": "cost", "old": old, "new": new}),
+      WordChange::ConnectionIds(old, new) => json!({
+        "field": "connection ids",
+        "old": [old.0, old.1],
+        "new": [new.0, new.1],
+      }),
+      WordChange::NormalizedForm(old, new) => {
+        json!({"field": "normalized form", "old": old, "new": new})
+      }
+      WordChange::Split(field, old, new) => json!({"field": field, "old": old, "new": new}),
+    }
+  }
+}
+
+impl fmt::Display for WordChange {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      WordChange::Cost(old, new) => write!(f, "cost {} -> {}", old, new),
+      WordChange::ConnectionIds(old, new) => write!(
+        f,
+        "connection ids {} {} -> {} {}",
+        old.0, old.1, new.0, new.1
+      ),
+      WordChange::NormalizedForm(old, new) => write!(f, "normalized form {} -> {}", old, new),
+      WordChange::Split(field, old, new) => write!(
+        f,
+        "{} {} -> {}",
+        field,
+        format_split(old),
+        format_split(new)
+      ),
+    }
+  }
+}
+
+fn format_split(split: &[String]) -> String {
+  if split.is_empty() {
+    String::from("*")
+  } else {
+    split.join("/")
+  }
+}
+
+/// A connection cost that changed by more than the threshold
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectionChange {
+  pub left_id: usize,
+  pub right_id: usize,
+  pub old: i16,
+  pub new: i16,
+}
+
+/// What differs between two builds of a dictionary, as shown by `sudachiclone diff`
+///
+/// Entries are matched by (surface, POS, reading), so word ids and POS ids may differ. Entries
+/// sharing a key are matched in word id order.
+pub struct DictionaryDiff {
+  pub added: Vec<WordKey>,
+  pub removed: Vec<WordKey>,
+  pub changed: Vec<(WordKey, Vec<WordChange>)>,
+  /// (left id size, right id size) of both matrices if they differ
+  pub matrix_sizes: Option<((usize, usize), (usize, usize))>,
+  /// changed cells of the ids both matrices have
+  pub connections: Vec<ConnectionChange>,
+}
+
+struct Entry {
+  left_id: i16,
+  right_id: i16,
+  cost: i16,
+  normalized_form: String,
+  a_unit_split: Vec<String>,
+  b_unit_split: Vec<String>,
+  word_structure: Vec<String>,
+}
+
+/// A dictionary with the system dictionary that resolves its shared POS and words
+struct Side<'a> {
+  dictionary: &'a BinaryDictionary,
+  system_dictionary: Option<&'a BinaryDictionary>,
+}
+
+impl<'a> Side<'a> {
+  /// Fails if a user dictionary does not fit `system_dictionary`
+  fn new(
+    dictionary: &'a BinaryDictionary,
+    system_dictionary: Option<&'a BinaryDictionary>,
+  ) -> Result<Side<'a>, ReadDictionaryErr> {
+    let system_dictionary = system_dictionary.filter(|_| dictionary.is_user_dictionary());
+    if let Some(system_dictionary) = system_dictionary {
+      dictionary.validate_against(system_dictionary)?;
+    }
+    Ok(Side {
+      dictionary,
+      system_dictionary,
+    })
+  }
+  fn pos_string(&self, pos_id: i16) -> Vec<String> {
+    let pos_id = pos_id as usize;
+    let grammar = &self.dictionary.grammar;
+    match self.system_dictionary {
+      Some(system) if pos_id < system.grammar.get_part_of_speech_size() => {
+        system.grammar.get_part_of_speech_string(pos_id).clone()
+      }
+      Some(system) => grammar
+        .get_part_of_speech_string(pos_id - system.grammar.get_part_of_speech_size())
+        .clone(),
+      // the POS ids of a user dictionary start with those of its unknown system dictionary
+      None if self.dictionary.is_user_dictionary() => vec![format!("#{}", pos_id)],
+      None => grammar.get_part_of_speech_string(pos_id).clone(),
+    }
+  }
+  fn word_key(&self, dictionary: &BinaryDictionary, word_id: usize) -> WordKey {
+    let word_info = dictionary.lexicon.get_word_info(word_id);
+    let pos = if std::ptr::eq(dictionary, self.dictionary) {
+      self.pos_string(word_info.pos_id)
+    } else {
+      dictionary
+        .grammar
+        .get_part_of_speech_string(word_info.pos_id as usize)
+        .clone()
+    };
+    WordKey {
+      surface: word_info.surface,
+      pos,
+      reading_form: word_info.reading_form,
+    }
+  }
+  /// The key of a word of split info, or its id if it can't be resolved
+  fn split_word(&self, word_id: i32) -> String {
+    let word_id = word_id as u32;
+    let is_user_dictionary = self.dictionary.is_user_dictionary();
+    let dictionary = if !is_user_dictionary || word_id & USER_WORD_ID_FLAG != 0 {
+      Some(self.dictionary)
+    } else {
+      self.system_dictionary
+    };
+    let local_id = (word_id & !USER_WORD_ID_FLAG) as usize;
+    match dictionary {
+      Some(dictionary) if local_id < dictionary.lexicon.size() => {
+        self.word_key(dictionary, local_id).to_string()
+      }
+      _ => word_id.to_string(),
+    }
+  }
+  fn entries(&self) -> BTreeMap<WordKey, Vec<Entry>> {
+    let lexicon = &self.dictionary.lexicon;
+    let mut entries: BTreeMap<WordKey, Vec<Entry>> = BTreeMap::new();
+    for word_id in 0..lexicon.size() {
+      let word_info = lexicon.get_word_info(word_id);
+      let split = |split: &[i32]| split.iter().map(|id| self.split_word(*id)).collect();
+      entries
+        .entry(self.word_key(self.dictionary, word_id))
+        .or_default()
+        .push(Entry {
+          left_id: lexicon.get_left_id(word_id),
+          right_id: lexicon.get_right_id(word_id),
+          cost: lexicon.get_cost(word_id),
+          a_unit_split: split(&word_info.a_unit_split),
+          b_unit_split: split(&word_info.b_unit_split),
+          word_structure: split(&word_info.word_structure),
+          normalized_form: word_info.normalized_form,
+        });
+    }
+    entries
+  }
+}
+
+impl DictionaryDiff {
+  /// Compare `old` with `new`, given the system dictionaries of user dictionaries
+  ///
+  /// Connection costs are reported when they change by more than `threshold`. Fails if a user
+  /// dictionary does not fit its system dictionary.
+  pub fn new(
+    old: &BinaryDictionary,
+    old_system: Option<&BinaryDictionary>,
+    new: &BinaryDictionary,
+    new_system: Option<&BinaryDictionary>,
+    threshold: u16,
+  ) -> Result<DictionaryDiff, ReadDictionaryErr> {
+    let mut old_entries = Side::new(old, old_system)?.entries();
+    let mut new_entries = Side::new(new, new_system)?.entries();
+
+    let mut added = vec![];
+    let mut removed = vec![];
+    let mut changed = vec![];
+    for (key, old_words) in old_entries.iter_mut() {
+      let mut new_words = new_entries.remove(key).unwrap_or_default().into_iter();
+      for old_word in old_words.drain(..) {
+        match new_words.next() {
+          Some(new_word) => {
+            let changes = compare(&old_word, &new_word);
+            if !changes.is_empty() {
+              changed.push((key.clone(), changes));
+            }
+          }
+          None => removed.push(key.clone()),
+        }
+      }
+      added.extend(new_words.map(|_| key.clone()));
+    }
+    for (key, new_words) in new_entries {
+      added.extend(new_words.iter().map(|_| key.clone()));
+    }
+    added.sort();
+
+    let (old_grammar, new_grammar) = (&old.grammar, &new.grammar);
+    let old_size = (
+      old_grammar.get_left_id_size(),
+      old_grammar.get_right_id_size(),
+    );
+    let new_size = (
+      new_grammar.get_left_id_size(),
+      new_grammar.get_right_id_size(),
+    );
+    let mut connections = vec![];
+    for left_id in 0..old_size.0.min(new_size.0) {
+      for right_id in 0..old_size.1.min(new_size.1) {
+        let old_cost = old_grammar.get_connect_cost(left_id, right_id);
+        let new_cost = new_grammar.get_connect_cost(left_id, right_id);
+        if (i32::from(old_cost) - i32::from(new_cost)).abs() > i32::from(threshold) {
+          connections.push(ConnectionChange {
+            left_id,
+            right_id,
+            old: old_cost,
+            new: new_cost,
+          });
+        }
+      }
+    }
+
+    Ok(DictionaryDiff {
+      added,
+      removed,
+      changed,
+      matrix_sizes: if old_size != new_size {
+        Some((old_size, new_size))
+      } else {
+        None
+      },
+      connections,
+    })
+  }
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty()
+      && self.removed.is_empty()
+      && self.changed.is_empty()
+      && self.matrix_sizes.is_none()
+      && self.connections.is_empty()
+  }
+  pub fn to_json(&self) -> Value {
+    json!({
+      "added": self.added.iter().map(WordKey::to_json).collect::<Vec<Value>>(),
+      "removed": self.removed.iter().map(WordKey::to_json).collect::<Vec<Value>>(),
+      "changed": self
+        .changed
+        .iter()
+        .map(|(key, changes)| {
+          let mut word = key.to_json();
+          word["changes"] = changes.iter().map(WordChange::to_json).collect();
+          word
+        })
+        .collect::<Vec<Value>>(),
+      "matrix_sizes": self.matrix_sizes.map(|(old, new)| json!({
+        "old": [old.0, old.1],
+        "new": [new.0, new.1],
+      })),
+      "connections": self
+        .connections
+        .iter()
+        .map(|c| json!({"left_id": c.left_id, "right_id": c.right_id, "old": c.old, "new": c.new}))
+        .collect::<Vec<Value>>(),
+    })
+  }
+}
+
+fn compare(old: &Entry, new: &Entry) -> Vec<WordChange> {
+  let mut changes = vec![];
+  if old.cost != new.cost {
+    changes.push(WordChange::Cost(old.cost, new.cost));
+  }
+  if (old.left_id, old.right_id) != (new.left_id, new.right_id) {
+    changes.push(WordChange::ConnectionIds(
+      (old.left_id, old.right_id),
+      (new.left_id, new.right_id),
+    ));
+  }
+  if old.normalized_form != new.normalized_form {
+    changes.push(WordChange::NormalizedForm(
+      old.normalized_form.clone(),
+      new.normalized_form.clone(),
+    ));
+  }
+  for (field, old_split, new_split) in [
+    ("A split", &old.a_unit_split, &new.a_unit_split),
+    ("B split", &old.b_unit_split, &new.b_unit_split),
+    ("word structure", &old.word_structure, &new.word_structure),
+  ] {
+    if old_split != new_split {
+      changes.push(WordChange::Split(
+        field,
+        old_split.clone(),
+        new_split.clone(),
+      ));
+    }
+  }
+  changes
+}
+
+impl fmt::Display for DictionaryDiff {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for key in &self.removed {
+      writeln!(f, "- {}", key)?;
+    }
+    for key in &self.added {
+      writeln!(f, "+ {}", key)?;
+    }
+    for (key, changes) in &self.changed {
+      let changes: Vec<String> = changes.iter().map(|change| change.to_string()).collect();
+      writeln!(f, "~ {}: {}", key, changes.join("; "))?;
+    }
+    if let Some((old, new)) = self.matrix_sizes {
+      writeln!(f, "matrix size {} {} -> {} {}", old.0, old.1, new.0, new.1)?;
+    }
+    for c in &self.connections {
+      writeln!(
+        f,
+        "connection {} {}: {} -> {}",
+        c.left_id, c.right_id, c.old, c.new
+      )?;
+    }
+    writeln!(
+      f,
+      "{} added, {} removed, {} changed, {} connections changed",
+      self.added.len(),
+      self.removed.len(),
+      self.changed.len(),
+      self.connections.len()
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::dictionary_builder::DictionaryBuilder;
+  use crate::dictionary_lib::dictionary_header::DictionaryHeader;
+  use crate::dictionary_lib::dictionary_printer::{DictionaryPrinter, SplitFormat};
+  use crate::dictionary_lib::system_dictionary_version::SYSTEM_DICT_VERSION;
+  use std::env::temp_dir;
+  use std::fs::{create_dir_all, write};
+  use std::io::{Cursor, Write};
+
+  fn system_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_system_dictionary_bytes(include_bytes!("../resources/test/system.dic"))
+      .unwrap()
+  }
+
+  fn user_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_user_dictionary_bytes(include_bytes!("../resources/test/user.dic"))
+      .unwrap()
+  }
+
+  fn rebuild_system(name: &str, edit: fn(String, String) -> (String, String)) -> BinaryDictionary {
+    let system = system_dictionary();
+    let printer = DictionaryPrinter::new(&system, None, SplitFormat::WordId).unwrap();
+    let (mut lexicon, mut matrix) = (vec![], vec![]);
+    printer.print_lexicon(&mut lexicon).unwrap();
+    printer.print_matrix(&mut matrix).unwrap();
+    let (lexicon, matrix) = edit(
+      String::from_utf8(lexicon).unwrap(),
+      String::from_utf8(matrix).unwrap(),
+    );
+
+    let dir = temp_dir().join("sudachiclone_dictionary_diff");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.csv", name));
+    write(&path, lexicon).unwrap();
+    let mut stream = Cursor::new(vec![]);
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 0, String::from(""));
+    stream.write_all(&header.to_bytes().unwrap()).unwrap();
+    DictionaryBuilder::default()
+      .build(
+        &[path.to_str().unwrap()],
+        Some(&mut Cursor::new(matrix.as_bytes())),
+        &mut stream,
+      )
+      .unwrap();
+    BinaryDictionary::from_system_dictionary_bytes(stream.get_ref()).unwrap()
+  }
+
+  #[test]
+  fn test_same_dictionary() {
+    let system = system_dictionary();
+    let diff = DictionaryDiff::new(&system, None, &system, None, 0).unwrap();
+    assert!(diff.is_empty());
+    assert_eq!(
+      "0 added, 0 removed, 0 changed, 0 connections changed\n",
+      diff.to_string()
+    );
+  }
+
+  #[test]
+  fn test_diff() {
+    let new = rebuild_system("new", |lexicon, matrix| {
+      let lexicon = lexicon
+        .replace("東,7,7,4675,", "東,7,7,4000,")
+        .replace(
+          "トウキョウト,東京都,*,B,5/9,*,5/9",
+          "トウキョウト,東京都,*,B,5/4,*,5/9",
+        )
+        .replace(
+          "特a,8,8,2914,特a,名詞,普通名詞,一般,*,*,*,トクエー,特a,*,A,*,*,*",
+          "すだち,8,8,2914,すだち,名詞,普通名詞,一般,*,*,*,スダチ,すだち,*,A,*,*,*",
+        );
+      let matrix = matrix
+        .replace("\n0 1 863\n", "\n0 1 900\n")
+        .replace("\n0 2 2124\n", "\n0 2 2129\n");
+      (lexicon, matrix)
+    });
+    let diff = DictionaryDiff::new(&system_dictionary(), None, &new, None, 100).unwrap();
+
+    let noun = |pos: &[&str]| pos.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+    assert_eq!(
+      vec![WordKey {
+        surface: String::from("すだち"),
+        pos: noun(&["名詞", "普通名詞", "一般", "*", "*", "*"]),
+        reading_form: String::from("スダチ"),
+      }],
+      diff.added
+    );
+    assert_eq!(1, diff.removed.len());
+    assert_eq!("特a", diff.removed[0].surface);
+    let changed: Vec<(String, Vec<String>)> = diff
+      .changed
+      .iter()
+      .map(|(key, changes)| {
+        (
+          key.surface.clone(),
+          changes.iter().map(|change| change.to_string()).collect(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      vec![
+        (String::from("東"), vec![String::from("cost 4675 -> 4000")]),
+        (
+          String::from("東京都"),
+          vec![String::from(
+            "A split 東京,名詞,固有名詞,地名,一般,*,*,トウキョウ/都,名詞,普通名詞,一般,*,*,*,ト -> \
+             東京,名詞,固有名詞,地名,一般,*,*,トウキョウ/東,名詞,普通名詞,一般,*,*,*,ヒガシ"
+          )]
+        ),
+      ],
+      changed
+    );
+    // only the cell changed by more than the threshold
+    assert_eq!(None, diff.matrix_sizes);
+    assert_eq!(
+      vec![ConnectionChange {
+        left_id: 0,
+        right_id: 1,
+        old: 863,
+        new: 900,
+      }],
+      DictionaryDiff::new(&system_dictionary(), None, &new, None, 10)
+        .unwrap()
+        .connections
+    );
+    assert!(diff.connections.is_empty());
+
+    let json = diff.to_json();
+    assert_eq!("すだち", json["added"][0]["surface"]);
+    assert_eq!("cost", json["changed"][0]["changes"][0]["field"]);
+    assert_eq!(4000, json["changed"][0]["changes"][0]["new"]);
+  }
+
+  #[test]
+  fn test_user_dictionary() {
+    let system = system_dictionary();
+    let user = user_dictionary();
+    let diff = DictionaryDiff::new(&user, Some(&system), &user, Some(&system), 0).unwrap();
+    assert!(diff.is_empty());
+
+    // without its system dictionary, no POS id of a user dictionary can be named
+    let keys = Side::new(&user, None).unwrap().entries();
+    assert!(keys
+      .keys()
+      .all(|key| key.pos.len() == 1 && key.pos[0].starts_with('#')));
+    let keys = Side::new(&user, Some(&system)).unwrap().entries();
+    assert!(keys.keys().all(|key| key.pos.len() == 6));
+  }
+
+  #[test]
+  fn test_user_dictionary_of_another_system_dictionary() {
+    // a system dictionary with a single word and part of speech
+    let small = rebuild_system("small", |lexicon, matrix| {
+      (format!("{}\n", lexicon.lines().next().unwrap()), matrix)
+    });
+    let system = system_dictionary();
+    let user = user_dictionary();
+    assert!(DictionaryDiff::new(&user, Some(&small), &user, Some(&system), 0).is_err());
+    assert!(DictionaryDiff::new(&user, Some(&system), &user, Some(&small), 0).is_err());
+  }
+}
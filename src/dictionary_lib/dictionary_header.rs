@@ -1,3 +1,4 @@
+use std::env;
 use std::io::{BufRead, Cursor, Error as IOError, Seek, SeekFrom, Write};
 use std::string::FromUtf8Error;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -6,6 +7,9 @@ use byteorder::{LittleEndian, ReadBytesExt};
 use thiserror::Error;
 
 use super::io::LittleEndianWrite;
+use super::system_dictionary_version::{
+  SYSTEM_DICT_VERSION, USER_DICT_VERSION_1, USER_DICT_VERSION_2,
+};
 
 pub struct DictionaryHeader {
   pub version: u64,
@@ -20,6 +24,8 @@ const STORAGE_SIZE: usize = 8 + 8 + DESCRIPTION_SIZE;
 pub enum DictionaryHeaderErr {
   #[error("description is too long")]
   DescriptionTooLongErr,
+  #[error("invalid timestamp: {0}")]
+  InvalidTimestampErr(String),
   #[error("{0}")]
   IOError(#[from] IOError),
   #[error("{0}")]
@@ -40,14 +46,13 @@ impl DictionaryHeader {
     let offset = reader.seek(SeekFrom::Current(0))?;
     let version = reader.read_u64::<LittleEndian>()?;
     let create_time = reader.read_u64::<LittleEndian>()?;
-    let mut buf = Vec::with_capacity(DESCRIPTION_SIZE);
-    reader.read_until(0u8, &mut buf)?;
-    buf.pop().unwrap();
-    if buf.len() > DESCRIPTION_SIZE {
-      buf.truncate(DESCRIPTION_SIZE);
+    let mut buf = vec![0u8; DESCRIPTION_SIZE];
+    reader.read_exact(&mut buf)?;
+    // the description is terminated by a NUL unless it fills the whole field
+    if let Some(end) = buf.iter().position(|b| *b == 0) {
+      buf.truncate(end);
     }
     reader.seek(SeekFrom::Start(offset + STORAGE_SIZE as u64))?;
-    // buf.pop().unwrap();
     let description = String::from_utf8(buf)?;
 
     Ok(DictionaryHeader {
@@ -62,6 +67,34 @@ impl DictionaryHeader {
       .unwrap()
       .as_secs()
   }
+  /// The create time of a dictionary built now: `SOURCE_DATE_EPOCH` if it is set, so that the
+  /// same sources give the same bytes, or else the current time
+  pub fn get_build_time() -> Result<u64, DictionaryHeaderErr> {
+    match env::var("SOURCE_DATE_EPOCH") {
+      Ok(epoch) => DictionaryHeader::parse_timestamp(&epoch),
+      Err(_) => Ok(DictionaryHeader::get_time()),
+    }
+  }
+  /// Seconds since the Unix epoch, as `SOURCE_DATE_EPOCH` or `--timestamp` give them
+  pub fn parse_timestamp(timestamp: &str) -> Result<u64, DictionaryHeaderErr> {
+    timestamp
+      .trim()
+      .parse()
+      .map_err(|_| DictionaryHeaderErr::InvalidTimestampErr(timestamp.to_string()))
+  }
+  /// Kind of the dictionary told by `version`
+  pub fn version_name(&self) -> &'static str {
+    match self.version {
+      SYSTEM_DICT_VERSION => "system",
+      USER_DICT_VERSION_1 => "user (v1)",
+      USER_DICT_VERSION_2 => "user (v2)",
+      _ => "unknown",
+    }
+  }
+  /// `create_time` as `YYYY-MM-DD hh:mm:ss` in UTC
+  pub fn create_time_string(&self) -> String {
+    time_string(self.create_time)
+  }
   pub fn to_bytes(&self) -> Result<Vec<u8>, DictionaryHeaderErr> {
     let mut cursor = Cursor::new(vec![0; 16 + DESCRIPTION_SIZE]);
     cursor.write_u64(self.version)?;
@@ -75,11 +108,34 @@ impl DictionaryHeader {
   }
 }
 
+/// Seconds since the Unix epoch as `YYYY-MM-DD hh:mm:ss` in UTC
+pub fn time_string(secs: u64) -> String {
+  let (days, rem) = (secs / 86400, secs % 86400);
+  // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+  let z = days as i64 + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!(
+    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+    year,
+    month,
+    day,
+    rem / 3600,
+    rem % 3600 / 60,
+    rem % 60
+  )
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::dictionary_lib::system_dictionary_version::SYSTEM_DICT_VERSION;
   use std::fs::File;
   use std::io::BufReader;
   use std::path::PathBuf;
@@ -114,6 +170,26 @@ mod tests {
     assert!(header.create_time > 0);
   }
 
+  #[test]
+  fn test_create_time_string() {
+    let header = read_header();
+    assert_eq!("2019-12-06 06:26:12", header.create_time_string());
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 951_782_400, String::new());
+    assert_eq!("2000-02-29 00:00:00", header.create_time_string());
+  }
+
+  #[test]
+  fn test_parse_timestamp() {
+    assert_eq!(
+      951_782_400,
+      DictionaryHeader::parse_timestamp("951782400").unwrap()
+    );
+    assert_eq!(0, DictionaryHeader::parse_timestamp("0\n").unwrap());
+    assert!(DictionaryHeader::parse_timestamp("").is_err());
+    assert!(DictionaryHeader::parse_timestamp("-1").is_err());
+    assert!(DictionaryHeader::parse_timestamp("2020-01-27").is_err());
+  }
+
   #[test]
   fn test_description() {
     let header = read_header();
@@ -0,0 +1,275 @@
+use std::io::{Seek, Write};
+
+use log::info;
+
+use super::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
+use super::dictionary_builder::{DictionaryBuilder, DictionaryBuilderErr, LexiconRow};
+use super::dictionary_printer::escape;
+use super::lexicon::{Lexicon, Size};
+use crate::tokenizer::CanTokenize;
+
+const USER_WORD_ID_FLAG: i32 = 1 << 28;
+
+/// Merges user dictionaries into their system dictionary, giving one system dictionary
+///
+/// Words of the system dictionary keep their ids and parts of speech, and the words of each
+/// user dictionary follow them. Split info and dictionary forms are renumbered accordingly and
+/// parts of speech with the same name are unified.
+pub struct DictionaryMerger {
+  system_dictionary: BinaryDictionary,
+  user_dictionaries: Vec<BinaryDictionary>,
+}
+
+impl DictionaryMerger {
+  pub fn new(system_dictionary: BinaryDictionary) -> DictionaryMerger {
+    DictionaryMerger {
+      system_dictionary,
+      user_dictionaries: vec![],
+    }
+  }
+  /// Add a user dictionary of the system dictionary, computing the costs it leaves to loading
+  /// with `tokenizer`
+  ///
+  /// Fails if the user dictionary does not fit the system dictionary.
+  pub fn add_user_dictionary<T: CanTokenize>(
+    &mut self,
+    mut user_dictionary: BinaryDictionary,
+    tokenizer: T,
+  ) -> Result<(), ReadDictionaryErr> {
+    user_dictionary.validate_against(&self.system_dictionary)?;
+    user_dictionary.lexicon.calculate_cost(tokenizer);
+    self.user_dictionaries.push(user_dictionary);
+    Ok(())
+  }
+  /// Write the merged grammar and lexicon, without a header
+  pub fn merge<W: Write + Seek>(&self, writer: &mut W) -> Result<(), DictionaryBuilderErr> {
+    let system_grammar = &self.system_dictionary.grammar;
+    let system_pos_list: Vec<Vec<String>> = (0..system_grammar.get_part_of_speech_size())
+      .map(|pos_id| system_grammar.get_part_of_speech_string(pos_id).clone())
+      .collect();
+    let mut builder = DictionaryBuilder::with_pos_list(&system_pos_list);
+
+    info!("reading the dictionaries...");
+    let mut base = 0;
+    builder.build_rows("system", self.rows(&self.system_dictionary, base));
+    base += self.system_dictionary.lexicon.size();
+    for (i, user_dictionary) in self.user_dictionaries.iter().enumerate() {
+      builder.build_rows(&format!("user {}", i + 1), self.rows(user_dictionary, base));
+      base += user_dictionary.lexicon.size();
+    }
+    info!("{} words", builder.entries.len());
+    builder.check_lexicon(&builder)?;
+
+    builder.write_grammar_from(system_grammar, writer)?;
+    builder.write_lexicon(writer)?;
+    Ok(())
+  }
+  /// The lexicon rows of `dictionary` whose first word gets the merged id `base`
+  fn rows(&self, dictionary: &BinaryDictionary, base: usize) -> Vec<LexiconRow> {
+    let lexicon = &dictionary.lexicon;
+    let mut headwords = vec![None; lexicon.size()];
+    for (headword, word_ids) in lexicon.get_headwords() {
+      for word_id in word_ids {
+        headwords[word_id] = Some(headword.clone());
+      }
+    }
+    let merged_id = |word_id: i32| {
+      if !dictionary.is_user_dictionary() {
+        word_id as usize
+      } else if word_id & USER_WORD_ID_FLAG != 0 {
+        base + (word_id & !USER_WORD_ID_FLAG) as usize
+      } else {
+        // a word of the system dictionary
+        word_id as usize
+      }
+    };
+    let format_split = |split: &[i32]| {
+      if split.is_empty() {
+        return String::from("*");
+      }
+      split
+        .iter()
+        .map(|word_id| merged_id(*word_id).to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+    };
+
+    let mut rows = vec![];
+    for (word_id, headword) in headwords.into_iter().enumerate() {
+      let word_info = lexicon.get_word_info(word_id);
+      let mode = if !word_info.b_unit_split.is_empty() {
+        "C"
+      } else if !word_info.a_unit_split.is_empty() {
+        "B"
+      } else {
+        "A"
+      };
+      let dictionary_form = if word_info.dictionary_form_word_id < 0 {
+        String::from("*")
+      } else {
+        // the dictionary form is always a word of the same dictionary
+        (base + word_info.dictionary_form_word_id as usize).to_string()
+      };
+      let mut cols = vec![
+        escape(&headword.unwrap_or_else(|| word_info.surface.clone())),
+        lexicon.get_left_id(word_id).to_string(),
+        lexicon.get_right_id(word_id).to_string(),
+        lexicon.get_cost(word_id).to_string(),
+        escape(&word_info.surface),
+      ];
+      cols.extend(
+        self
+          .pos_string(dictionary, word_info.pos_id)
+          .iter()
+          .map(|p| escape(p)),
+      );
+      cols.extend(vec![
+        escape(&word_info.reading_form),
+        escape(&word_info.normalized_form),
+        dictionary_form,
+        String::from(mode),
+        format_split(&word_info.a_unit_split),
+        format_split(&word_info.b_unit_split),
+        format_split(&word_info.word_structure),
+      ]);
+      rows.push((word_id + 1, Ok(cols)));
+    }
+    rows
+  }
+  fn pos_string<'a>(&'a self, dictionary: &'a BinaryDictionary, pos_id: i16) -> &'a Vec<String> {
+    let system_grammar = &self.system_dictionary.grammar;
+    let pos_id = pos_id as usize;
+    if pos_id < system_grammar.get_part_of_speech_size() {
+      system_grammar.get_part_of_speech_string(pos_id)
+    } else {
+      dictionary
+        .grammar
+        .get_part_of_speech_string(pos_id - system_grammar.get_part_of_speech_size())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary::Dictionary;
+  use crate::dictionary_lib::dictionary_header::DictionaryHeader;
+  use crate::dictionary_lib::double_array_lexicon::SIGNED_SHORT_MIN;
+  use crate::dictionary_lib::system_dictionary_version::SYSTEM_DICT_VERSION;
+  use std::env::temp_dir;
+  use std::io::Cursor;
+
+  fn read_system_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_system_dictionary_bytes(include_bytes!("../resources/test/system.dic"))
+      .unwrap()
+  }
+
+  fn read_user_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_user_dictionary_bytes(include_bytes!("../resources/test/user.dic"))
+      .unwrap()
+  }
+
+  fn build_dictionary(system_dic: &[u8], user_dics: &[&[u8]]) -> Dictionary {
+    Dictionary::from_bytes(
+      system_dic,
+      include_bytes!("../resources/test/char.def"),
+      include_bytes!("../resources/test/unk.def"),
+      include_bytes!("../resources/test/rewrite.def"),
+      user_dics,
+      Some(include_str!("../resources/test/sudachi.json")),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_merge() {
+    let system_only = build_dictionary(include_bytes!("../resources/test/system.dic"), &[]);
+    let mut merger = DictionaryMerger::new(read_system_dictionary());
+    merger
+      .add_user_dictionary(read_user_dictionary(), system_only.create())
+      .unwrap();
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 0, String::from("merged"));
+    let mut writer = Cursor::new(header.to_bytes().unwrap());
+    writer.set_position(writer.get_ref().len() as u64);
+    merger.merge(&mut writer).unwrap();
+    let merged_dic = writer.into_inner();
+
+    let system = read_system_dictionary();
+    let user = read_user_dictionary();
+    let merged_binary = BinaryDictionary::from_system_dictionary_bytes(&merged_dic).unwrap();
+    let system_size = system.lexicon.size();
+    assert_eq!(
+      system_size + user.lexicon.size(),
+      merged_binary.lexicon.size()
+    );
+    // system words and parts of speech keep their ids
+    for pos_id in 0..system.grammar.get_part_of_speech_size() {
+      assert_eq!(
+        system.grammar.get_part_of_speech_string(pos_id),
+        merged_binary.grammar.get_part_of_speech_string(pos_id)
+      );
+    }
+    assert_eq!("行く", merged_binary.lexicon.get_word_info(7).surface);
+    // 東京府 is split into 東京 of the system dictionary and 府 of the user dictionary
+    let (word_id, word_info) = build_dictionary(&merged_dic, &[])
+      .exact_lookup("東京府")
+      .into_iter()
+      .next()
+      .unwrap();
+    assert_eq!(system_size + 2, word_id);
+    assert_eq!(vec![5, system_size as i32 + 1], word_info.a_unit_split);
+    assert_eq!(
+      "府",
+      merged_binary.lexicon.get_word_info(system_size + 1).surface
+    );
+    // ぴらる has its cost computed
+    assert!(merged_binary.lexicon.get_cost(system_size) > SIGNED_SHORT_MIN);
+
+    let with_user = build_dictionary(
+      include_bytes!("../resources/test/system.dic"),
+      &[include_bytes!("../resources/test/user.dic")],
+    );
+    let merged = build_dictionary(&merged_dic, &[]);
+    let tokenize = |dictionary: &Dictionary, text: &str| {
+      dictionary
+        .create()
+        .tokenize(text, None, None)
+        .unwrap()
+        .iter()
+        .map(|m| {
+          (
+            m.surface(),
+            m.part_of_speech(),
+            m.normalized_form().to_string(),
+          )
+        })
+        .collect::<Vec<(String, Vec<String>, String)>>()
+    };
+    for text in &["東京府", "ぴらる", "すだち", "京都に行く"] {
+      assert_eq!(tokenize(&with_user, text), tokenize(&merged, text));
+    }
+  }
+
+  #[test]
+  fn test_add_user_dictionary_of_another_system_dictionary() {
+    // a system dictionary with a single word and part of speech
+    let path = temp_dir().join("sudachiclone_dictionary_merger_small.csv");
+    std::fs::write(&path, "た,0,0,0,た,助動詞,*,*,*,*,*,タ,た,*,A,*,*,*\n").unwrap();
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 0, String::from(""));
+    let mut writer = Cursor::new(header.to_bytes().unwrap());
+    writer.set_position(writer.get_ref().len() as u64);
+    DictionaryBuilder::default()
+      .build(
+        &[path.to_str().unwrap()],
+        Some(&mut Cursor::new("1 1\n0 0 0\n")),
+        &mut writer,
+      )
+      .unwrap();
+    let small = BinaryDictionary::from_system_dictionary_bytes(writer.get_ref()).unwrap();
+    let system_only = build_dictionary(include_bytes!("../resources/test/system.dic"), &[]);
+    let mut merger = DictionaryMerger::new(small);
+    assert!(merger
+      .add_user_dictionary(read_user_dictionary(), system_only.create())
+      .is_err());
+  }
+}
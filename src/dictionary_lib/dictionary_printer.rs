@@ -0,0 +1,438 @@
+use std::io::{Error as IOError, Write};
+
+use thiserror::Error;
+
+use super::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
+use super::lexicon::{GetWordId, Lexicon, Size};
+
+const USER_WORD_ID_FLAG: u32 = 1 << 28;
+
+#[derive(Error, Debug)]
+pub enum DictionaryPrinterErr {
+  #[error("a user dictionary needs its system dictionary")]
+  NotFoundSystemDictionaryErr,
+  #[error("a user dictionary has no connection matrix")]
+  NotFoundMatrixErr,
+  #[error("{0}")]
+  ReadDictionaryErr(#[from] ReadDictionaryErr),
+  #[error("{0}")]
+  IOError(#[from] IOError),
+  #[error("{0}")]
+  CSVError(#[from] csv::Error),
+}
+
+/// How split info and word structure are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitFormat {
+  /// word ids, `U` prefixed for words of the user dictionary
+  WordId,
+  /// (surface, POS, reading) triples, or word ids where a triple would be ambiguous
+  Word,
+}
+
+/// Writes a binary dictionary back as the lexicon CSV and `matrix.def` it can be built from
+pub struct DictionaryPrinter<'a> {
+  dictionary: &'a BinaryDictionary,
+  system_dictionary: Option<&'a BinaryDictionary>,
+  split_format: SplitFormat,
+}
+
+impl<'a> DictionaryPrinter<'a> {
+  /// `system_dictionary` is required for a user dictionary to resolve the system's parts of
+  /// speech and words, and the user dictionary is checked against it
+  pub fn new(
+    dictionary: &'a BinaryDictionary,
+    system_dictionary: Option<&'a BinaryDictionary>,
+    split_format: SplitFormat,
+  ) -> Result<DictionaryPrinter<'a>, DictionaryPrinterErr> {
+    if dictionary.is_user_dictionary() && system_dictionary.is_none() {
+      return Err(DictionaryPrinterErr::NotFoundSystemDictionaryErr);
+    }
+    if let (true, Some(system_dictionary)) = (dictionary.is_user_dictionary(), system_dictionary) {
+      dictionary.validate_against(system_dictionary)?;
+    }
+    Ok(DictionaryPrinter {
+      dictionary,
+      system_dictionary: system_dictionary.filter(|_| dictionary.is_user_dictionary()),
+      split_format,
+    })
+  }
+  /// Write every word in word id order, so that split info in ids stays valid when rebuilt
+  pub fn print_lexicon<W: Write>(&self, writer: W) -> Result<(), DictionaryPrinterErr> {
+    let lexicon = &self.dictionary.lexicon;
+    let mut headwords = vec![None; lexicon.size()];
+    for (headword, word_ids) in lexicon.get_headwords() {
+      for word_id in word_ids {
+        headwords[word_id] = Some(headword.clone());
+      }
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+      .has_headers(false)
+      .from_writer(writer);
+    for (word_id, headword) in headwords.into_iter().enumerate() {
+      let word_info = lexicon.get_word_info(word_id);
+      let mode = if !word_info.b_unit_split.is_empty() {
+        "C"
+      } else if !word_info.a_unit_split.is_empty() {
+        "B"
+      } else {
+        "A"
+      };
+      let dictionary_form = if word_info.dictionary_form_word_id < 0 {
+        String::from("*")
+      } else {
+        word_info.dictionary_form_word_id.to_string()
+      };
+      let mut record = vec![
+        escape(&headword.unwrap_or_else(|| word_info.surface.clone())),
+        lexicon.get_left_id(word_id).to_string(),
+        lexicon.get_right_id(word_id).to_string(),
+        lexicon.get_cost(word_id).to_string(),
+        escape(&word_info.surface),
+      ];
+      record.extend(self.pos_string(word_info.pos_id).iter().map(|p| escape(p)));
+      record.extend(vec![
+        escape(&word_info.reading_form),
+        escape(&word_info.normalized_form),
+        dictionary_form,
+        String::from(mode),
+        escape(&self.format_split(&word_info.a_unit_split)),
+        escape(&self.format_split(&word_info.b_unit_split)),
+        escape(&self.format_split(&word_info.word_structure)),
+      ]);
+      writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+  }
+  /// Write the connection matrix in MeCab's matrix.def format
+  pub fn print_matrix<W: Write>(&self, writer: &mut W) -> Result<(), DictionaryPrinterErr> {
+    if self.dictionary.is_user_dictionary() {
+      return Err(DictionaryPrinterErr::NotFoundMatrixErr);
+    }
+    let grammar = &self.dictionary.grammar;
+    let left_id_size = grammar.get_left_id_size();
+    let right_id_size = grammar.get_right_id_size();
+    writeln!(writer, "{} {}", left_id_size, right_id_size)?;
+    for left_id in 0..left_id_size {
+      for right_id in 0..right_id_size {
+        writeln!(
+          writer,
+          "{} {} {}",
+          left_id,
+          right_id,
+          grammar.get_connect_cost(left_id, right_id)
+        )?;
+      }
+    }
+    Ok(())
+  }
+  fn pos_string(&self, pos_id: i16) -> &Vec<String> {
+    let pos_id = pos_id as usize;
+    match self.system_dictionary {
+      Some(system) if pos_id < system.grammar.get_part_of_speech_size() => {
+        system.grammar.get_part_of_speech_string(pos_id)
+      }
+      Some(system) => self
+        .dictionary
+        .grammar
+        .get_part_of_speech_string(pos_id - system.grammar.get_part_of_speech_size()),
+      None => self.dictionary.grammar.get_part_of_speech_string(pos_id),
+    }
+  }
+  fn format_split(&self, split: &[i32]) -> String {
+    if split.is_empty() {
+      return String::from("*");
+    }
+    split
+      .iter()
+      .map(|word_id| self.format_word(*word_id as u32))
+      .collect::<Vec<String>>()
+      .join("/")
+  }
+  fn format_word(&self, word_id: u32) -> String {
+    let is_user_word = self.system_dictionary.is_some() && word_id & USER_WORD_ID_FLAG != 0;
+    let local_id = (word_id & !USER_WORD_ID_FLAG) as usize;
+    let id = if is_user_word {
+      format!("U{}", local_id)
+    } else {
+      local_id.to_string()
+    };
+    if self.split_format == SplitFormat::WordId {
+      return id;
+    }
+    let (lexicon, is_local) = match self.system_dictionary {
+      Some(system) if !is_user_word => (&system.lexicon, false),
+      _ => (&self.dictionary.lexicon, true),
+    };
+    if local_id >= lexicon.size() {
+      return id;
+    }
+    let word_info = lexicon.get_word_info(local_id);
+    let (surface, pos_id, reading_form) = (
+      &word_info.surface,
+      word_info.pos_id,
+      &word_info.reading_form,
+    );
+    // the builder resolves a triple to its first word, in the dictionary being built first
+    let local = self
+      .dictionary
+      .lexicon
+      .get_word_id(surface, pos_id as u16, reading_form)
+      .ok();
+    let resolved = match (is_local, self.system_dictionary) {
+      (true, _) => local,
+      (false, Some(system)) if local.is_none() => system
+        .lexicon
+        .get_word_id(surface, pos_id as u16, reading_form)
+        .ok(),
+      (false, _) => None,
+    };
+    if resolved != Some(local_id) {
+      return id;
+    }
+    let mut cols = vec![escape_word_col(surface)];
+    cols.extend(self.pos_string(pos_id).iter().map(|p| escape_word_col(p)));
+    cols.push(escape_word_col(reading_form));
+    cols.join(",")
+  }
+}
+
+/// Escape backslashes, which the builder reads as the start of `\uXXXX`
+pub(crate) fn escape(text: &str) -> String {
+  text.replace('\\', "\\u005c")
+}
+
+/// Escape the separators of a (surface, POS, reading) triple; `escape` is applied on top
+fn escape_word_col(text: &str) -> String {
+  text
+    .replace('\\', "\\u005c")
+    .replace(',', "\\u002c")
+    .replace('/', "\\u002f")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::dictionary_builder::DictionaryBuilder;
+  use crate::dictionary_lib::dictionary_header::DictionaryHeader;
+  use crate::dictionary_lib::system_dictionary_version::{
+    SYSTEM_DICT_VERSION, USER_DICT_VERSION_2,
+  };
+  use crate::dictionary_lib::user_dictionary_builder::UserDictionaryBuilder;
+  use std::env::temp_dir;
+  use std::fs::{create_dir_all, write};
+  use std::io::Cursor;
+  use std::path::PathBuf;
+
+  fn system_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_system_dictionary_bytes(include_bytes!("../resources/test/system.dic"))
+      .unwrap()
+  }
+
+  fn user_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_user_dictionary_bytes(include_bytes!("../resources/test/user.dic"))
+      .unwrap()
+  }
+
+  fn write_source(name: &str, lexicon: &[u8]) -> PathBuf {
+    let dir = temp_dir().join("sudachiclone_dictionary_printer");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.csv", name));
+    write(&path, lexicon).unwrap();
+    path
+  }
+
+  fn dump(
+    dictionary: &BinaryDictionary,
+    system_dictionary: Option<&BinaryDictionary>,
+    split_format: SplitFormat,
+  ) -> Vec<u8> {
+    let printer = DictionaryPrinter::new(dictionary, system_dictionary, split_format).unwrap();
+    let mut lexicon = vec![];
+    printer.print_lexicon(&mut lexicon).unwrap();
+    lexicon
+  }
+
+  fn rebuild_system(name: &str, lexicon: &[u8], matrix: &[u8]) -> BinaryDictionary {
+    let path = write_source(name, lexicon);
+    let mut stream = Cursor::new(vec![]);
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 0, String::from(""));
+    stream.write_all(&header.to_bytes().unwrap()).unwrap();
+    DictionaryBuilder::default()
+      .build(
+        &[path.to_str().unwrap()],
+        Some(&mut Cursor::new(matrix)),
+        &mut stream,
+      )
+      .unwrap();
+    BinaryDictionary::from_system_dictionary_bytes(stream.get_ref()).unwrap()
+  }
+
+  fn rebuild_user(name: &str, lexicon: &[u8]) -> BinaryDictionary {
+    let path = write_source(name, lexicon);
+    let system = system_dictionary();
+    let mut stream = Cursor::new(vec![]);
+    let header = DictionaryHeader::new(USER_DICT_VERSION_2, 0, String::from(""));
+    stream.write_all(&header.to_bytes().unwrap()).unwrap();
+    UserDictionaryBuilder::new(system.grammar, system.lexicon)
+      .build(&[path.to_str().unwrap()], &mut stream)
+      .unwrap();
+    BinaryDictionary::from_user_dictionary_bytes(stream.get_ref()).unwrap()
+  }
+
+  fn assert_equivalent(expected: &BinaryDictionary, actual: &BinaryDictionary) {
+    let (expected_grammar, actual_grammar) = (&expected.grammar, &actual.grammar);
+    assert_eq!(
+      expected_grammar.get_part_of_speech_size(),
+      actual_grammar.get_part_of_speech_size()
+    );
+    for pos_id in 0..expected_grammar.get_part_of_speech_size() {
+      assert_eq!(
+        expected_grammar.get_part_of_speech_string(pos_id),
+        actual_grammar.get_part_of_speech_string(pos_id)
+      );
+    }
+    assert_eq!(
+      expected_grammar.get_left_id_size(),
+      actual_grammar.get_left_id_size()
+    );
+    assert_eq!(
+      expected_grammar.get_right_id_size(),
+      actual_grammar.get_right_id_size()
+    );
+    for left_id in 0..expected_grammar.get_left_id_size() {
+      for right_id in 0..expected_grammar.get_right_id_size() {
+        assert_eq!(
+          expected_grammar.get_connect_cost(left_id, right_id),
+          actual_grammar.get_connect_cost(left_id, right_id)
+        );
+      }
+    }
+
+    let (expected_lexicon, actual_lexicon) = (&expected.lexicon, &actual.lexicon);
+    assert_eq!(
+      expected_lexicon.get_headwords(),
+      actual_lexicon.get_headwords()
+    );
+    assert_eq!(expected_lexicon.size(), actual_lexicon.size());
+    for word_id in 0..expected_lexicon.size() {
+      assert_eq!(
+        expected_lexicon.get_left_id(word_id),
+        actual_lexicon.get_left_id(word_id)
+      );
+      assert_eq!(
+        expected_lexicon.get_right_id(word_id),
+        actual_lexicon.get_right_id(word_id)
+      );
+      assert_eq!(
+        expected_lexicon.get_cost(word_id),
+        actual_lexicon.get_cost(word_id)
+      );
+      assert_eq!(
+        format!("{:?}", expected_lexicon.get_word_info(word_id)),
+        format!("{:?}", actual_lexicon.get_word_info(word_id))
+      );
+    }
+  }
+
+  #[test]
+  fn test_print_matrix() {
+    let dictionary = system_dictionary();
+    let printer = DictionaryPrinter::new(&dictionary, None, SplitFormat::WordId).unwrap();
+    let mut matrix = vec![];
+    printer.print_matrix(&mut matrix).unwrap();
+    let matrix = String::from_utf8(matrix).unwrap();
+    let mut lines = matrix.lines();
+    assert_eq!(Some("10 10"), lines.next());
+    assert_eq!(100, lines.count());
+  }
+
+  #[test]
+  fn test_print_lexicon() {
+    let dictionary = system_dictionary();
+    let lexicon = String::from_utf8(dump(&dictionary, None, SplitFormat::WordId)).unwrap();
+    assert_eq!(
+      Some("た,1,1,8729,た,助動詞,*,*,*,助動詞-タ,終止形-一般,タ,た,*,A,*,*,*"),
+      lexicon.lines().next()
+    );
+    assert_eq!(dictionary.lexicon.size(), lexicon.lines().count());
+  }
+
+  #[test]
+  fn test_user_dictionary_needs_system_dictionary() {
+    let dictionary = user_dictionary();
+    assert!(DictionaryPrinter::new(&dictionary, None, SplitFormat::WordId).is_err());
+    let system = system_dictionary();
+    let printer = DictionaryPrinter::new(&dictionary, Some(&system), SplitFormat::WordId).unwrap();
+    assert!(printer.print_matrix(&mut vec![]).is_err());
+  }
+
+  #[test]
+  fn test_user_dictionary_of_another_system_dictionary() {
+    let system = system_dictionary();
+    let mut matrix = vec![];
+    DictionaryPrinter::new(&system, None, SplitFormat::WordId)
+      .unwrap()
+      .print_matrix(&mut matrix)
+      .unwrap();
+    // a system dictionary with a single word and part of speech
+    let lexicon = dump(&system, None, SplitFormat::WordId);
+    let first_line = lexicon.split(|b| *b == b'\n').next().unwrap();
+    let small = rebuild_system("small", first_line, &matrix);
+    let dictionary = user_dictionary();
+    match DictionaryPrinter::new(&dictionary, Some(&small), SplitFormat::WordId) {
+      Err(DictionaryPrinterErr::ReadDictionaryErr(_)) => (),
+      _ => panic!("the user dictionary should not match the system dictionary"),
+    }
+  }
+
+  #[test]
+  fn test_rebuild_system_dictionary() {
+    let dictionary = system_dictionary();
+    let mut matrix = vec![];
+    DictionaryPrinter::new(&dictionary, None, SplitFormat::WordId)
+      .unwrap()
+      .print_matrix(&mut matrix)
+      .unwrap();
+    for (name, split_format) in &[
+      ("system_id", SplitFormat::WordId),
+      ("system_word", SplitFormat::Word),
+    ] {
+      let lexicon = dump(&dictionary, None, *split_format);
+      assert_equivalent(&dictionary, &rebuild_system(name, &lexicon, &matrix));
+    }
+  }
+
+  #[test]
+  fn test_rebuild_user_dictionary() {
+    let system = system_dictionary();
+    let dictionary = user_dictionary();
+    for (name, split_format) in &[
+      ("user_id", SplitFormat::WordId),
+      ("user_word", SplitFormat::Word),
+    ] {
+      let lexicon = dump(&dictionary, Some(&system), *split_format);
+      assert_equivalent(&dictionary, &rebuild_user(name, &lexicon));
+    }
+  }
+
+  #[test]
+  fn test_rebuild_escaped_words() {
+    let source = "a\\b,0,0,0,a\\b,名詞,*,*,*,*,*,ア,a\\b,*,A,*,*,*\n\
+                  \"x,y\",0,0,0,\"x,y\",名詞,*,*,*,*,*,エ/イ,\"x,y\",*,A,*,*,*\n\
+                  ab,0,0,0,ab,名詞,*,*,*,*,*,アブ,ab,*,B,0/1,*,*\n";
+    let original = rebuild_system("escaped", source.as_bytes(), b"1 1\n0 0 0\n");
+    let lexicon = dump(&original, None, SplitFormat::Word);
+    let text = String::from_utf8(lexicon.clone()).unwrap();
+    assert!(text.contains("a\\u005cb"));
+    assert!(text.contains("x\\u005cu002cy"));
+    assert!(text.contains("エ\\u005cu002fイ"));
+    assert_equivalent(
+      &original,
+      &rebuild_system("escaped_dump", &lexicon, b"1 1\n0 0 0\n"),
+    );
+  }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
+use super::dictionary_trailer::DictionaryTrailer;
+use super::double_array_lexicon::SIGNED_SHORT_MIN;
+use super::grammar::INHIBITED_CONNECTION;
+use super::lexicon::{Lexicon, Size};
+
+/// What a binary dictionary contains, as shown by `sudachiclone info`
+pub struct DictionaryStats {
+  pub version_name: &'static str,
+  pub create_time: String,
+  pub description: String,
+  pub part_of_speech_size: usize,
+  pub left_id_size: usize,
+  pub right_id_size: usize,
+  /// connections with a non-zero cost
+  pub weighted_connections: usize,
+  pub inhibited_connections: usize,
+  pub word_size: usize,
+  pub trie_size: usize,
+  /// word counts by the first level of POS, most frequent first; `None` if unresolved
+  pub words_per_pos: Vec<(Option<String>, usize)>,
+  /// words whose cost is computed when the user dictionary is loaded
+  pub auto_cost_words: Option<usize>,
+  pub trailer: Option<DictionaryTrailer>,
+  /// whether the content still has the checksum of the trailer, if it was verified
+  pub checksum_verified: Option<bool>,
+}
+
+impl DictionaryStats {
+  /// `system_dictionary` resolves the parts of speech a user dictionary shares with it, and
+  /// the user dictionary is checked against it
+  pub fn new(
+    dictionary: &BinaryDictionary,
+    system_dictionary: Option<&BinaryDictionary>,
+  ) -> Result<DictionaryStats, ReadDictionaryErr> {
+    if let (true, Some(system_dictionary)) = (dictionary.is_user_dictionary(), system_dictionary) {
+      dictionary.validate_against(system_dictionary)?;
+    }
+    let grammar = &dictionary.grammar;
+    let mut weighted_connections = 0;
+    let mut inhibited_connections = 0;
+    for left_id in 0..grammar.get_left_id_size() {
+      for right_id in 0..grammar.get_right_id_size() {
+        match grammar.get_connect_cost(left_id, right_id) {
+          0 => (),
+          INHIBITED_CONNECTION => {
+            weighted_connections += 1;
+            inhibited_connections += 1;
+          }
+          _ => weighted_connections += 1,
+        }
+      }
+    }
+
+    let lexicon = &dictionary.lexicon;
+    let system_pos_size = match (dictionary.is_user_dictionary(), system_dictionary) {
+      (true, Some(system)) => Some(system.grammar.get_part_of_speech_size()),
+      (true, None) => None,
+      (false, _) => Some(0),
+    };
+    let mut words_per_pos = HashMap::new();
+    let mut auto_cost_words = 0;
+    for word_id in 0..lexicon.size() {
+      let pos_id = lexicon.get_word_info(word_id).pos_id as usize;
+      let pos = match (system_pos_size, system_dictionary) {
+        (Some(size), Some(system)) if pos_id < size => {
+          Some(&system.grammar.get_part_of_speech_string(pos_id)[0])
+        }
+        (Some(size), _) => Some(&grammar.get_part_of_speech_string(pos_id - size)[0]),
+        (None, _) => None,
+      };
+      *words_per_pos.entry(pos.cloned()).or_insert(0) += 1;
+      if lexicon.get_cost(word_id) == SIGNED_SHORT_MIN {
+        auto_cost_words += 1;
+      }
+    }
+    let mut words_per_pos: Vec<(Option<String>, usize)> = words_per_pos.into_iter().collect();
+    words_per_pos
+      .sort_by(|(pos1, count1), (pos2, count2)| count2.cmp(count1).then_with(|| pos1.cmp(pos2)));
+
+    Ok(DictionaryStats {
+      version_name: dictionary.header.version_name(),
+      create_time: dictionary.header.create_time_string(),
+      description: dictionary.header.description.clone(),
+      part_of_speech_size: grammar.get_part_of_speech_size(),
+      left_id_size: grammar.get_left_id_size(),
+      right_id_size: grammar.get_right_id_size(),
+      weighted_connections,
+      inhibited_connections,
+      word_size: lexicon.size(),
+      trie_size: lexicon.get_trie_size(),
+      words_per_pos,
+      auto_cost_words: if dictionary.is_user_dictionary() {
+        Some(auto_cost_words)
+      } else {
+        None
+      },
+      trailer: dictionary.trailer.clone(),
+      checksum_verified: None,
+    })
+  }
+  pub fn connection_size(&self) -> usize {
+    self.left_id_size * self.right_id_size
+  }
+  /// Share of connections with a non-zero cost
+  pub fn connection_density(&self) -> f64 {
+    share(self.weighted_connections, self.connection_size())
+  }
+  pub fn inhibited_share(&self) -> f64 {
+    share(self.inhibited_connections, self.connection_size())
+  }
+}
+
+fn share(count: usize, total: usize) -> f64 {
+  if total == 0 {
+    0.0
+  } else {
+    count as f64 / total as f64
+  }
+}
+
+impl fmt::Display for DictionaryStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{:<17}{}", "version:", self.version_name)?;
+    writeln!(f, "{:<17}{} (UTC)", "create time:", self.create_time)?;
+    writeln!(f, "{:<17}{}", "description:", self.description)?;
+    writeln!(f, "{:<17}{}", "POS:", self.part_of_speech_size)?;
+    writeln!(f, "{:<17}{}", "left ids:", self.left_id_size)?;
+    writeln!(f, "{:<17}{}", "right ids:", self.right_id_size)?;
+    writeln!(
+      f,
+      "{:<17}{} (density {:.2}%, inhibited {:.2}%)",
+      "connections:",
+      self.connection_size(),
+      self.connection_density() * 100.0,
+      self.inhibited_share() * 100.0
+    )?;
+    writeln!(f, "{:<17}{}", "words:", self.word_size)?;
+    writeln!(f, "{:<17}{}", "trie units:", self.trie_size)?;
+    if let Some(auto_cost_words) = self.auto_cost_words {
+      writeln!(f, "{:<17}{}", "automatic costs:", auto_cost_words)?;
+    }
+    writeln!(f, "words per POS:")?;
+    for (pos, count) in &self.words_per_pos {
+      writeln!(
+        f,
+        "\t{}\t{}",
+        pos.as_deref().unwrap_or("(unresolved)"),
+        count
+      )?;
+    }
+    if let Some(trailer) = &self.trailer {
+      let verified = match self.checksum_verified {
+        Some(true) => " (verified)",
+        Some(false) => " (does not match the content)",
+        None => "",
+      };
+      writeln!(f, "{:<17}{}{}", "checksum:", trailer.checksum, verified)?;
+      writeln!(f, "{:<17}{}", "built by:", trailer.builder_version)?;
+      if let Some(system_dictionary) = &trailer.system_dictionary {
+        writeln!(f, "{:<17}{}", "built against:", system_dictionary)?;
+      }
+      writeln!(f, "sources:")?;
+      for source in &trailer.sources {
+        writeln!(f, "\t{}\t{}", source.name, source.checksum)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::dictionary_builder::DictionaryBuilder;
+  use crate::dictionary_lib::dictionary_header::DictionaryHeader;
+  use crate::dictionary_lib::system_dictionary_version::SYSTEM_DICT_VERSION;
+  use std::env::temp_dir;
+  use std::io::{Cursor, Write};
+
+  fn system_dictionary() -> BinaryDictionary {
+    BinaryDictionary::from_system_dictionary_bytes(include_bytes!("../resources/test/system.dic"))
+      .unwrap()
+  }
+
+  #[test]
+  fn test_system_dictionary_stats() {
+    let stats = DictionaryStats::new(&system_dictionary(), None).unwrap();
+    assert_eq!("system", stats.version_name);
+    assert_eq!(10, stats.left_id_size);
+    assert_eq!(10, stats.right_id_size);
+    assert!(stats.connection_density() > 0.0 && stats.connection_density() <= 1.0);
+    assert_eq!(None, stats.auto_cost_words);
+    assert_eq!(
+      stats.word_size,
+      stats
+        .words_per_pos
+        .iter()
+        .map(|(_, count)| count)
+        .sum::<usize>()
+    );
+    assert_eq!(Some(String::from("名詞")), stats.words_per_pos[0].0);
+    assert!(stats.to_string().contains("words per POS:\n\t名詞\t"));
+  }
+
+  #[test]
+  fn test_user_dictionary_stats() {
+    let system = system_dictionary();
+    let user =
+      BinaryDictionary::from_user_dictionary_bytes(include_bytes!("../resources/test/user.dic"))
+        .unwrap();
+    let stats = DictionaryStats::new(&user, Some(&system)).unwrap();
+    assert_eq!("user (v2)", stats.version_name);
+    assert_eq!(0, stats.connection_size());
+    assert_eq!(0.0, stats.connection_density());
+    assert!(stats.auto_cost_words.is_some());
+    assert!(stats.words_per_pos.iter().all(|(pos, _)| pos.is_some()));
+
+    let stats = DictionaryStats::new(&user, None).unwrap();
+    assert_eq!(vec![(None, stats.word_size)], stats.words_per_pos);
+  }
+
+  #[test]
+  fn test_user_dictionary_of_another_system_dictionary() {
+    // a system dictionary with a single word and part of speech
+    let path = temp_dir().join("sudachiclone_dictionary_stats_small.csv");
+    std::fs::write(&path, "た,0,0,0,た,助動詞,*,*,*,*,*,タ,た,*,A,*,*,*\n").unwrap();
+    let mut stream = Cursor::new(vec![]);
+    let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, 0, String::from(""));
+    stream.write_all(&header.to_bytes().unwrap()).unwrap();
+    DictionaryBuilder::default()
+      .build(
+        &[path.to_str().unwrap()],
+        Some(&mut Cursor::new("1 1\n0 0 0\n")),
+        &mut stream,
+      )
+      .unwrap();
+    let small = BinaryDictionary::from_system_dictionary_bytes(stream.get_ref()).unwrap();
+    let user =
+      BinaryDictionary::from_user_dictionary_bytes(include_bytes!("../resources/test/user.dic"))
+        .unwrap();
+    assert!(DictionaryStats::new(&user, Some(&small)).is_err());
+  }
+}
@@ -0,0 +1,367 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Error as IOError, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::binary_dictionary::{BinaryDictionary, ReadDictionaryErr};
+use super::corruption::CorruptionErr;
+use super::dictionary_header::time_string;
+use super::io::LittleEndianWrite;
+use super::lexicon::Size;
+
+/// Ends a dictionary with a trailer, after the length of its JSON content
+const TRAILER_MAGIC: &[u8; 8] = b"SUDCLTR1";
+const FOOTER_SIZE: u64 = 4 + TRAILER_MAGIC.len() as u64;
+
+/// A file a dictionary was built from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceFile {
+  pub name: String,
+  /// SHA-256 of its content, in hex
+  pub checksum: String,
+}
+
+impl SourceFile {
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SourceFile, IOError> {
+    let path = path.as_ref();
+    let name = path.file_name().map_or_else(
+      || path.display().to_string(),
+      |name| name.to_string_lossy().to_string(),
+    );
+    let checksum = checksum_of(&mut BufReader::new(File::open(path)?), None)?;
+    Ok(SourceFile { name, checksum })
+  }
+}
+
+/// What tells the system dictionary a user dictionary was built against from another one
+#[derive(Clone, Debug, PartialEq)]
+pub struct SystemDictionaryIdentity {
+  pub create_time: u64,
+  pub part_of_speech_size: usize,
+  pub word_size: usize,
+  /// `None` if the system dictionary has no trailer
+  pub checksum: Option<String>,
+}
+
+impl SystemDictionaryIdentity {
+  pub fn of(system_dictionary: &BinaryDictionary) -> SystemDictionaryIdentity {
+    SystemDictionaryIdentity {
+      create_time: system_dictionary.header.create_time,
+      part_of_speech_size: system_dictionary.grammar.get_part_of_speech_size(),
+      word_size: system_dictionary.lexicon.size(),
+      checksum: system_dictionary
+        .trailer
+        .as_ref()
+        .map(|trailer| trailer.checksum.clone()),
+    }
+  }
+  /// Whether both are the same system dictionary, comparing checksums only if both have one
+  pub fn matches(&self, other: &SystemDictionaryIdentity) -> bool {
+    self.create_time == other.create_time
+      && self.part_of_speech_size == other.part_of_speech_size
+      && self.word_size == other.word_size
+      && match (&self.checksum, &other.checksum) {
+        (Some(checksum), Some(other_checksum)) => checksum == other_checksum,
+        _ => true,
+      }
+  }
+  fn to_json(&self) -> Value {
+    json!({
+      "create_time": self.create_time,
+      "pos_size": self.part_of_speech_size,
+      "word_size": self.word_size,
+      "checksum": self.checksum,
+    })
+  }
+  fn from_json(value: &Value) -> Result<SystemDictionaryIdentity, CorruptionErr> {
+    Ok(SystemDictionaryIdentity {
+      create_time: get_u64(value, "create_time")?,
+      part_of_speech_size: get_u64(value, "pos_size")? as usize,
+      word_size: get_u64(value, "word_size")? as usize,
+      checksum: match &value["checksum"] {
+        Value::Null => None,
+        _ => Some(get_str(value, "checksum")?),
+      },
+    })
+  }
+}
+
+impl fmt::Display for SystemDictionaryIdentity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "created {} (UTC) with {} POS and {} words",
+      time_string(self.create_time),
+      self.part_of_speech_size,
+      self.word_size
+    )?;
+    if let Some(checksum) = &self.checksum {
+      write!(f, ", checksum {}", checksum)?;
+    }
+    Ok(())
+  }
+}
+
+/// Integrity and provenance of a dictionary, stored after its lexicon
+///
+/// The trailer is optional: dictionaries without one load as before, and readers that do not
+/// know it see its bytes as unused space after the word infos.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DictionaryTrailer {
+  /// SHA-256 of every byte before the trailer, in hex
+  pub checksum: String,
+  pub builder_version: String,
+  pub sources: Vec<SourceFile>,
+  pub part_of_speech_size: usize,
+  pub word_size: usize,
+  /// for a user dictionary, the system dictionary it was built against
+  pub system_dictionary: Option<SystemDictionaryIdentity>,
+}
+
+impl DictionaryTrailer {
+  /// Append a trailer to the dictionary in `stream`, which must not have one yet
+  pub fn append<S: Read + Write + Seek>(
+    stream: &mut S,
+    sources: Vec<SourceFile>,
+    system_dictionary: Option<SystemDictionaryIdentity>,
+  ) -> Result<DictionaryTrailer, ReadDictionaryErr> {
+    stream.seek(SeekFrom::Start(0))?;
+    let dictionary =
+      BinaryDictionary::read_dictionary_from_reader(&mut BufReader::new(&mut *stream))?;
+    stream.seek(SeekFrom::Start(0))?;
+    let trailer = DictionaryTrailer {
+      checksum: checksum_of(stream, None)?,
+      builder_version: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+      sources,
+      part_of_speech_size: dictionary.grammar.get_part_of_speech_size(),
+      word_size: dictionary.lexicon.size(),
+      system_dictionary,
+    };
+    let content = trailer.to_json().to_string().into_bytes();
+    stream.seek(SeekFrom::End(0))?;
+    stream.write_all(&content)?;
+    stream.write_u32(content.len() as u32)?;
+    stream.write_all(TRAILER_MAGIC)?;
+    Ok(trailer)
+  }
+  /// Read the trailer at the end of `reader` with the position it starts at, if there is one,
+  /// leaving the position of `reader` as it was
+  pub fn from_reader<R: Read + Seek>(
+    reader: &mut R,
+  ) -> Result<Option<(DictionaryTrailer, u64)>, CorruptionErr> {
+    let position = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    let trailer = DictionaryTrailer::read_at_end(reader, position, end);
+    reader.seek(SeekFrom::Start(position))?;
+    trailer
+  }
+  fn read_at_end<R: Read + Seek>(
+    reader: &mut R,
+    position: u64,
+    end: u64,
+  ) -> Result<Option<(DictionaryTrailer, u64)>, CorruptionErr> {
+    if end < position + FOOTER_SIZE {
+      return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+    let size = reader.read_u32::<LittleEndian>()? as u64;
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != TRAILER_MAGIC {
+      return Ok(None);
+    }
+    if end - position - FOOTER_SIZE < size {
+      return Err(CorruptionErr::TruncatedErr(
+        "the trailer",
+        size,
+        end - position - FOOTER_SIZE,
+      ));
+    }
+    let start = end - FOOTER_SIZE - size;
+    reader.seek(SeekFrom::Start(start))?;
+    let mut content = vec![0u8; size as usize];
+    reader.read_exact(&mut content)?;
+    let value =
+      serde_json::from_slice(&content).map_err(|e| CorruptionErr::TrailerErr(e.to_string()))?;
+    Ok(Some((DictionaryTrailer::from_json(&value)?, start)))
+  }
+  /// Whether the content of the dictionary in `reader` still has its checksum; `None` if it
+  /// has no trailer
+  pub fn verify_checksum<R: Read + Seek>(reader: &mut R) -> Result<Option<bool>, CorruptionErr> {
+    reader.seek(SeekFrom::Start(0))?;
+    match DictionaryTrailer::from_reader(reader)? {
+      Some((trailer, start)) => Ok(Some(checksum_of(reader, Some(start))? == trailer.checksum)),
+      None => Ok(None),
+    }
+  }
+  /// Fail unless the counts of the trailer are those of `dictionary`
+  pub fn check_counts(&self, dictionary: &BinaryDictionary) -> Result<(), CorruptionErr> {
+    let part_of_speech_size = dictionary.grammar.get_part_of_speech_size();
+    let word_size = dictionary.lexicon.size();
+    if self.part_of_speech_size != part_of_speech_size || self.word_size != word_size {
+      return Err(CorruptionErr::TrailerErr(format!(
+        "it counts {} POS and {} words but the dictionary has {} and {}",
+        self.part_of_speech_size, self.word_size, part_of_speech_size, word_size
+      )));
+    }
+    Ok(())
+  }
+  pub fn to_json(&self) -> Value {
+    json!({
+      "checksum": self.checksum,
+      "builder": self.builder_version,
+      "sources": self
+        .sources
+        .iter()
+        .map(|source| json!({"name": source.name, "checksum": source.checksum}))
+        .collect::<Vec<Value>>(),
+      "pos_size": self.part_of_speech_size,
+      "word_size": self.word_size,
+      "system_dictionary": self.system_dictionary.as_ref().map(|system| system.to_json()),
+    })
+  }
+  fn from_json(value: &Value) -> Result<DictionaryTrailer, CorruptionErr> {
+    let sources = match &value["sources"] {
+      Value::Array(sources) => sources
+        .iter()
+        .map(|source| {
+          Ok(SourceFile {
+            name: get_str(source, "name")?,
+            checksum: get_str(source, "checksum")?,
+          })
+        })
+        .collect::<Result<Vec<SourceFile>, CorruptionErr>>()?,
+      _ => return Err(CorruptionErr::TrailerErr(String::from("no sources"))),
+    };
+    Ok(DictionaryTrailer {
+      checksum: get_str(value, "checksum")?,
+      builder_version: get_str(value, "builder")?,
+      sources,
+      part_of_speech_size: get_u64(value, "pos_size")? as usize,
+      word_size: get_u64(value, "word_size")? as usize,
+      system_dictionary: match &value["system_dictionary"] {
+        Value::Null => None,
+        system => Some(SystemDictionaryIdentity::from_json(system)?),
+      },
+    })
+  }
+}
+
+fn get_str(value: &Value, key: &str) -> Result<String, CorruptionErr> {
+  value[key]
+    .as_str()
+    .map(|s| s.to_string())
+    .ok_or_else(|| CorruptionErr::TrailerErr(format!("no {}", key)))
+}
+
+fn get_u64(value: &Value, key: &str) -> Result<u64, CorruptionErr> {
+  value[key]
+    .as_u64()
+    .ok_or_else(|| CorruptionErr::TrailerErr(format!("no {}", key)))
+}
+
+/// SHA-256 in hex of what is left in `reader`, or of its next `size` bytes
+fn checksum_of<R: Read>(reader: &mut R, size: Option<u64>) -> Result<String, IOError> {
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0u8; 1 << 16];
+  let mut left = size;
+  loop {
+    let want = left.map_or(buf.len(), |left| (left as usize).min(buf.len()));
+    if want == 0 {
+      break;
+    }
+    let len = reader.read(&mut buf[..want])?;
+    if len == 0 {
+      break;
+    }
+    hasher.update(&buf[..len]);
+    left = left.map(|left| left - len as u64);
+  }
+  Ok(
+    hasher
+      .finalize()
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::lexicon::Lexicon;
+  use std::io::Cursor;
+
+  const SYSTEM_DIC: &[u8] = include_bytes!("../resources/test/system.dic");
+
+  fn append_trailer() -> (Vec<u8>, DictionaryTrailer) {
+    let mut stream = Cursor::new(SYSTEM_DIC.to_vec());
+    let sources = vec![SourceFile {
+      name: String::from("lex.csv"),
+      checksum: String::from("0123"),
+    }];
+    let trailer = DictionaryTrailer::append(&mut stream, sources, None).unwrap();
+    (stream.into_inner(), trailer)
+  }
+
+  #[test]
+  fn test_append() {
+    let (bytes, trailer) = append_trailer();
+    assert_eq!(&bytes[..SYSTEM_DIC.len()], SYSTEM_DIC);
+    assert_eq!(8, trailer.part_of_speech_size);
+    assert_eq!(38, trailer.word_size);
+    assert_eq!("lex.csv", trailer.sources[0].name);
+
+    let dictionary = BinaryDictionary::from_system_dictionary_bytes(&bytes).unwrap();
+    assert_eq!(Some(&trailer), dictionary.trailer.as_ref());
+    assert_eq!(38, dictionary.lexicon.size());
+    assert_eq!("特a", dictionary.lexicon.get_word_info(37).surface);
+    assert!(BinaryDictionary::from_system_dictionary_bytes(SYSTEM_DIC)
+      .unwrap()
+      .trailer
+      .is_none());
+  }
+
+  #[test]
+  fn test_verify_checksum() {
+    let (mut bytes, _) = append_trailer();
+    assert_eq!(
+      None,
+      DictionaryTrailer::verify_checksum(&mut Cursor::new(SYSTEM_DIC)).unwrap()
+    );
+    assert_eq!(
+      Some(true),
+      DictionaryTrailer::verify_checksum(&mut Cursor::new(&bytes)).unwrap()
+    );
+    bytes[SYSTEM_DIC.len() - 1] ^= 0xFF;
+    assert_eq!(
+      Some(false),
+      DictionaryTrailer::verify_checksum(&mut Cursor::new(&bytes)).unwrap()
+    );
+  }
+
+  #[test]
+  fn test_invalid_trailer() {
+    let (bytes, trailer) = append_trailer();
+    let mut wrong_counts = Cursor::new(SYSTEM_DIC.to_vec());
+    wrong_counts.set_position(SYSTEM_DIC.len() as u64);
+    let content = trailer
+      .to_json()
+      .to_string()
+      .replace("\"word_size\":38", "\"word_size\":39");
+    wrong_counts.write_all(content.as_bytes()).unwrap();
+    wrong_counts.write_u32(content.len() as u32).unwrap();
+    wrong_counts.write_all(TRAILER_MAGIC).unwrap();
+    assert!(BinaryDictionary::from_system_dictionary_bytes(wrong_counts.get_ref()).is_err());
+
+    // a trailer whose length runs over the dictionary
+    let mut too_long = bytes.clone();
+    let footer = too_long.len() - FOOTER_SIZE as usize;
+    too_long[footer + 3] = 0x7F;
+    assert!(BinaryDictionary::from_system_dictionary_bytes(&too_long).is_err());
+  }
+}
@@ -1,11 +1,16 @@
 use std::cmp::{max, min};
+use std::collections::HashMap;
 use std::io::{BufRead, Seek};
+use std::sync::OnceLock;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use rand::Rng;
 
+use super::corruption::{check_remaining, CorruptionErr};
+use super::grammar::Grammar;
 use super::lexicon::{GetWordId, Lexicon, LexiconErr, Size};
 use super::word_id_table::WordIdTable;
+use super::word_index::WordIndex;
 use super::word_info::WordInfo;
 use super::word_info_list::WordInfoList;
 use super::word_parameter_list::WordParameterList;
@@ -15,6 +20,29 @@ use crate::tokenizer::CanTokenize;
 pub const SIGNED_SHORT_MIN: i16 = std::i16::MIN;
 pub const SIGNED_SHORT_MAX: i16 = std::i16::MAX;
 pub const USER_DICT_COST_PER_MORPH: i16 = -20;
+const USER_WORD_ID_FLAG: i32 = 1 << 28;
+
+/// The ranges the ids of the words of a lexicon must be in
+pub struct LexiconLimits {
+  /// the POS of the system dictionary and of the lexicon's own
+  pub pos_size: usize,
+  pub left_id_size: usize,
+  pub right_id_size: usize,
+  /// the words of the system dictionary, for a user dictionary
+  pub system_word_size: Option<usize>,
+}
+
+impl LexiconLimits {
+  pub fn new(grammar: &Grammar, pos_size: usize, system_word_size: Option<usize>) -> LexiconLimits {
+    // the connection matrix is indexed by the right id of the word on the left first
+    LexiconLimits {
+      pos_size,
+      left_id_size: grammar.get_right_id_size(),
+      right_id_size: grammar.get_left_id_size(),
+      system_word_size,
+    }
+  }
+}
 
 pub struct DoubleArrayLexicon {
   id: u64,
@@ -22,6 +50,7 @@ pub struct DoubleArrayLexicon {
   word_id_table: WordIdTable,
   word_params: WordParameterList,
   word_infos: WordInfoList,
+  word_index: OnceLock<WordIndex>,
 }
 
 impl<L: AsRef<DoubleArrayLexicon>> Lexicon for L {
@@ -41,6 +70,32 @@ impl<L: AsRef<DoubleArrayLexicon>> Lexicon for L {
       .flatten()
       .collect()
   }
+  fn exact_lookup(&self, headword: &[u8]) -> Vec<usize> {
+    match self.as_ref().trie.exact_match_search(headword) {
+      Some(index) => self.as_ref().word_id_table.get(index as usize),
+      None => vec![],
+    }
+  }
+  fn predictive_lookup(&self, prefix: &[u8], limit: Option<usize>) -> Vec<(String, usize)> {
+    // every headword has a word, so `limit` headwords have enough words
+    self
+      .as_ref()
+      .trie
+      .predictive_search(prefix, limit)
+      .into_iter()
+      .flat_map(|(key, index)| {
+        let headword = String::from_utf8_lossy(&key).to_string();
+        self
+          .as_ref()
+          .word_id_table
+          .get(index as usize)
+          .into_iter()
+          .map(|word_id| (headword.clone(), word_id))
+          .collect::<Vec<(String, usize)>>()
+      })
+      .take(limit.unwrap_or(usize::MAX))
+      .collect()
+  }
   fn get_left_id(&self, word_id: usize) -> i16 {
     self.as_ref().word_params.get_left_id(word_id)
   }
@@ -71,22 +126,26 @@ impl GetWordId for DoubleArrayLexicon {
     pos_id: u16,
     reading_form: &str,
   ) -> Result<usize, LexiconErr> {
-    for word_id in 0..self.word_infos.size() {
-      let info = self.word_infos.get_word_info(word_id);
-      if info.surface == headword
-        && info.pos_id == pos_id as i16
-        && info.reading_form == reading_form
-      {
-        return Ok(word_id);
-      }
-    }
-    Err(LexiconErr::NotFoundWordIdErr)
+    self
+      .word_index()
+      .get_word_id(headword, pos_id as i16, reading_form)
+      .ok_or(LexiconErr::NotFoundWordIdErr)
   }
 }
 
 impl DoubleArrayLexicon {
-  pub fn from_reader<R: BufRead + Seek>(reader: &mut R) -> Result<DoubleArrayLexicon, LexiconErr> {
+  pub fn from_reader<R: BufRead + Seek>(
+    reader: &mut R,
+  ) -> Result<DoubleArrayLexicon, CorruptionErr> {
+    DoubleArrayLexicon::from_reader_until(reader, None)
+  }
+  /// Read a lexicon ending at the position `end`, e.g. before a trailer
+  pub fn from_reader_until<R: BufRead + Seek>(
+    reader: &mut R,
+    end: Option<u64>,
+  ) -> Result<DoubleArrayLexicon, CorruptionErr> {
     let size = reader.read_u32::<LittleEndian>()? as usize;
+    check_remaining(reader, "the trie", 4 * size as u64)?;
 
     let mut trie = DoubleArrayTrie::default();
     let mut buf = vec![0u8; size * 4];
@@ -97,7 +156,7 @@ impl DoubleArrayLexicon {
 
     let word_params = WordParameterList::from_reader(reader)?;
 
-    let word_infos = WordInfoList::from_reader(reader, word_params.get_size())?;
+    let word_infos = WordInfoList::from_reader(reader, word_params.get_size(), end)?;
 
     Ok(DoubleArrayLexicon {
       id: rand::thread_rng().gen(),
@@ -105,25 +164,189 @@ impl DoubleArrayLexicon {
       word_id_table,
       word_params,
       word_infos,
+      word_index: OnceLock::new(),
     })
   }
+  pub fn get_trie_size(&self) -> usize {
+    self.trie.size()
+  }
+  /// Check every reference between the parts of the lexicon, and the ids of its words against
+  /// `limits` if given
+  ///
+  /// Without `limits` the lexicon is taken as a user dictionary whose system dictionary is
+  /// unknown. Lookups and word infos of a valid lexicon don't panic.
+  pub fn validate(&self, limits: Option<&LexiconLimits>) -> Result<(), CorruptionErr> {
+    let word_size = self.size();
+    let starts = self.word_id_table.validate(word_size)?;
+    let is_start = |index: i32| starts.get(index as usize) == Some(&true);
+    if let Some((node_pos, leaf_pos)) = self.trie.find_invalid_leaf(is_start) {
+      let array = self.trie.get_array();
+      return Err(match array.get(leaf_pos) {
+        Some(leaf) => CorruptionErr::TrieValueErr(node_pos, (leaf & !(1 << 31)) as usize),
+        None => CorruptionErr::TrieUnitErr(node_pos, leaf_pos, array.len()),
+      });
+    }
+
+    let is_user = match limits {
+      Some(limits) => limits.system_word_size.is_some(),
+      None => true,
+    };
+    let check_split = |split: &[i32]| {
+      for word_id in split {
+        let in_range = match (is_user, limits) {
+          (true, _) if word_id & !0x0FFF_FFFF == USER_WORD_ID_FLAG => {
+            ((word_id & 0x0FFF_FFFF) as usize) < word_size
+          }
+          (true, Some(limits)) => {
+            *word_id >= 0 && (*word_id as usize) < limits.system_word_size.unwrap_or(0)
+          }
+          // the system dictionary is unknown
+          (true, None) => *word_id >= 0 && word_id & !0x0FFF_FFFF == 0,
+          (false, _) => *word_id >= 0 && (*word_id as usize) < word_size,
+        };
+        if !in_range {
+          return Err(format!("its split refers to an unknown word {}", word_id));
+        }
+      }
+      Ok(())
+    };
+    for word_id in 0..word_size {
+      let corrupted = |message: String| CorruptionErr::WordInfoErr(word_id, message);
+      let references = self
+        .word_infos
+        .get_word_references(word_id)
+        .map_err(corrupted)?;
+      let dictionary_form_word_id = references.dictionary_form_word_id;
+      if dictionary_form_word_id < -1 || dictionary_form_word_id >= word_size as i32 {
+        return Err(corrupted(format!(
+          "its dictionary form is an unknown word {}",
+          dictionary_form_word_id
+        )));
+      }
+      check_split(&references.a_unit_split).map_err(corrupted)?;
+      check_split(&references.b_unit_split).map_err(corrupted)?;
+      check_split(&references.word_structure).map_err(corrupted)?;
+      if let Some(limits) = limits {
+        let pos_id = references.pos_id;
+        if pos_id < 0 || pos_id as usize >= limits.pos_size {
+          return Err(corrupted(format!("its POS id {} is unknown", pos_id)));
+        }
+        // -1 keeps a word only used in split info out of the lattice
+        let left_id = self.word_params.get_left_id(word_id);
+        let right_id = self.word_params.get_right_id(word_id);
+        if left_id < -1 || left_id >= limits.left_id_size as i16 {
+          return Err(corrupted(format!(
+            "its left id {} is out of range",
+            left_id
+          )));
+        }
+        if right_id < -1 || right_id >= limits.right_id_size as i16 {
+          return Err(corrupted(format!(
+            "its right id {} is out of range",
+            right_id
+          )));
+        }
+      }
+    }
+    Ok(())
+  }
+  /// Every headword in the trie with its word ids, in byte order
+  pub fn get_headwords(&self) -> Vec<(String, Vec<usize>)> {
+    self
+      .trie
+      .entries()
+      .into_iter()
+      .map(|(key, index)| {
+        (
+          String::from_utf8_lossy(&key).to_string(),
+          self.word_id_table.get(index as usize),
+        )
+      })
+      .collect()
+  }
+  pub fn get_pos_id(&self, word_id: usize) -> i16 {
+    self.word_infos.get_pos_id(word_id)
+  }
+  /// Reverse lookup indexes, built on first use
+  fn word_index(&self) -> &WordIndex {
+    self.word_index.get_or_init(|| {
+      WordIndex::new(
+        (0..self.word_infos.size()).map(|word_id| self.word_infos.get_word_info(word_id)),
+      )
+    })
+  }
+  pub fn get_word_ids_by_reading_form(&self, reading_form: &str) -> Vec<usize> {
+    self
+      .word_index()
+      .get_word_ids_by_reading_form(reading_form)
+      .to_vec()
+  }
+  pub fn get_word_ids_by_normalized_form(&self, normalized_form: &str) -> Vec<usize> {
+    self
+      .word_index()
+      .get_word_ids_by_normalized_form(normalized_form)
+      .to_vec()
+  }
+  /// The word itself and every entry whose dictionary form it is
+  pub fn get_word_ids_by_dictionary_form(&self, word_id: usize) -> Vec<usize> {
+    self
+      .word_index()
+      .get_word_ids_by_dictionary_form(word_id)
+      .to_vec()
+  }
+  /// The (left id, right id) most words of each POS have, the lowest on a tie
+  pub fn get_most_frequent_connection_ids(&self) -> HashMap<u16, (i16, i16)> {
+    self
+      .get_connection_ids_by_frequency()
+      .into_iter()
+      .map(|(pos_id, connection_ids)| (pos_id, connection_ids[0]))
+      .collect()
+  }
+  /// Every (left id, right id) the words of each POS have, the most frequent first
+  pub fn get_connection_ids_by_frequency(&self) -> HashMap<u16, Vec<(i16, i16)>> {
+    let mut counts: HashMap<u16, HashMap<(i16, i16), usize>> = HashMap::new();
+    for word_id in 0..self.size() {
+      let connection_ids = (self.get_left_id(word_id), self.get_right_id(word_id));
+      *counts
+        .entry(self.get_pos_id(word_id) as u16)
+        .or_default()
+        .entry(connection_ids)
+        .or_insert(0) += 1;
+    }
+    counts
+      .into_iter()
+      .map(|(pos_id, counts)| {
+        let mut counts: Vec<((i16, i16), usize)> = counts.into_iter().collect();
+        counts.sort_by(|(ids1, count1), (ids2, count2)| count2.cmp(count1).then(ids1.cmp(ids2)));
+        (pos_id, counts.into_iter().map(|(ids, _)| ids).collect())
+      })
+      .collect()
+  }
+  pub fn set_cost(&mut self, word_id: usize, cost: i16) {
+    self.word_params.set_cost(word_id, cost);
+  }
   pub fn calculate_cost<T: CanTokenize>(&mut self, tokenizer: T) {
     for word_id in 0..self.word_params.get_size() {
       if self.get_cost(word_id) != SIGNED_SHORT_MIN {
         continue;
       }
       let surface = self.get_word_info(word_id).surface;
-      let ms = tokenizer.tokenize(&surface, None, None);
-      if let Some(ms) = ms {
-        let mut cost = ms.get_internal_cost() + USER_DICT_COST_PER_MORPH * ms.len() as i16;
-        cost = min(cost, SIGNED_SHORT_MAX);
-        cost = max(cost, SIGNED_SHORT_MIN);
+      if let Some(cost) = estimate_cost(&tokenizer, &surface) {
         self.word_params.set_cost(word_id, cost);
       }
     }
   }
 }
 
+/// The cost of a user word from how `tokenizer` splits its surface without it
+pub fn estimate_cost<T: CanTokenize>(tokenizer: &T, surface: &str) -> Option<i16> {
+  let ms = tokenizer.tokenize(surface, None, None)?;
+  let mut cost = ms.get_internal_cost() + USER_DICT_COST_PER_MORPH * ms.len() as i16;
+  cost = min(cost, SIGNED_SHORT_MAX);
+  cost = max(cost, SIGNED_SHORT_MIN);
+  Some(cost)
+}
+
 impl PartialEq for DoubleArrayLexicon {
   fn eq(&self, other: &Self) -> bool {
     self.id == other.id
@@ -183,6 +406,58 @@ mod tests {
     assert_eq!(0, results3.len());
   }
 
+  #[test]
+  fn test_exact_lookup() {
+    let lexicon = read_lexicon();
+    assert_eq!(vec![1, 2], lexicon.exact_lookup("に".as_bytes()));
+    assert_eq!(vec![6], lexicon.exact_lookup("東京都".as_bytes()));
+    assert!(lexicon.exact_lookup("東京都に".as_bytes()).is_empty());
+  }
+
+  #[test]
+  fn test_predictive_lookup() {
+    let lexicon = read_lexicon();
+    assert_eq!(
+      vec![(String::from("東京"), 5), (String::from("東京都"), 6)],
+      lexicon.predictive_lookup("東京".as_bytes(), None)
+    );
+    assert_eq!(
+      vec![(String::from("東京"), 5)],
+      lexicon.predictive_lookup("東京".as_bytes(), Some(1))
+    );
+    assert!(lexicon
+      .predictive_lookup("あれ".as_bytes(), None)
+      .is_empty());
+  }
+
+  #[test]
+  fn test_get_word_id() {
+    let lexicon = read_lexicon();
+    let pos_id = lexicon.get_word_info(2).pos_id as u16;
+    assert_eq!(2, lexicon.get_word_id("に", pos_id, "ニ").unwrap());
+    let pos_id = lexicon.get_word_info(34).pos_id as u16;
+    assert_eq!(7, lexicon.get_word_id("行く", pos_id, "イク").unwrap());
+    assert_eq!(34, lexicon.get_word_id("いく", pos_id, "イク").unwrap());
+    assert!(lexicon.get_word_id("行く", pos_id, "ユク").is_err());
+  }
+
+  #[test]
+  fn test_reverse_lookup() {
+    let lexicon = read_lexicon();
+    assert_eq!(
+      vec![1, 2, 15, 25],
+      lexicon.get_word_ids_by_reading_form("ニ")
+    );
+    assert_eq!(
+      vec![7, 8, 34, 35],
+      lexicon.get_word_ids_by_normalized_form("行く")
+    );
+    assert_eq!(vec![7, 8], lexicon.get_word_ids_by_dictionary_form(7));
+    assert_eq!(vec![34, 35], lexicon.get_word_ids_by_dictionary_form(34));
+    assert!(lexicon.get_word_ids_by_dictionary_form(8).is_empty());
+    assert!(lexicon.get_word_ids_by_reading_form("コウエン").is_empty());
+  }
+
   #[test]
   fn test_parameters() {
     // た
@@ -245,6 +520,24 @@ mod tests {
     assert_eq!(570, word_info.reading_form.chars().count());
   }
 
+  #[test]
+  fn test_get_most_frequent_connection_ids() {
+    let lexicon = read_lexicon();
+    let connection_ids = lexicon.get_most_frequent_connection_ids();
+    // 京都, 東京, 東京都, アイアイウ and 六三四, only 東京都 has another right id
+    let pos_id = lexicon.get_word_info(3).pos_id as u16;
+    assert_eq!(Some(&(6, 6)), connection_ids.get(&pos_id));
+    // 東, アイ and アイウ against 都 and 特a
+    let pos_id = lexicon.get_word_info(4).pos_id as u16;
+    assert_eq!(Some(&(7, 7)), connection_ids.get(&pos_id));
+    assert_eq!(
+      Some(&vec![(6, 6), (6, 8)]),
+      lexicon
+        .get_connection_ids_by_frequency()
+        .get(&(lexicon.get_word_info(3).pos_id as u16))
+    );
+  }
+
   #[test]
   fn test_size() {
     let lexicon = read_lexicon();
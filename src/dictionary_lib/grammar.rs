@@ -1,27 +1,33 @@
-use std::io::{BufRead, Error as IOError, Seek, SeekFrom};
+use std::io::{BufRead, Seek, SeekFrom};
+use std::sync::Arc;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use encoding_rs::UTF_16LE;
 
 use super::character_category::CharacterCategory;
+use super::corruption::{check_remaining, CorruptionErr};
 
 pub const INHIBITED_CONNECTION: i16 = 0x7fff;
 
 pub const POS_DEPTH: usize = 6;
 
+#[derive(Clone)]
 pub struct Grammar {
   bos_parameter: [u32; 3],
   eos_parameter: [u32; 3],
   character_category: Option<CharacterCategory>,
   pos_list: Vec<Vec<String>>,
   storage_size: usize,
-  matrix_view: Vec<Vec<i16>>,
+  left_id_size: usize,
+  right_id_size: usize,
+  /// shared by clones, which only differ in their user defined POS
+  matrix_view: Arc<Vec<Vec<i16>>>,
 }
 
 impl Grammar {
-  pub fn from_reader<R: Seek + BufRead>(reader: &mut R) -> Result<Grammar, IOError> {
+  pub fn from_reader<R: Seek + BufRead>(reader: &mut R) -> Result<Grammar, CorruptionErr> {
     let offset = reader.seek(SeekFrom::Current(0))? as usize;
-    let pos_size = reader.read_i16::<LittleEndian>()? as usize;
+    let pos_size = read_size(reader, "the POS table")?;
     let mut pos_list = vec![Vec::with_capacity(6); pos_size];
     for pos in pos_list.iter_mut() {
       for _ in 0..POS_DEPTH {
@@ -32,8 +38,13 @@ impl Grammar {
         pos.push(p.to_string());
       }
     }
-    let left_id_size = reader.read_i16::<LittleEndian>()? as usize;
-    let right_id_size = reader.read_i16::<LittleEndian>()? as usize;
+    let left_id_size = read_size(reader, "the connection matrix")?;
+    let right_id_size = read_size(reader, "the connection matrix")?;
+    check_remaining(
+      reader,
+      "the connection matrix",
+      2 * (left_id_size * right_id_size) as u64,
+    )?;
     let connect_table_offset = reader.seek(SeekFrom::Current(0))? as usize;
 
     let storage_size = (connect_table_offset - offset) + 2 * left_id_size * right_id_size;
@@ -43,7 +54,10 @@ impl Grammar {
     } else {
       let mut buf = vec![0i16; left_id_size * right_id_size];
       reader.read_i16_into::<LittleEndian>(&mut buf)?;
-      buf.chunks(right_id_size).map(|chunk| chunk.to_vec()).collect()
+      buf
+        .chunks(left_id_size)
+        .map(|chunk| chunk.to_vec())
+        .collect()
     };
 
     Ok(Grammar {
@@ -52,21 +66,47 @@ impl Grammar {
       character_category: None,
       pos_list,
       storage_size,
-      matrix_view,
+      left_id_size,
+      right_id_size,
+      matrix_view: Arc::new(matrix_view),
     })
   }
+  /// The grammar of a version 1 user dictionary, which has no POS of its own and connects
+  /// with the matrix of the system dictionary
+  pub fn empty() -> Grammar {
+    Grammar {
+      bos_parameter: [0, 0, 0],
+      eos_parameter: [0, 0, 0],
+      character_category: None,
+      pos_list: vec![],
+      storage_size: 0,
+      left_id_size: 0,
+      right_id_size: 0,
+      matrix_view: Arc::new(vec![]),
+    }
+  }
   pub fn get_storage_size(&self) -> usize {
     self.storage_size
   }
   pub fn get_part_of_speech_size(&self) -> usize {
     self.pos_list.len()
   }
+  pub fn get_left_id_size(&self) -> usize {
+    self.left_id_size
+  }
+  pub fn get_right_id_size(&self) -> usize {
+    self.right_id_size
+  }
   pub fn get_part_of_speech_string(&self, pos_id: usize) -> &Vec<String> {
     &self.pos_list[pos_id]
   }
   pub fn get_connect_cost(&self, left: usize, right: usize) -> i16 {
     self.matrix_view[right][left]
   }
+  /// Change a connection cost, copying the matrix shared with clones first
+  pub fn set_connect_cost(&mut self, left: usize, right: usize, cost: i16) {
+    Arc::make_mut(&mut self.matrix_view)[right][left] = cost;
+  }
   pub fn get_bos_parameter(&self) -> [u32; 3] {
     self.bos_parameter
   }
@@ -76,6 +116,18 @@ impl Grammar {
   pub fn add_pos_list(&mut self, grammar: &Grammar) {
     self.pos_list.extend_from_slice(&grammar.pos_list);
   }
+  /// Forget the POS added after the first `size` ones, e.g. the ones of user dictionaries
+  pub fn truncate_pos_list(&mut self, size: usize) {
+    self.pos_list.truncate(size);
+  }
+}
+
+fn read_size<R: BufRead>(reader: &mut R, name: &'static str) -> Result<usize, CorruptionErr> {
+  let size = reader.read_i16::<LittleEndian>()?;
+  if size < 0 {
+    return Err(CorruptionErr::NegativeSizeErr(name, size.into()));
+  }
+  Ok(size as usize)
 }
 
 pub trait GetPartOfSpeech {
@@ -231,6 +283,32 @@ mod tests {
     assert_eq!(200, grammar.get_connect_cost(1, 2));
   }
   #[test]
+  fn test_get_connect_cost_non_square() {
+    let mut bytes = vec![];
+    build_partofspeech(&mut bytes);
+    let mut buf = vec![0; 4];
+    // 2 leftIds and 3 rightIds
+    LittleEndian::write_i16_into(&[2, 3], &mut buf);
+    bytes.extend(buf);
+    // leftId varies fastest
+    let costs: Vec<i16> = (0..3)
+      .flat_map(|right| (0..2).map(move |left| 10 * left + right))
+      .collect();
+    let mut buf = vec![0; costs.len() * 2];
+    LittleEndian::write_i16_into(&costs, &mut buf);
+    bytes.extend(buf);
+
+    let grammar = Grammar::from_reader(&mut Cursor::new(bytes)).unwrap();
+    for left in 0..2 {
+      for right in 0..3 {
+        assert_eq!(
+          10 * left as i16 + right as i16,
+          grammar.get_connect_cost(left, right)
+        );
+      }
+    }
+  }
+  #[test]
   fn test_get_bos_parameters() {
     let grammar = build_grammar();
     assert_eq!(0, grammar.get_bos_parameter()[0]);
@@ -6,6 +6,11 @@ use super::word_info::WordInfo;
 
 pub trait Lexicon {
   fn lookup(&self, text: &[u8], offset: usize) -> Vec<(usize, usize)>;
+  /// Word ids of the words whose headword is exactly `headword`
+  fn exact_lookup(&self, headword: &[u8]) -> Vec<usize>;
+  /// Every headword starting with `prefix` with each of its word ids, in byte order, up to
+  /// `limit` words
+  fn predictive_lookup(&self, prefix: &[u8], limit: Option<usize>) -> Vec<(String, usize)>;
   fn get_left_id(&self, word_id: usize) -> i16;
   fn get_right_id(&self, word_id: usize) -> i16;
   fn get_cost(&self, word_id: usize) -> i16;
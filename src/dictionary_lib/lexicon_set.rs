@@ -1,48 +1,179 @@
+use std::sync::Arc;
+
 use super::double_array_lexicon::DoubleArrayLexicon;
 use super::lexicon::Lexicon;
+use super::memory_lexicon::MemoryLexicon;
 use super::word_info::WordInfo;
 
 const MAX_DICTIONARIES: usize = 16;
+/// The dictionary id of the words added at runtime, the same whatever user dictionaries are read
+const MEMORY_DICTIONARY_ID: usize = MAX_DICTIONARIES - 1;
+
+pub enum AnyLexicon {
+  DoubleArrayLexicon(Arc<DoubleArrayLexicon>),
+  MemoryLexicon(MemoryLexicon),
+}
+
+impl AnyLexicon {
+  fn as_lexicon(&self) -> &dyn Lexicon {
+    match self {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => lexicon,
+      AnyLexicon::MemoryLexicon(lexicon) => lexicon,
+    }
+  }
+  pub fn get_word_ids_by_reading_form(&self, reading_form: &str) -> Vec<usize> {
+    match self {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => lexicon.get_word_ids_by_reading_form(reading_form),
+      AnyLexicon::MemoryLexicon(lexicon) => lexicon.get_word_ids_by_reading_form(reading_form),
+    }
+  }
+  pub fn get_word_ids_by_normalized_form(&self, normalized_form: &str) -> Vec<usize> {
+    match self {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => {
+        lexicon.get_word_ids_by_normalized_form(normalized_form)
+      }
+      AnyLexicon::MemoryLexicon(lexicon) => {
+        lexicon.get_word_ids_by_normalized_form(normalized_form)
+      }
+    }
+  }
+  pub fn get_word_ids_by_dictionary_form(&self, word_id: usize) -> Vec<usize> {
+    match self {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => lexicon.get_word_ids_by_dictionary_form(word_id),
+      AnyLexicon::MemoryLexicon(lexicon) => lexicon.get_word_ids_by_dictionary_form(word_id),
+    }
+  }
+}
 
 pub struct LexiconSet {
-  lexicons: Vec<DoubleArrayLexicon>,
+  lexicons: Vec<AnyLexicon>,
   pos_offsets: Vec<usize>,
+  memory_lexicon: Option<AnyLexicon>,
 }
 
 impl LexiconSet {
   pub fn new(system_lexicon: DoubleArrayLexicon) -> LexiconSet {
     LexiconSet {
-      lexicons: vec![system_lexicon],
+      lexicons: vec![AnyLexicon::DoubleArrayLexicon(Arc::new(system_lexicon))],
       pos_offsets: vec![0],
+      memory_lexicon: None,
+    }
+  }
+  /// A set of the system lexicon of this one only, shared rather than copied
+  pub fn system_only(&self) -> LexiconSet {
+    let system_lexicon = match &self.lexicons[0] {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => Arc::clone(lexicon),
+      AnyLexicon::MemoryLexicon(_) => unreachable!("the system lexicon is a double array"),
+    };
+    LexiconSet {
+      lexicons: vec![AnyLexicon::DoubleArrayLexicon(system_lexicon)],
+      pos_offsets: vec![0],
+      memory_lexicon: None,
     }
   }
   pub fn is_full(&self) -> bool {
-    self.lexicons.len() >= MAX_DICTIONARIES
+    self.lexicons.len() + self.memory_lexicon.iter().count() >= MAX_DICTIONARIES
+  }
+  /// The number of POS of the system dictionary, known once another lexicon is added
+  pub fn get_system_pos_size(&self) -> Option<usize> {
+    self.pos_offsets.get(1).copied()
   }
   fn build_word_id(&self, dict_id: usize, word_id: usize) -> usize {
     if word_id > 0x0FFF_FFFF {
       panic!("word id is too large: {}", word_id)
     }
-    if dict_id > self.lexicons.len() {
+    if self.lexicon(dict_id).is_none() {
       panic!("dict id is too large: {}", word_id)
     }
     (dict_id << 28) | word_id
   }
   pub fn first(&self) -> &DoubleArrayLexicon {
-    &self.lexicons[0]
+    match &self.lexicons[0] {
+      AnyLexicon::DoubleArrayLexicon(lexicon) => lexicon,
+      AnyLexicon::MemoryLexicon(_) => unreachable!("the system lexicon is a double array"),
+    }
   }
   pub fn add(&mut self, lexicon: DoubleArrayLexicon, pos_offset: usize) {
-    if !self.lexicons.contains(&lexicon) {
-      self.lexicons.push(lexicon);
+    let added = self.lexicons.iter().any(|added| match added {
+      AnyLexicon::DoubleArrayLexicon(added) => **added == lexicon,
+      AnyLexicon::MemoryLexicon(_) => false,
+    });
+    if !added {
+      self
+        .lexicons
+        .push(AnyLexicon::DoubleArrayLexicon(Arc::new(lexicon)));
       self.pos_offsets.push(pos_offset);
     }
   }
-  fn _lookup(&self, text: &[u8], offset: usize) -> Vec<(usize, usize)> {
-    let mut res = vec![];
+  pub fn has_memory_lexicon(&self) -> bool {
+    self.memory_lexicon.is_some()
+  }
+  /// Add the lexicon of the words added at runtime if there is none yet
+  ///
+  /// Its words keep their word ids whatever user dictionaries are added later or to a copy.
+  pub fn add_memory_lexicon(&mut self, lexicon: MemoryLexicon) {
+    if !self.has_memory_lexicon() {
+      self.memory_lexicon = Some(AnyLexicon::MemoryLexicon(lexicon));
+    }
+  }
+  pub fn get_memory_lexicon(&self) -> Option<&MemoryLexicon> {
+    match &self.memory_lexicon {
+      Some(AnyLexicon::MemoryLexicon(lexicon)) => Some(lexicon),
+      _ => None,
+    }
+  }
+  fn lexicon(&self, dict_id: usize) -> Option<&AnyLexicon> {
+    match dict_id {
+      MEMORY_DICTIONARY_ID if self.has_memory_lexicon() => self.memory_lexicon.as_ref(),
+      _ => self.lexicons.get(dict_id),
+    }
+  }
+  /// Add a word to the lexicon added by `add_memory_lexicon` and return its word id
+  ///
+  /// The POS id of `word_info` is the one of the grammar, not of a user dictionary.
+  pub fn add_word(
+    &mut self,
+    headword: &str,
+    left_id: i16,
+    right_id: i16,
+    cost: i16,
+    word_info: WordInfo,
+  ) -> Option<usize> {
+    let word_id = match self.memory_lexicon.as_mut()? {
+      AnyLexicon::MemoryLexicon(lexicon) => {
+        lexicon.add_word(headword, left_id, right_id, cost, word_info)
+      }
+      AnyLexicon::DoubleArrayLexicon(_) => unreachable!(),
+    };
+    Some(self.build_word_id(MEMORY_DICTIONARY_ID, word_id))
+  }
+  /// Remove a word added by `add_word`, returning whether it was there
+  pub fn remove_word(&mut self, word_id: usize) -> bool {
+    if self.get_dictionary_id(word_id) != MEMORY_DICTIONARY_ID {
+      return false;
+    }
+    match &mut self.memory_lexicon {
+      Some(AnyLexicon::MemoryLexicon(lexicon)) => lexicon.remove_word(get_word_id(word_id)),
+      _ => false,
+    }
+  }
+  /// User dictionaries first, then the words added at runtime, then the system dictionary
+  fn dictionary_ids(&self) -> Vec<usize> {
     let mut indices: Vec<usize> = (1..self.lexicons.len()).collect();
+    if self.has_memory_lexicon() {
+      indices.push(MEMORY_DICTIONARY_ID);
+    }
     indices.push(0);
-    for dict_id in indices {
-      let pairs = self.lexicons[dict_id].lookup(text, offset);
+    indices
+  }
+  fn _lookup(&self, text: &[u8], offset: usize) -> Vec<(usize, usize)> {
+    let mut res = vec![];
+    for dict_id in self.dictionary_ids() {
+      let pairs = self
+        .lexicon(dict_id)
+        .unwrap()
+        .as_lexicon()
+        .lookup(text, offset);
       for (word_id, length) in pairs {
         res.push((self.build_word_id(dict_id, word_id), length));
       }
@@ -62,26 +193,95 @@ impl LexiconSet {
       .collect()
   }
   pub fn lookup(&self, text: &[u8], offset: usize) -> Vec<(usize, usize)> {
-    if self.lexicons.len() == 1 {
-      return self.lexicons[0].lookup(text, offset);
+    if self.lexicons.len() == 1 && !self.has_memory_lexicon() {
+      return self.first().lookup(text, offset);
     }
     self._lookup(text, offset)
   }
+  /// Every word whose headword is exactly `headword`, with its word id
+  pub fn exact_lookup(&self, headword: &str) -> Vec<(usize, WordInfo)> {
+    self.collect_words(|lexicon| lexicon.as_lexicon().exact_lookup(headword.as_bytes()))
+  }
+  /// Every word read as `reading_form`, with its word id
+  pub fn lookup_by_reading_form(&self, reading_form: &str) -> Vec<(usize, WordInfo)> {
+    self.collect_words(|lexicon| lexicon.get_word_ids_by_reading_form(reading_form))
+  }
+  /// Every word normalized to `normalized_form`, with its word id
+  pub fn lookup_by_normalized_form(&self, normalized_form: &str) -> Vec<(usize, WordInfo)> {
+    self.collect_words(|lexicon| lexicon.get_word_ids_by_normalized_form(normalized_form))
+  }
+  /// The word `word_id` and every inflected entry whose dictionary form it is
+  pub fn lookup_by_dictionary_form(&self, word_id: usize) -> Vec<(usize, WordInfo)> {
+    let dict_id = self.get_dictionary_id(word_id);
+    match self.lexicon(dict_id) {
+      Some(lexicon) => lexicon
+        .get_word_ids_by_dictionary_form(get_word_id(word_id))
+        .into_iter()
+        .map(|local_id| {
+          let word_id = self.build_word_id(dict_id, local_id);
+          (word_id, self.get_word_info(word_id))
+        })
+        .collect(),
+      None => vec![],
+    }
+  }
+  fn collect_words<F: Fn(&AnyLexicon) -> Vec<usize>>(
+    &self,
+    local_ids: F,
+  ) -> Vec<(usize, WordInfo)> {
+    let mut res = vec![];
+    for dict_id in self.dictionary_ids() {
+      for word_id in local_ids(self.lexicon(dict_id).unwrap()) {
+        let word_id = self.build_word_id(dict_id, word_id);
+        res.push((word_id, self.get_word_info(word_id)));
+      }
+    }
+    res
+  }
+  /// Every headword starting with `prefix` with each of its word ids, in byte order, up to
+  /// `limit` words
+  pub fn predictive_lookup(&self, prefix: &str, limit: Option<usize>) -> Vec<(String, usize)> {
+    // the first words overall are among the first words of each lexicon
+    let mut res = vec![];
+    for dict_id in self.dictionary_ids() {
+      let lexicon = self.lexicon(dict_id).unwrap().as_lexicon();
+      for (headword, word_id) in lexicon.predictive_lookup(prefix.as_bytes(), limit) {
+        res.push((headword, self.build_word_id(dict_id, word_id)));
+      }
+    }
+    res.sort_by(|(headword1, _), (headword2, _)| headword1.cmp(headword2));
+    res.truncate(limit.unwrap_or(usize::MAX));
+    res
+  }
   pub fn get_left_id(&self, word_id: usize) -> i16 {
-    self.lexicons[self.get_dictionary_id(word_id) as usize].get_left_id(get_word_id(word_id))
+    self
+      .lexicon(self.get_dictionary_id(word_id))
+      .unwrap()
+      .as_lexicon()
+      .get_left_id(get_word_id(word_id))
   }
   pub fn get_right_id(&self, word_id: usize) -> i16 {
-    self.lexicons[self.get_dictionary_id(word_id) as usize].get_right_id(get_word_id(word_id))
+    self
+      .lexicon(self.get_dictionary_id(word_id))
+      .unwrap()
+      .as_lexicon()
+      .get_right_id(get_word_id(word_id))
   }
   pub fn get_cost(&self, word_id: usize) -> i16 {
-    self.lexicons[self.get_dictionary_id(word_id) as usize].get_cost(get_word_id(word_id))
+    self
+      .lexicon(self.get_dictionary_id(word_id))
+      .unwrap()
+      .as_lexicon()
+      .get_cost(get_word_id(word_id))
   }
   pub fn get_word_info(&self, word_id: usize) -> WordInfo {
     let dict_id = self.get_dictionary_id(word_id);
-    let mut word_info = self.lexicons[dict_id].get_word_info(get_word_id(word_id));
+    let lexicon = self.lexicon(dict_id).unwrap();
+    let mut word_info = lexicon.as_lexicon().get_word_info(get_word_id(word_id));
     let pos_id = word_info.pos_id;
     // user defined part-of-speech
-    if dict_id > 0 && pos_id >= self.pos_offsets[1] as i16 {
+    let is_binary = matches!(lexicon, AnyLexicon::DoubleArrayLexicon(_));
+    if dict_id > 0 && is_binary && pos_id >= self.pos_offsets[1] as i16 {
       word_info.pos_id =
         word_info.pos_id - (self.pos_offsets[1] as i16) + (self.pos_offsets[dict_id] as i16);
     }
@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+use std::fs::read;
+use std::io::{BufRead, Error as IOError, Write};
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_8};
+use thiserror::Error;
+
+use super::dictionary_builder::{LexiconDiagnostic, LexiconRow};
+use super::dictionary_printer::escape;
+use super::simple_lexicon::to_katakana;
+use crate::plugin::default_input_text_plugin::DefaultInputTextPlugin;
+
+#[derive(Debug, Error)]
+pub enum MecabLexiconErr {
+  #[error("unknown column layout: {0} (expected ipadic, unidic or name=column pairs)")]
+  InvalidColumnsErr(String),
+  #[error("unknown encoding: {0}")]
+  UnknownEncodingErr(String),
+  #[error("{0} is not valid {1}")]
+  DecodeErr(String, &'static str),
+  #[error("invalid POS mapping at line {0}: {1}")]
+  InvalidPosMappingErr(usize, String),
+  #[error("{0}")]
+  IOError(#[from] IOError),
+  #[error("{0}")]
+  CSVError(#[from] csv::Error),
+}
+
+/// Where the fields of a MeCab dictionary row are, counting from 0 at the surface
+#[derive(Clone, Debug, PartialEq)]
+pub struct MecabColumns {
+  /// the first of the 6 POS columns, the last of which is the conjugation form
+  pub pos: usize,
+  /// the surface of the uninflected word
+  pub base_form: usize,
+  pub reading: usize,
+  /// the base form if `None`
+  pub normalized_form: Option<usize>,
+}
+
+impl MecabColumns {
+  /// IPADIC and NAIST-jdic: POS, conjugation type and form, base form, reading and pronunciation
+  pub fn ipadic() -> MecabColumns {
+    MecabColumns {
+      pos: 4,
+      base_form: 10,
+      reading: 11,
+      normalized_form: None,
+    }
+  }
+  /// UniDic (unidic-cwj 3.x): `orthBase` as the base form, `kana` as the reading and `lemma` as
+  /// the normalized form
+  pub fn unidic() -> MecabColumns {
+    MecabColumns {
+      pos: 4,
+      base_form: 14,
+      reading: 24,
+      normalized_form: Some(11),
+    }
+  }
+  fn len(&self) -> usize {
+    *[
+      self.pos + 6,
+      self.base_form + 1,
+      self.reading + 1,
+      self.normalized_form.map_or(0, |column| column + 1),
+    ]
+    .iter()
+    .max()
+    .unwrap()
+  }
+}
+
+impl FromStr for MecabColumns {
+  type Err = MecabLexiconErr;
+  /// `ipadic`, `unidic`, or `name=column` pairs by commas changing the IPADIC layout, such as
+  /// `reading=13,base=16,normalized=11`
+  fn from_str(s: &str) -> Result<MecabColumns, MecabLexiconErr> {
+    match s {
+      "ipadic" => return Ok(MecabColumns::ipadic()),
+      "unidic" => return Ok(MecabColumns::unidic()),
+      _ => {}
+    }
+    let mut columns = MecabColumns::ipadic();
+    for pair in s.split(',') {
+      let err = || MecabLexiconErr::InvalidColumnsErr(s.to_string());
+      let mut fields = pair.splitn(2, '=');
+      let name = fields.next().unwrap().trim();
+      let column = fields
+        .next()
+        .and_then(|column| column.trim().parse::<usize>().ok())
+        .ok_or_else(err)?;
+      match name {
+        "pos" => columns.pos = column,
+        "base" => columns.base_form = column,
+        "reading" => columns.reading = column,
+        "normalized" => columns.normalized_form = Some(column),
+        _ => return Err(err()),
+      }
+    }
+    Ok(columns)
+  }
+}
+
+/// Sudachi parts of speech for MeCab ones, read from lines of
+/// `MeCab POS<TAB>Sudachi POS` with the levels separated by commas.
+///
+/// A MeCab POS of fewer levels matches every POS starting with them and `*` matches any level;
+/// the most specific line wins, and the first of them on a tie. A Sudachi POS of fewer than 6
+/// levels is completed with the MeCab levels at the same positions, which keeps the conjugation
+/// type and form. POS no line matches are kept as they are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PosMapping {
+  rules: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl PosMapping {
+  pub fn from_reader<R: BufRead>(reader: &mut R) -> Result<PosMapping, MecabLexiconErr> {
+    let mut rules = vec![];
+    for (i, line) in reader.lines().enumerate() {
+      let line = line?;
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let fields: Vec<&str> = line.split('\t').collect();
+      if fields.len() != 2 {
+        return Err(MecabLexiconErr::InvalidPosMappingErr(
+          i + 1,
+          String::from("expected a MeCab POS and a Sudachi POS separated by a tab"),
+        ));
+      }
+      let levels = |field: &str| -> Vec<String> {
+        field
+          .split(',')
+          .map(|level| level.trim().to_string())
+          .collect()
+      };
+      let (from, to) = (levels(fields[0]), levels(fields[1]));
+      if to.len() > 6 {
+        return Err(MecabLexiconErr::InvalidPosMappingErr(
+          i + 1,
+          String::from("more than 6 levels of Sudachi POS"),
+        ));
+      }
+      rules.push((from, to));
+    }
+    Ok(PosMapping { rules })
+  }
+  /// The 6 levels of Sudachi POS for the 6 levels of MeCab POS
+  pub fn map(&self, pos: &[String]) -> Vec<String> {
+    let matches = |from: &Vec<String>| {
+      from.len() <= pos.len()
+        && from
+          .iter()
+          .zip(pos)
+          .all(|(level, pos_level)| level == "*" || level == pos_level)
+    };
+    let specificity = |from: &Vec<String>| {
+      (
+        from.iter().filter(|level| *level != "*").count(),
+        from.len(),
+      )
+    };
+    let mut best: Option<&(Vec<String>, Vec<String>)> = None;
+    for rule in self.rules.iter().filter(|(from, _)| matches(from)) {
+      if best.is_none_or(|(from, _)| specificity(&rule.0) > specificity(from)) {
+        best = Some(rule);
+      }
+    }
+    let mut levels = best.map_or(vec![], |(_, to)| to.clone());
+    levels.extend(pos.iter().skip(levels.len()).cloned());
+    levels.resize(6, String::from("*"));
+    levels
+  }
+}
+
+/// A column, the value in it and what is wrong with it
+type FieldErr = (Option<&'static str>, String, String);
+
+struct MecabRow {
+  surface: String,
+  left_id: String,
+  right_id: String,
+  cost: String,
+  pos: Vec<String>,
+  base_form: String,
+  reading: String,
+  normalized_form: String,
+}
+
+/// Converts the CSV of MeCab dictionaries to the 18 lexicon columns.
+///
+/// Connection ids and costs are kept, so the converted lexicon goes with the matrix.def and
+/// char.def of the same MeCab dictionary. The headword is the surface as the tokenizer sees it,
+/// the reading is made katakana and the dictionary form is the word id of the row of the base
+/// form with the same POS but the conjugation form, counting the rows of every file in order.
+/// No word is split.
+pub struct MecabLexiconReader<'a> {
+  input_text_plugin: &'a DefaultInputTextPlugin,
+  columns: MecabColumns,
+  pos_mapping: PosMapping,
+  encoding: &'static Encoding,
+}
+
+impl<'a> MecabLexiconReader<'a> {
+  pub fn new(
+    input_text_plugin: &'a DefaultInputTextPlugin,
+    columns: MecabColumns,
+  ) -> MecabLexiconReader<'a> {
+    MecabLexiconReader {
+      input_text_plugin,
+      columns,
+      pos_mapping: PosMapping::default(),
+      encoding: UTF_8,
+    }
+  }
+  pub fn set_pos_mapping(&mut self, pos_mapping: PosMapping) {
+    self.pos_mapping = pos_mapping;
+  }
+  /// Decode the source files with `label`, such as `euc-jp` for IPADIC, rather than UTF-8
+  pub fn set_encoding(&mut self, label: &str) -> Result<(), MecabLexiconErr> {
+    self.encoding = Encoding::for_label(label.as_bytes())
+      .ok_or_else(|| MecabLexiconErr::UnknownEncodingErr(label.to_string()))?;
+    Ok(())
+  }
+  /// The lexicon columns of every row of each of `paths` with its line number
+  pub fn read(&self, paths: &[&str]) -> Result<Vec<(String, Vec<LexiconRow>)>, MecabLexiconErr> {
+    let mut files = vec![];
+    for path in paths {
+      let bytes = read(path)?;
+      let (text, _, had_errors) = self.encoding.decode(&bytes);
+      if had_errors {
+        return Err(MecabLexiconErr::DecodeErr(
+          path.to_string(),
+          self.encoding.name(),
+        ));
+      }
+      let mut rows = vec![];
+      for (i, record) in csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes())
+        .into_records()
+        .enumerate()
+      {
+        let line = match &record {
+          Ok(r) => r.position(),
+          Err(e) => e.position(),
+        }
+        .map_or(i + 1, |p| p.line() as usize);
+        let row = record
+          .map_err(|e| (None, String::new(), e.to_string()))
+          .and_then(|record| self.parse_record(&record));
+        rows.push((line, row));
+      }
+      files.push((path.to_string(), rows));
+    }
+
+    // the word id of each row, as the builder numbers the rows it is given
+    let mut word_ids: HashMap<(&str, &[String]), usize> = HashMap::new();
+    for (word_id, row) in files
+      .iter()
+      .flat_map(|(_, rows)| rows)
+      .filter_map(|(_, row)| row.as_ref().ok())
+      .enumerate()
+    {
+      word_ids
+        .entry((row.surface.as_str(), &row.pos[..5]))
+        .or_insert(word_id);
+    }
+    let dictionary_form = |row: &MecabRow| {
+      if row.base_form == row.surface || row.base_form == "*" {
+        return String::from("*");
+      }
+      word_ids
+        .get(&(row.base_form.as_str(), &row.pos[..5]))
+        .map_or(String::from("*"), |word_id| word_id.to_string())
+    };
+
+    Ok(
+      files
+        .iter()
+        .map(|(path, rows)| {
+          let rows = rows
+            .iter()
+            .map(|(line, row)| {
+              let cols = match row {
+                Ok(row) => Ok(self.expand(row, dictionary_form(row))),
+                Err((column, value, reason)) => Err(LexiconDiagnostic {
+                  file: path.clone(),
+                  line: *line,
+                  column: *column,
+                  value: value.clone(),
+                  reason: reason.clone(),
+                }),
+              };
+              (*line, cols)
+            })
+            .collect();
+          (path.clone(), rows)
+        })
+        .collect(),
+    )
+  }
+  fn parse_record(&self, record: &csv::StringRecord) -> Result<MecabRow, FieldErr> {
+    if record.len() < self.columns.len() {
+      return Err((
+        None,
+        String::new(),
+        format!(
+          "{} fields, expected at least {}",
+          record.len(),
+          self.columns.len()
+        ),
+      ));
+    }
+    let field = |column: usize| record[column].to_string();
+    let surface = field(0);
+    if surface.is_empty() {
+      return Err((Some("surface"), String::new(), String::from("empty")));
+    }
+    let reading = match field(self.columns.reading) {
+      reading if reading.is_empty() || reading == "*" => surface.clone(),
+      reading => reading,
+    };
+    let base_form = field(self.columns.base_form);
+    let normalized_form = match self.columns.normalized_form {
+      Some(column) => strip_lemma_subtype(&record[column]).to_string(),
+      None => base_form.clone(),
+    };
+    let normalized_form = if normalized_form.is_empty() || normalized_form == "*" {
+      surface.clone()
+    } else {
+      normalized_form
+    };
+    Ok(MecabRow {
+      left_id: field(1),
+      right_id: field(2),
+      cost: field(3),
+      pos: (self.columns.pos..self.columns.pos + 6)
+        .map(field)
+        .collect(),
+      base_form,
+      reading,
+      normalized_form,
+      surface,
+    })
+  }
+  fn expand(&self, row: &MecabRow, dictionary_form: String) -> Vec<String> {
+    let mut cols = vec![
+      escape(&self.input_text_plugin.normalize(&row.surface)),
+      row.left_id.clone(),
+      row.right_id.clone(),
+      row.cost.clone(),
+      escape(&row.surface),
+    ];
+    cols.extend(self.pos_mapping.map(&row.pos).iter().map(|p| escape(p)));
+    cols.extend(vec![
+      escape(&to_katakana(&row.reading)),
+      escape(&row.normalized_form),
+      dictionary_form,
+      String::from("A"),
+      String::from("*"),
+      String::from("*"),
+      String::from("*"),
+    ]);
+    cols
+  }
+}
+
+/// A UniDic lemma without the origin of loanwords, such as `ペン` of `ペン-pen`
+fn strip_lemma_subtype(lemma: &str) -> &str {
+  match lemma.rfind('-') {
+    Some(i) if i > 0 && lemma[i + 1..].is_ascii() => &lemma[..i],
+    _ => lemma,
+  }
+}
+
+/// Write converted rows as a lexicon CSV `DictionaryBuilder` reads
+pub fn write_rows<'b, I, W>(rows: I, writer: W) -> Result<(), MecabLexiconErr>
+where
+  I: IntoIterator<Item = &'b Vec<String>>,
+  W: Write,
+{
+  let mut writer = csv::WriterBuilder::new()
+    .has_headers(false)
+    .from_writer(writer);
+  for cols in rows {
+    writer.write_record(cols)?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::resources::get_bundled_resource;
+  use encoding_rs::EUC_JP;
+  use std::env::temp_dir;
+  use std::fs::{create_dir_all, File};
+
+  const IPADIC: &str = "\
+行く,10,10,5000,動詞,自立,*,*,五段・カ行促音便,基本形,行く,イク,イク\n\
+行っ,11,11,5100,動詞,自立,*,*,五段・カ行促音便,連用タ接続,行く,イッ,イッ\n\
+\"，\",1,1,100,記号,読点,*,*,*,*,\"，\",、,、\n\
+ＡＢＣ,2,2,3000,名詞,固有名詞,組織,*,*,*,*,*\n\
+東京,3,3,2000,名詞,固有名詞,地域,一般,*,*,東京,とうきょう,トーキョー\n";
+
+  fn write_source(name: &str, source: &[u8]) -> String {
+    let dir = temp_dir().join("test_mecab_lexicon");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    File::create(&path).unwrap().write_all(source).unwrap();
+    path.to_str().unwrap().to_string()
+  }
+
+  fn plugin() -> DefaultInputTextPlugin {
+    DefaultInputTextPlugin::from_reader(
+      &mut get_bundled_resource("rewrite.def").unwrap().as_bytes(),
+    )
+    .unwrap()
+  }
+
+  fn rows(files: Vec<(String, Vec<LexiconRow>)>) -> Vec<Result<Vec<String>, LexiconDiagnostic>> {
+    files
+      .into_iter()
+      .flat_map(|(_, rows)| rows)
+      .map(|(_, row)| row)
+      .collect()
+  }
+
+  #[test]
+  fn test_read_ipadic() {
+    let path = write_source("ipadic.csv", IPADIC.as_bytes());
+    let plugin = plugin();
+    let reader = MecabLexiconReader::new(&plugin, MecabColumns::ipadic());
+    let rows = rows(reader.read(&[&path]).unwrap());
+    let rows: Vec<String> = rows.into_iter().map(|row| row.unwrap().join(",")).collect();
+    assert_eq!(
+      vec![
+        "行く,10,10,5000,行く,動詞,自立,*,*,五段・カ行促音便,基本形,イク,行く,*,A,*,*,*",
+        "行っ,11,11,5100,行っ,動詞,自立,*,*,五段・カ行促音便,連用タ接続,イッ,行く,0,A,*,*,*",
+        ",,1,1,100,，,記号,読点,*,*,*,*,、,，,*,A,*,*,*",
+        "abc,2,2,3000,ＡＢＣ,名詞,固有名詞,組織,*,*,*,ＡＢＣ,ＡＢＣ,*,A,*,*,*",
+        "東京,3,3,2000,東京,名詞,固有名詞,地域,一般,*,*,トウキョウ,東京,*,A,*,*,*",
+      ],
+      rows
+    );
+  }
+
+  #[test]
+  fn test_read_across_files() {
+    let base = write_source("base.csv", IPADIC.lines().next().unwrap().as_bytes());
+    let source = write_source(
+      "inflected.csv",
+      "\
+行か,12,12,5200,動詞,自立,*,*,五段・カ行促音便,未然形,行く,イカ,イカ\n\
+行か,12,12,5200,名詞,一般,*,*,*,*,行く,イカ,イカ\n\
+行き\n"
+        .as_bytes(),
+    );
+    let plugin = plugin();
+    let reader = MecabLexiconReader::new(&plugin, MecabColumns::ipadic());
+    let files = reader.read(&[&base, &source]).unwrap();
+    assert_eq!(source, files[1].0);
+    assert_eq!(
+      vec![1, 2, 3],
+      files[1]
+        .1
+        .iter()
+        .map(|(line, _)| *line)
+        .collect::<Vec<usize>>()
+    );
+    let rows = rows(files);
+    assert_eq!("0", rows[1].as_ref().unwrap()[13]);
+    assert_eq!("*", rows[2].as_ref().unwrap()[13]);
+    let diagnostic = rows[3].as_ref().unwrap_err();
+    assert_eq!(3, diagnostic.line);
+    assert_eq!("1 fields, expected at least 12", diagnostic.reason);
+  }
+
+  #[test]
+  fn test_read_unidic_euc_jp() {
+    let mut fields = vec!["*"; 33];
+    fields[..16].copy_from_slice(&[
+      "ペン",
+      "5",
+      "5",
+      "4000",
+      "名詞",
+      "普通名詞",
+      "一般",
+      "*",
+      "*",
+      "*",
+      "ペン",
+      "ペン-pen",
+      "ペン",
+      "ペン",
+      "ペン",
+      "ペン",
+    ]);
+    fields[24] = "ペン";
+    let source = fields.join(",");
+    let (source, _, _) = EUC_JP.encode(&source);
+    let path = write_source("unidic.csv", &source);
+    let plugin = plugin();
+    let mut reader = MecabLexiconReader::new(&plugin, MecabColumns::unidic());
+    assert!(reader.read(&[&path]).is_err());
+    reader.set_encoding("euc-jp").unwrap();
+    let cols = rows(reader.read(&[&path]).unwrap()).remove(0).unwrap();
+    assert_eq!("ペン", cols[11]);
+    assert_eq!("ペン", cols[12]);
+    assert_eq!("*", cols[13]);
+  }
+
+  #[test]
+  fn test_columns_from_str() {
+    assert_eq!(MecabColumns::unidic(), "unidic".parse().unwrap());
+    assert_eq!(
+      MecabColumns {
+        pos: 4,
+        base_form: 16,
+        reading: 13,
+        normalized_form: Some(11),
+      },
+      "reading=13,base=16,normalized=11".parse().unwrap()
+    );
+    assert!("reading=x".parse::<MecabColumns>().is_err());
+    assert!("lemma=1".parse::<MecabColumns>().is_err());
+  }
+
+  #[test]
+  fn test_pos_mapping() {
+    let mapping = PosMapping::from_reader(
+      &mut "\
+# IPADIC to UniDic
+名詞\t名詞,普通名詞,一般,*,*,*\n\
+名詞,固有名詞,地域\t名詞,固有名詞,地名,一般,*,*\n\
+名詞,*,組織\t名詞,固有名詞,一般,*,*,*\n\
+動詞,自立\t動詞,一般,*,*\n"
+        .as_bytes(),
+    )
+    .unwrap();
+    let map = |pos: &str| {
+      mapping
+        .map(&pos.split(',').map(String::from).collect::<Vec<String>>())
+        .join(",")
+    };
+    assert_eq!("名詞,普通名詞,一般,*,*,*", map("名詞,一般,*,*,*,*"));
+    assert_eq!(
+      "名詞,固有名詞,地名,一般,*,*",
+      map("名詞,固有名詞,地域,一般,*,*")
+    );
+    assert_eq!("名詞,固有名詞,一般,*,*,*", map("名詞,固有名詞,組織,*,*,*"));
+    assert_eq!(
+      "動詞,一般,*,*,五段・カ行促音便,連用タ接続",
+      map("動詞,自立,*,*,五段・カ行促音便,連用タ接続")
+    );
+    assert_eq!("記号,読点,*,*,*,*", map("記号,読点,*,*,*,*"));
+
+    assert!(PosMapping::from_reader(&mut "名詞 名詞\n".as_bytes()).is_err());
+    assert!(PosMapping::from_reader(&mut "名詞\ta,b,c,d,e,f,g\n".as_bytes()).is_err());
+  }
+}
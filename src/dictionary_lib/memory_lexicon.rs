@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use super::lexicon::{Lexicon, Size};
+use super::word_info::WordInfo;
+
+#[derive(Clone)]
+struct MemoryWord {
+  headword: String,
+  left_id: i16,
+  right_id: i16,
+  cost: i16,
+  word_info: WordInfo,
+  removed: bool,
+}
+
+/// A lexicon held in memory whose words can be added and removed at runtime
+///
+/// Removed words only disappear from the lookups: their word ids are never reused, so
+/// morphemes tokenized before the removal still resolve to the right word.
+#[derive(Clone, Default)]
+pub struct MemoryLexicon {
+  words: Vec<MemoryWord>,
+  headwords: BTreeMap<String, Vec<usize>>,
+  max_headword_length: usize,
+}
+
+impl MemoryLexicon {
+  pub fn new() -> MemoryLexicon {
+    MemoryLexicon::default()
+  }
+  /// Add a word looked up by `headword` and return its word id
+  pub fn add_word(
+    &mut self,
+    headword: &str,
+    left_id: i16,
+    right_id: i16,
+    cost: i16,
+    word_info: WordInfo,
+  ) -> usize {
+    let word_id = self.words.len();
+    self.words.push(MemoryWord {
+      headword: headword.to_string(),
+      left_id,
+      right_id,
+      cost,
+      word_info,
+      removed: false,
+    });
+    self
+      .headwords
+      .entry(headword.to_string())
+      .or_default()
+      .push(word_id);
+    self.max_headword_length = self.max_headword_length.max(headword.len());
+    word_id
+  }
+  /// Remove a word from the lookups, returning whether it was there
+  pub fn remove_word(&mut self, word_id: usize) -> bool {
+    let word = match self.words.get_mut(word_id) {
+      Some(word) if !word.removed => word,
+      _ => return false,
+    };
+    word.removed = true;
+    if let Some(word_ids) = self.headwords.get_mut(&word.headword) {
+      word_ids.retain(|id| *id != word_id);
+      if word_ids.is_empty() {
+        self.headwords.remove(&word.headword);
+      }
+    }
+    true
+  }
+  fn find_word_ids<F: Fn(&WordInfo) -> bool>(&self, predicate: F) -> Vec<usize> {
+    self
+      .words
+      .iter()
+      .enumerate()
+      .filter(|(_, word)| !word.removed && predicate(&word.word_info))
+      .map(|(word_id, _)| word_id)
+      .collect()
+  }
+  pub fn get_word_ids_by_reading_form(&self, reading_form: &str) -> Vec<usize> {
+    self.find_word_ids(|word_info| word_info.reading_form == reading_form)
+  }
+  pub fn get_word_ids_by_normalized_form(&self, normalized_form: &str) -> Vec<usize> {
+    self.find_word_ids(|word_info| word_info.normalized_form == normalized_form)
+  }
+  /// Words added at runtime are their own dictionary form
+  pub fn get_word_ids_by_dictionary_form(&self, word_id: usize) -> Vec<usize> {
+    match self.words.get(word_id) {
+      Some(word) if !word.removed => vec![word_id],
+      _ => vec![],
+    }
+  }
+}
+
+impl Lexicon for MemoryLexicon {
+  fn lookup(&self, text: &[u8], offset: usize) -> Vec<(usize, usize)> {
+    let end = text.len().min(offset + self.max_headword_length);
+    let mut res = vec![];
+    for length in offset + 1..=end {
+      let key = match std::str::from_utf8(&text[offset..length]) {
+        Ok(key) => key,
+        Err(_) => continue,
+      };
+      if let Some(word_ids) = self.headwords.get(key) {
+        res.extend(word_ids.iter().map(|word_id| (*word_id, length)));
+      }
+    }
+    res
+  }
+  fn exact_lookup(&self, headword: &[u8]) -> Vec<usize> {
+    std::str::from_utf8(headword)
+      .ok()
+      .and_then(|headword| self.headwords.get(headword))
+      .cloned()
+      .unwrap_or_default()
+  }
+  fn predictive_lookup(&self, prefix: &[u8], limit: Option<usize>) -> Vec<(String, usize)> {
+    let prefix = String::from_utf8_lossy(prefix).to_string();
+    self
+      .headwords
+      .range(prefix.clone()..)
+      .take_while(|(headword, _)| headword.starts_with(&prefix))
+      .flat_map(|(headword, word_ids)| {
+        word_ids
+          .iter()
+          .map(move |word_id| (headword.clone(), *word_id))
+      })
+      .take(limit.unwrap_or(usize::MAX))
+      .collect()
+  }
+  fn get_left_id(&self, word_id: usize) -> i16 {
+    self.words[word_id].left_id
+  }
+  fn get_right_id(&self, word_id: usize) -> i16 {
+    self.words[word_id].right_id
+  }
+  fn get_cost(&self, word_id: usize) -> i16 {
+    self.words[word_id].cost
+  }
+  fn get_word_info(&self, word_id: usize) -> WordInfo {
+    self.words[word_id].word_info.clone()
+  }
+  fn get_dictionary_id(&self, _word_id: usize) -> usize {
+    0
+  }
+}
+
+impl Size for MemoryLexicon {
+  fn size(&self) -> usize {
+    self.words.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn word_info(surface: &str) -> WordInfo {
+    WordInfo {
+      surface: surface.to_string(),
+      head_word_length: surface.len(),
+      pos_id: 0,
+      normalized_form: surface.to_string(),
+      dictionary_form_word_id: -1,
+      dictionary_form: surface.to_string(),
+      reading_form: String::from("スダチ"),
+      a_unit_split: vec![],
+      b_unit_split: vec![],
+      word_structure: vec![],
+    }
+  }
+
+  #[test]
+  fn test_add_and_remove_word() {
+    let mut lexicon = MemoryLexicon::new();
+    assert_eq!(
+      0,
+      lexicon.add_word("すだち", 1, 2, 3000, word_info("すだち"))
+    );
+    assert_eq!(
+      1,
+      lexicon.add_word("すだちくん", 4, 5, 2000, word_info("すだちくん"))
+    );
+
+    let text = "すだちくんだ".as_bytes();
+    assert_eq!(vec![(0, 9), (1, 15)], lexicon.lookup(text, 0));
+    assert!(lexicon.lookup(text, 3).is_empty());
+    assert_eq!(vec![1], lexicon.exact_lookup("すだちくん".as_bytes()));
+    assert_eq!(
+      vec![(String::from("すだち"), 0), (String::from("すだちくん"), 1)],
+      lexicon.predictive_lookup("すだ".as_bytes(), None)
+    );
+    assert_eq!(
+      vec![(String::from("すだち"), 0)],
+      lexicon.predictive_lookup("すだ".as_bytes(), Some(1))
+    );
+    assert_eq!(vec![0, 1], lexicon.get_word_ids_by_reading_form("スダチ"));
+    assert_eq!(4, lexicon.get_left_id(1));
+    assert_eq!(5, lexicon.get_right_id(1));
+    assert_eq!(2000, lexicon.get_cost(1));
+
+    assert!(lexicon.remove_word(0));
+    assert!(!lexicon.remove_word(0));
+    assert!(!lexicon.remove_word(2));
+    assert_eq!(vec![(1, 15)], lexicon.lookup(text, 0));
+    assert!(lexicon.exact_lookup("すだち".as_bytes()).is_empty());
+    assert!(lexicon.get_word_ids_by_dictionary_form(0).is_empty());
+    assert_eq!("すだち", lexicon.get_word_info(0).surface);
+    assert_eq!(2, lexicon.size());
+
+    // the other words of the headword stay
+    assert_eq!(
+      2,
+      lexicon.add_word("すだちくん", 4, 5, 1000, word_info("すだちくん"))
+    );
+    assert!(lexicon.remove_word(1));
+    assert_eq!(vec![2], lexicon.exact_lookup("すだちくん".as_bytes()));
+    assert!(lexicon.remove_word(2));
+    assert!(lexicon.predictive_lookup(b"", None).is_empty());
+  }
+}
@@ -0,0 +1,390 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error as IOError};
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::dictionary_builder::{LexiconDiagnostic, LexiconRow};
+use super::dictionary_printer::escape;
+use super::double_array_lexicon::DoubleArrayLexicon;
+use super::lexicon::Lexicon;
+use crate::plugin::default_input_text_plugin::DefaultInputTextPlugin;
+
+/// Parts of speech that can be given by name instead of their 6 levels
+pub const POS_PRESETS: [(&str, [&str; 6]); 9] = [
+  ("noun", ["名詞", "普通名詞", "一般", "*", "*", "*"]),
+  ("suru noun", ["名詞", "普通名詞", "サ変可能", "*", "*", "*"]),
+  (
+    "adjectival noun",
+    ["名詞", "普通名詞", "形状詞可能", "*", "*", "*"],
+  ),
+  ("proper noun", ["名詞", "固有名詞", "一般", "*", "*", "*"]),
+  (
+    "person name",
+    ["名詞", "固有名詞", "人名", "一般", "*", "*"],
+  ),
+  ("surname", ["名詞", "固有名詞", "人名", "姓", "*", "*"]),
+  ("given name", ["名詞", "固有名詞", "人名", "名", "*", "*"]),
+  ("place name", ["名詞", "固有名詞", "地名", "一般", "*", "*"]),
+  ("country", ["名詞", "固有名詞", "地名", "国", "*", "*"]),
+];
+
+/// The simplified user dictionary sources, told apart by file extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimpleLexiconFormat {
+  /// `.tsv`: surface, POS, reading and optionally normalized form and split, by tabs
+  Tsv,
+  /// `.jsonl`: an object with `surface`, `pos`, `reading` and optionally `normalized` and
+  /// `split` by line
+  JsonLines,
+}
+
+impl SimpleLexiconFormat {
+  /// `None` for the 18 column CSV
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Option<SimpleLexiconFormat> {
+    match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+      Some("tsv") => Some(SimpleLexiconFormat::Tsv),
+      Some("jsonl") => Some(SimpleLexiconFormat::JsonLines),
+      _ => None,
+    }
+  }
+}
+
+/// A column, the value in it and what is wrong with it
+type FieldErr = (Option<&'static str>, String, String);
+
+struct SimpleRow {
+  surface: String,
+  pos: Vec<String>,
+  reading: String,
+  normalized: Option<String>,
+  split: Vec<String>,
+}
+
+/// Expands the rows of the simplified formats to the 18 lexicon columns.
+///
+/// The headword is the surface as the tokenizer sees it, the reading is made katakana and the
+/// connection ids and cost are left to the builder. A split unit is a word id,
+/// (surface, POS, reading) as in the CSV, or a surface of a single word of the system
+/// dictionary.
+pub struct SimpleLexiconReader<'a> {
+  input_text_plugin: &'a DefaultInputTextPlugin,
+  system_lexicon: &'a DoubleArrayLexicon,
+}
+
+impl<'a> SimpleLexiconReader<'a> {
+  pub fn new(
+    input_text_plugin: &'a DefaultInputTextPlugin,
+    system_lexicon: &'a DoubleArrayLexicon,
+  ) -> SimpleLexiconReader<'a> {
+    SimpleLexiconReader {
+      input_text_plugin,
+      system_lexicon,
+    }
+  }
+  /// The lexicon columns of every row of `path` with its line number
+  pub fn read(&self, path: &str, format: SimpleLexiconFormat) -> Result<Vec<LexiconRow>, IOError> {
+    let mut rows = vec![];
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+      let line = line?;
+      let line = line.trim_end_matches('\r');
+      if line.trim().is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let row = match format {
+        SimpleLexiconFormat::Tsv if i == 0 && line.starts_with("surface\t") => continue,
+        SimpleLexiconFormat::Tsv => parse_tsv(line),
+        SimpleLexiconFormat::JsonLines => parse_json(line),
+      };
+      let cols = row
+        .and_then(|row| self.expand(row))
+        .map_err(|(column, value, reason)| LexiconDiagnostic {
+          file: path.to_string(),
+          line: i + 1,
+          column,
+          value,
+          reason,
+        });
+      rows.push((i + 1, cols));
+    }
+    Ok(rows)
+  }
+  fn expand(&self, row: SimpleRow) -> Result<Vec<String>, FieldErr> {
+    if row.surface.is_empty() {
+      return Err((Some("surface"), String::new(), String::from("empty")));
+    }
+    if row.reading.is_empty() {
+      return Err((Some("reading"), String::new(), String::from("empty")));
+    }
+    let pos = expand_pos(&row.pos)?;
+    let split = row
+      .split
+      .iter()
+      .map(|unit| self.resolve_split_unit(unit))
+      .collect::<Result<Vec<String>, FieldErr>>()?;
+    let (mode, split) = if split.is_empty() {
+      ("A", String::from("*"))
+    } else {
+      ("B", split.join("/"))
+    };
+
+    let mut cols = vec![
+      escape(&self.input_text_plugin.normalize(&row.surface)),
+      String::from("*"),
+      String::from("*"),
+      String::from("*"),
+      escape(&row.surface),
+    ];
+    cols.extend(pos.iter().map(|p| escape(p)));
+    cols.extend(vec![
+      escape(&to_katakana(&row.reading)),
+      escape(row.normalized.as_ref().unwrap_or(&row.surface)),
+      String::from("*"),
+      String::from(mode),
+      split.clone(),
+      String::from("*"),
+      split,
+    ]);
+    Ok(cols)
+  }
+  fn resolve_split_unit(&self, unit: &str) -> Result<String, FieldErr> {
+    let is_id = !unit.trim_start_matches('U').is_empty()
+      && unit
+        .trim_start_matches('U')
+        .chars()
+        .all(|c| c.is_ascii_digit());
+    if is_id || unit.contains(',') {
+      return Ok(unit.to_string());
+    }
+    let headword = self.input_text_plugin.normalize(unit);
+    match self
+      .system_lexicon
+      .exact_lookup(headword.as_bytes())
+      .as_slice()
+    {
+      [word_id] => Ok(word_id.to_string()),
+      [] => Err((
+        Some("split"),
+        unit.to_string(),
+        String::from("no word of the system dictionary has this surface"),
+      )),
+      word_ids => Err((
+        Some("split"),
+        unit.to_string(),
+        format!(
+          "ambiguous surface, {} words of the system dictionary have it; give surface,POS,reading or a word id",
+          word_ids.len()
+        ),
+      )),
+    }
+  }
+}
+
+fn parse_tsv(line: &str) -> Result<SimpleRow, FieldErr> {
+  let fields: Vec<&str> = line.split('\t').map(|field| field.trim()).collect();
+  if fields.len() < 3 || fields.len() > 5 {
+    return Err((
+      None,
+      String::new(),
+      format!("{} fields, expected 3 to 5", fields.len()),
+    ));
+  }
+  let optional = |i: usize| fields.get(i).filter(|field| !field.is_empty());
+  Ok(SimpleRow {
+    surface: fields[0].to_string(),
+    pos: fields[1].split(',').map(String::from).collect(),
+    reading: fields[2].to_string(),
+    normalized: optional(3).map(|field| field.to_string()),
+    split: optional(4).map_or(vec![], |field| field.split('/').map(String::from).collect()),
+  })
+}
+
+fn parse_json(line: &str) -> Result<SimpleRow, FieldErr> {
+  let value: Value =
+    serde_json::from_str(line).map_err(|e| (None, String::new(), e.to_string()))?;
+  let object = value
+    .as_object()
+    .ok_or_else(|| (None, String::new(), String::from("not an object")))?;
+  let string = |key: &'static str| match object.get(key) {
+    None | Some(Value::Null) => Ok(None),
+    Some(Value::String(s)) => Ok(Some(s.clone())),
+    Some(value) => Err((Some(key), value.to_string(), String::from("not a string"))),
+  };
+  // `pos` and `split` are also accepted as arrays
+  let strings = |key: &'static str, separator: char| match object.get(key) {
+    Some(Value::Array(values)) => values
+      .iter()
+      .map(|value| match value {
+        Value::String(s) => Ok(s.clone()),
+        value => Err((Some(key), value.to_string(), String::from("not a string"))),
+      })
+      .collect::<Result<Vec<String>, FieldErr>>(),
+    _ => Ok(string(key)?.map_or(vec![], |s| s.split(separator).map(String::from).collect())),
+  };
+  Ok(SimpleRow {
+    surface: string("surface")?.unwrap_or_default(),
+    pos: strings("pos", ',')?,
+    reading: string("reading")?.unwrap_or_default(),
+    normalized: string("normalized")?.filter(|s| !s.is_empty()),
+    split: strings("split", '/')?,
+  })
+}
+
+/// A preset name, or up to 6 levels of POS filled up with `*`
+fn expand_pos(pos: &[String]) -> Result<Vec<String>, FieldErr> {
+  if pos.len() == 1 {
+    if let Some((_, levels)) = POS_PRESETS.iter().find(|(name, _)| *name == pos[0]) {
+      return Ok(levels.iter().map(|level| level.to_string()).collect());
+    }
+  }
+  if pos.iter().all(|level| level.is_empty()) {
+    return Err((Some("pos"), String::new(), String::from("empty")));
+  }
+  if pos.len() > 6 {
+    return Err((
+      Some("pos"),
+      pos.join(","),
+      String::from("more than 6 levels"),
+    ));
+  }
+  let mut levels: Vec<String> = pos.to_vec();
+  levels.resize(6, String::from("*"));
+  Ok(levels)
+}
+
+/// Hiragana as katakana, leaving everything else as it is
+pub(crate) fn to_katakana(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| match c {
+      'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => std::char::from_u32(c as u32 + 0x60).unwrap_or(c),
+      c => c,
+    })
+    .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::binary_dictionary::BinaryDictionary;
+  use crate::resources::get_bundled_resource;
+  use std::env::temp_dir;
+  use std::fs::create_dir_all;
+  use std::io::Write;
+
+  fn write_source(name: &str, source: &str) -> String {
+    let dir = temp_dir().join("test_simple_lexicon");
+    create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    File::create(&path)
+      .unwrap()
+      .write_all(source.as_bytes())
+      .unwrap();
+    path.to_str().unwrap().to_string()
+  }
+
+  fn read(name: &str, source: &str) -> Vec<LexiconRow> {
+    let dictionary = BinaryDictionary::from_system_dictionary_bytes(include_bytes!(
+      "../resources/test/system.dic"
+    ))
+    .unwrap();
+    let plugin = DefaultInputTextPlugin::from_reader(
+      &mut get_bundled_resource("rewrite.def").unwrap().as_bytes(),
+    )
+    .unwrap();
+    let path = write_source(name, source);
+    SimpleLexiconReader::new(&plugin, &dictionary.lexicon)
+      .read(&path, SimpleLexiconFormat::from_path(&path).unwrap())
+      .unwrap()
+  }
+
+  #[test]
+  fn test_from_path() {
+    assert_eq!(
+      Some(SimpleLexiconFormat::Tsv),
+      SimpleLexiconFormat::from_path("words.tsv")
+    );
+    assert_eq!(
+      Some(SimpleLexiconFormat::JsonLines),
+      SimpleLexiconFormat::from_path("words.jsonl")
+    );
+    assert_eq!(None, SimpleLexiconFormat::from_path("words.csv"));
+  }
+
+  #[test]
+  fn test_read_tsv() {
+    let rows = read(
+      "words.tsv",
+      "surface\tpos\treading\tnormalized\tsplit\n\
+       ＡＢＣ銀行\tproper noun\tえーびーしーぎんこう\tABC銀行\n\
+       \n\
+       東京タワー\t名詞,固有名詞,一般\tトウキョウタワー\t\t東京/タワー,名詞,普通名詞,一般,*,*,*,タワー\n",
+    );
+    assert_eq!(2, rows.len());
+    assert_eq!(2, rows[0].0);
+    assert_eq!(
+      "abc銀行,*,*,*,ＡＢＣ銀行,名詞,固有名詞,一般,*,*,*,エービーシーギンコウ,ABC銀行,*,A,*,*,*",
+      rows[0].1.as_ref().unwrap().join(",")
+    );
+    assert_eq!(4, rows[1].0);
+    let cols = rows[1].1.as_ref().unwrap();
+    assert_eq!(
+      vec!["名詞", "固有名詞", "一般", "*", "*", "*"],
+      cols[5..11].to_vec()
+    );
+    assert_eq!("東京タワー", cols[12]);
+    assert_eq!("B", cols[14]);
+    assert_eq!("5/タワー,名詞,普通名詞,一般,*,*,*,タワー", cols[15]);
+    assert_eq!(cols[15], cols[17]);
+  }
+
+  #[test]
+  fn test_read_json_lines() {
+    let rows = read(
+      "words.jsonl",
+      "{\"surface\": \"東京タワー\", \"pos\": \"place name\", \"reading\": \"トウキョウタワー\", \"split\": [\"東京\", \"1\"]}\n\
+       {\"surface\": \"東京\", \"pos\": [\"名詞\", \"普通名詞\"], \"reading\": 1}\n\
+       {\"surface\": \"東京\", \"pos\": \"unknown preset,a,b,c,d,e,f\", \"reading\": \"とうきょう\"}\n\
+       {\"surface\": \"ソラ\", \"pos\": \"noun\", \"reading\": \"そら\", \"split\": \"ソ/ラ\"}\n\
+       {\"surface\": \"東京に\", \"pos\": \"noun\", \"reading\": \"とうきょうに\", \"split\": \"東京/に\"}\n\
+       [\"東京\"]\n",
+    );
+    let cols = rows[0].1.as_ref().unwrap();
+    assert_eq!("地名", cols[7]);
+    assert_eq!("5/1", cols[15]);
+
+    let errs: Vec<(Option<&str>, String)> = rows[1..]
+      .iter()
+      .map(|(_, row)| {
+        let diagnostic = row.as_ref().unwrap_err();
+        (diagnostic.column, diagnostic.reason.clone())
+      })
+      .collect();
+    assert_eq!(
+      vec![
+        (Some("reading"), String::from("not a string")),
+        (Some("pos"), String::from("more than 6 levels")),
+        (
+          Some("split"),
+          String::from("no word of the system dictionary has this surface")
+        ),
+        (
+          Some("split"),
+          String::from(
+            "ambiguous surface, 2 words of the system dictionary have it; give \
+             surface,POS,reading or a word id"
+          )
+        ),
+        (None, String::from("not an object")),
+      ],
+      errs
+    );
+  }
+
+  #[test]
+  fn test_to_katakana() {
+    assert_eq!("トウキョウタワー", to_katakana("とうきょうたわー"));
+    assert_eq!("ヽヾABC", to_katakana("ゝゞABC"));
+  }
+}
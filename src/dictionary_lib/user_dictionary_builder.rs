@@ -2,25 +2,45 @@ use std::io::{Cursor, Seek, Write};
 
 use log::info;
 
-use super::dictionary_builder::{DictionaryBuilder, DictionaryBuilderErr, WordIdToIdConverter};
+use super::dictionary_builder::{
+  parse_id, word_to_id, DictionaryBuilder, DictionaryBuilderErr, IdParser, InferredParameters,
+  SplitInfoParser, WordIdToIdConverter,
+};
 use super::double_array_lexicon::DoubleArrayLexicon;
-use super::grammar::{GetPartOfSpeech, Grammar};
-use super::lexicon::GetWordId;
+use super::grammar::Grammar;
+use super::lexicon::{GetWordId, Size};
+use super::simple_lexicon::{SimpleLexiconFormat, SimpleLexiconReader};
+use crate::plugin::default_input_text_plugin::DefaultInputTextPlugin;
+use crate::resources::get_bundled_resource;
+
+const USER_WORD_ID_FLAG: u32 = 1 << 28;
 
 pub struct UserDictionaryBuilder {
   dictionary_builder: DictionaryBuilder,
-  grammar: Grammar,
   system_lexicon: DoubleArrayLexicon,
+  input_text_plugin: Option<DefaultInputTextPlugin>,
 }
 
 impl UserDictionaryBuilder {
   pub fn new(grammar: Grammar, system_lexicon: DoubleArrayLexicon) -> UserDictionaryBuilder {
+    let system_pos_list: Vec<Vec<String>> = (0..grammar.get_part_of_speech_size())
+      .map(|pos_id| grammar.get_part_of_speech_string(pos_id).clone())
+      .collect();
     UserDictionaryBuilder {
-      dictionary_builder: DictionaryBuilder::default(),
-      grammar,
+      dictionary_builder: DictionaryBuilder::for_user_dictionary(&system_pos_list),
       system_lexicon,
+      input_text_plugin: None,
     }
   }
+  /// Make headwords of the simplified formats with `input_text_plugin` rather than the bundled
+  /// `rewrite.def`
+  pub fn set_input_text_plugin(&mut self, input_text_plugin: DefaultInputTextPlugin) {
+    self.input_text_plugin = Some(input_text_plugin);
+  }
+  /// Stop reading and checking lexicons after `max_errors` problems
+  pub fn set_max_errors(&mut self, max_errors: usize) {
+    self.dictionary_builder.set_max_errors(max_errors);
+  }
   pub fn build<W: Write + Seek>(
     &mut self,
     lexicon_paths: &[&str],
@@ -28,27 +48,77 @@ impl UserDictionaryBuilder {
   ) -> Result<(), DictionaryBuilderErr> {
     info!("reading the source file...");
     for path in lexicon_paths {
-      self.dictionary_builder.build_lexicons(path)?;
+      match SimpleLexiconFormat::from_path(path) {
+        Some(format) => {
+          if self.input_text_plugin.is_none() {
+            let rewrite_def = get_bundled_resource("rewrite.def").unwrap_or_default();
+            self.input_text_plugin = Some(DefaultInputTextPlugin::from_reader(
+              &mut rewrite_def.as_bytes(),
+            )?);
+          }
+          let rows = SimpleLexiconReader::new(
+            self.input_text_plugin.as_ref().unwrap(),
+            &self.system_lexicon,
+          )
+          .read(path, format)?;
+          self.dictionary_builder.build_rows(path, rows);
+        }
+        None => self.dictionary_builder.build_lexicons(path)?,
+      }
     }
     info!("{} words", self.dictionary_builder.entries.len());
+    if self.dictionary_builder.needs_connection_ids() {
+      info!("inferring connection ids...");
+      let connection_ids = self.system_lexicon.get_most_frequent_connection_ids();
+      self
+        .dictionary_builder
+        .infer_connection_ids(|pos_id| connection_ids.get(&pos_id).copied());
+    }
+    self.dictionary_builder.check_lexicon(self)?;
 
     self
       .dictionary_builder
       .write_grammar::<Cursor<&[u8]>, W>(None, output_stream)?;
-    self.dictionary_builder.write_lexicon(output_stream)?;
+    self
+      .dictionary_builder
+      .write_lexicon_with(output_stream, self)?;
     Ok(())
   }
+  /// Every row whose connection ids or cost were left to the builder
+  pub fn get_inferred_parameters(&self) -> Vec<InferredParameters> {
+    self.dictionary_builder.get_inferred_parameters()
+  }
+}
+
+impl IdParser for UserDictionaryBuilder {
+  fn is_user_dictionary(&self) -> bool {
+    true
+  }
+  fn check_word_id(&self, word_id: u32) -> Result<(), DictionaryBuilderErr> {
+    if word_id & USER_WORD_ID_FLAG != 0 {
+      self
+        .dictionary_builder
+        .check_word_id(word_id & !USER_WORD_ID_FLAG)
+    } else if word_id as usize >= self.system_lexicon.size() {
+      Err(DictionaryBuilderErr::InvalidWordIdErr)
+    } else {
+      Ok(())
+    }
+  }
+}
+
+impl SplitInfoParser for UserDictionaryBuilder {
+  fn parse_id(&self, text: &str) -> Result<u32, DictionaryBuilderErr> {
+    parse_id(self, text)
+  }
+  fn word_to_id(&self, text: &str) -> Result<u32, DictionaryBuilderErr> {
+    word_to_id(self, text)
+  }
 }
 
 impl WordIdToIdConverter for UserDictionaryBuilder {
   fn get_pos_id(&self, strs: &[&str]) -> Result<u16, DictionaryBuilderErr> {
-    let pos_id = self.grammar.get_part_of_speech_id(strs);
-    match pos_id {
-      Some(i) => Ok(i as u16),
-      None => Ok(
-        self.dictionary_builder.get_pos_id(strs)? + self.grammar.get_part_of_speech_size() as u16,
-      ),
-    }
+    self.dictionary_builder.get_pos_id(strs)
   }
   fn get_word_id(
     &self,
@@ -60,12 +130,66 @@ impl WordIdToIdConverter for UserDictionaryBuilder {
       .dictionary_builder
       .get_word_id(headword, pos_id, reading_form)
     {
-      Ok(wid) => Ok(wid | 1 << 28),
-      Err(_) => Ok(
-        self
-          .system_lexicon
-          .get_word_id(headword, pos_id, reading_form)? as u32,
-      ),
+      Ok(wid) => Ok(wid | USER_WORD_ID_FLAG),
+      Err(_) => self
+        .system_lexicon
+        .get_word_id(headword, pos_id, reading_form)
+        .map(|wid| wid as u32)
+        .map_err(|_| DictionaryBuilderErr::NotFoundWordErr),
     }
   }
 }
+
+#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+  use super::*;
+  use crate::dictionary_lib::binary_dictionary::BinaryDictionary;
+  use crate::dictionary_lib::dictionary_header::DictionaryHeader;
+  use crate::dictionary_lib::grammar::GetPartOfSpeech;
+  use crate::dictionary_lib::lexicon::Lexicon;
+  use crate::dictionary_lib::system_dictionary_version::USER_DICT_VERSION_2;
+  use std::env::temp_dir;
+  use std::fs::write;
+
+  #[test]
+  fn test_build() {
+    let system = BinaryDictionary::from_system_dictionary("src/resources/test/system.dic").unwrap();
+    let system_pos_size = system.grammar.get_part_of_speech_size();
+    let place_pos_id = system
+      .grammar
+      .get_part_of_speech_id(&["名詞", "固有名詞", "地名", "一般", "*", "*"])
+      .unwrap();
+    assert!(system
+      .grammar
+      .get_part_of_speech_id(&["感動詞", "フィラー", "*", "*", "*", "*"])
+      .is_none());
+
+    let input_path = temp_dir().join("sudachiclone_test_user_build.csv");
+    write(
+      &input_path,
+      "ぴらる,8,8,-32768,ぴらる,感動詞,フィラー,*,*,*,*,ピラル,ぴらる,*,A,*,*,*\n\
+       東京府,6,6,2816,東京府,名詞,固有名詞,地名,一般,*,*,トウキョウフ,東京府,*,B,5/U0,*,5/U0\n",
+    )
+    .unwrap();
+    let mut stream = Cursor::new(vec![]);
+    let header = DictionaryHeader::new(
+      USER_DICT_VERSION_2,
+      DictionaryHeader::get_time(),
+      String::new(),
+    );
+    stream.write_all(&header.to_bytes().unwrap()).unwrap();
+    let mut builder = UserDictionaryBuilder::new(system.grammar, system.lexicon);
+    builder
+      .build(&[input_path.to_str().unwrap()], &mut stream)
+      .unwrap();
+
+    let user = BinaryDictionary::from_user_dictionary_bytes(stream.get_ref()).unwrap();
+    // only the new part of speech is written, numbered after the system ones
+    assert_eq!(1, user.grammar.get_part_of_speech_size());
+    assert_eq!(system_pos_size as i16, user.lexicon.get_word_info(0).pos_id);
+    let word_info = user.lexicon.get_word_info(1);
+    assert_eq!(place_pos_id as i16, word_info.pos_id);
+    assert_eq!(vec![5, USER_WORD_ID_FLAG as i32], word_info.a_unit_split);
+  }
+}
@@ -1,14 +1,17 @@
-use std::io::{BufRead, Error as IOError};
+use std::io::{BufRead, Seek};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+use super::corruption::{check_remaining, CorruptionErr};
+
 pub struct WordIdTable {
   bytes: Vec<u8>,
 }
 
 impl WordIdTable {
-  pub fn from_reader<R: BufRead>(reader: &mut R) -> Result<WordIdTable, IOError> {
+  pub fn from_reader<R: BufRead + Seek>(reader: &mut R) -> Result<WordIdTable, CorruptionErr> {
     let size = reader.read_u32::<LittleEndian>()? as usize;
+    check_remaining(reader, "the word id table", size as u64)?;
     let mut bytes = vec![0u8; size];
     reader.read_exact(&mut bytes)?;
     Ok(WordIdTable { bytes })
@@ -19,12 +22,30 @@ impl WordIdTable {
     let mut result = Vec::with_capacity(len);
     for i in 0..len {
       result.push(u32::from_le_bytes([
-        self.bytes[(offset + i * 4)],
-        self.bytes[(offset + i * 4 + 1)],
-        self.bytes[(offset + i * 4 + 2)],
-        self.bytes[(offset + i * 4 + 3)],
+        self.bytes[offset + i * 4],
+        self.bytes[offset + i * 4 + 1],
+        self.bytes[offset + i * 4 + 2],
+        self.bytes[offset + i * 4 + 3],
       ]) as usize);
     }
     result
   }
+  /// Check that every entry is complete and refers to one of `word_size` words, and return
+  /// whether an entry starts at each byte
+  pub fn validate(&self, word_size: usize) -> Result<Vec<bool>, CorruptionErr> {
+    let mut starts = vec![false; self.bytes.len()];
+    let mut index = 0;
+    while index < self.bytes.len() {
+      let len = self.bytes[index] as usize;
+      if index + 1 + len * 4 > self.bytes.len() {
+        return Err(CorruptionErr::WordIdTableErr(index));
+      }
+      if let Some(word_id) = self.get(index).into_iter().find(|id| *id >= word_size) {
+        return Err(CorruptionErr::WordIdErr(word_id, word_size));
+      }
+      starts[index] = true;
+      index += 1 + len * 4;
+    }
+    Ok(starts)
+  }
 }
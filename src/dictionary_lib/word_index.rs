@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use super::word_info::WordInfo;
+
+/// Secondary indexes of a lexicon for reverse lookups
+#[derive(Default)]
+pub struct WordIndex {
+  /// the first word id of every (surface, POS, reading form)
+  words: HashMap<(String, i16, String), usize>,
+  reading_forms: HashMap<String, Vec<usize>>,
+  normalized_forms: HashMap<String, Vec<usize>>,
+  dictionary_forms: HashMap<usize, Vec<usize>>,
+}
+
+impl WordIndex {
+  pub fn new<I: Iterator<Item = WordInfo>>(word_infos: I) -> WordIndex {
+    let mut index = WordIndex::default();
+    for (word_id, word_info) in word_infos.enumerate() {
+      index
+        .reading_forms
+        .entry(word_info.reading_form.clone())
+        .or_insert_with(Vec::new)
+        .push(word_id);
+      index
+        .normalized_forms
+        .entry(word_info.normalized_form)
+        .or_insert_with(Vec::new)
+        .push(word_id);
+      let dictionary_form_word_id = if word_info.dictionary_form_word_id < 0 {
+        word_id
+      } else {
+        word_info.dictionary_form_word_id as usize
+      };
+      index
+        .dictionary_forms
+        .entry(dictionary_form_word_id)
+        .or_insert_with(Vec::new)
+        .push(word_id);
+      index
+        .words
+        .entry((word_info.surface, word_info.pos_id, word_info.reading_form))
+        .or_insert(word_id);
+    }
+    index
+  }
+  pub fn get_word_id(&self, surface: &str, pos_id: i16, reading_form: &str) -> Option<usize> {
+    self
+      .words
+      .get(&(surface.to_string(), pos_id, reading_form.to_string()))
+      .copied()
+  }
+  pub fn get_word_ids_by_reading_form(&self, reading_form: &str) -> &[usize] {
+    self
+      .reading_forms
+      .get(reading_form)
+      .map_or(&[], |ids| ids.as_slice())
+  }
+  pub fn get_word_ids_by_normalized_form(&self, normalized_form: &str) -> &[usize] {
+    self
+      .normalized_forms
+      .get(normalized_form)
+      .map_or(&[], |ids| ids.as_slice())
+  }
+  /// Words whose dictionary form is `word_id`, including itself if it is one
+  pub fn get_word_ids_by_dictionary_form(&self, word_id: usize) -> &[usize] {
+    self
+      .dictionary_forms
+      .get(&word_id)
+      .map_or(&[], |ids| ids.as_slice())
+  }
+}
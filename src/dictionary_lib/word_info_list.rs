@@ -1,9 +1,10 @@
 use std::io::Cursor;
-use std::io::{BufRead, Error as IOError, Seek, SeekFrom};
+use std::io::{BufRead, Read, Seek, SeekFrom};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use encoding_rs::UTF_16LE;
 
+use super::corruption::CorruptionErr;
 use super::word_info::WordInfo;
 
 pub struct WordInfoList {
@@ -13,13 +14,28 @@ pub struct WordInfoList {
 }
 
 impl WordInfoList {
+  /// Read the word infos up to the position `end`, or to the end of `reader`
   pub fn from_reader<R: BufRead + Seek>(
     reader: &mut R,
     word_size: usize,
-  ) -> Result<WordInfoList, IOError> {
+    end: Option<u64>,
+  ) -> Result<WordInfoList, CorruptionErr> {
     let offset = reader.seek(SeekFrom::Current(0))? as usize;
     let mut bytes = vec![];
-    reader.read_to_end(&mut bytes)?;
+    match end {
+      Some(end) => reader
+        .by_ref()
+        .take(end.saturating_sub(offset as u64))
+        .read_to_end(&mut bytes)?,
+      None => reader.read_to_end(&mut bytes)?,
+    };
+    if bytes.len() < 4 * word_size {
+      return Err(CorruptionErr::TruncatedErr(
+        "the word info offsets",
+        4 * word_size as u64,
+        bytes.len() as u64,
+      ));
+    }
     Ok(WordInfoList {
       bytes,
       word_size,
@@ -59,8 +75,7 @@ impl WordInfoList {
 
     let dictionary_form =
       if dictionary_form_word_id >= 0 && dictionary_form_word_id != word_id as i32 {
-        let wi = self.get_word_info(dictionary_form_word_id as usize);
-        wi.surface
+        self.get_surface(dictionary_form_word_id as usize)
       } else {
         surface.clone()
       };
@@ -78,6 +93,52 @@ impl WordInfoList {
       word_structure,
     }
   }
+  fn get_surface(&self, word_id: usize) -> String {
+    let offset = self.word_id_to_offset(word_id) as usize - self.offset;
+    WordInfoList::buffer_to_string(&self.bytes, offset).0
+  }
+  /// The ids the word refers to, read as `get_word_info` does but checking every bound
+  pub fn get_word_references(&self, word_id: usize) -> Result<WordReferences, String> {
+    let offset = self.word_id_to_offset(word_id) as usize;
+    if offset < self.offset + 4 * self.word_size || offset >= self.offset + self.bytes.len() {
+      return Err(format!(
+        "its info at byte {} is outside the lexicon",
+        offset
+      ));
+    }
+    let mut parser = Parser {
+      bytes: &self.bytes,
+      offset: offset - self.offset,
+    };
+    let cut_off = || String::from("its info is cut off");
+    parser.skip_string().ok_or_else(cut_off)?;
+    parser.string_length().ok_or_else(cut_off)?;
+    let pos_id = parser.take(2).ok_or_else(cut_off)?;
+    let pos_id = u16::from_le_bytes([pos_id[0], pos_id[1]]) as i16;
+    parser.skip_string().ok_or_else(cut_off)?;
+    let dictionary_form_word_id = parser.int().ok_or_else(cut_off)?;
+    parser.skip_string().ok_or_else(cut_off)?;
+    let a_unit_split = parser.int_array().ok_or_else(cut_off)?;
+    let b_unit_split = parser.int_array().ok_or_else(cut_off)?;
+    let word_structure = parser.int_array().ok_or_else(cut_off)?;
+    Ok(WordReferences {
+      pos_id,
+      dictionary_form_word_id,
+      a_unit_split,
+      b_unit_split,
+      word_structure,
+    })
+  }
+  /// The POS id alone, without decoding the strings of the word
+  pub fn get_pos_id(&self, word_id: usize) -> i16 {
+    let offset = self.word_id_to_offset(word_id) as usize - self.offset;
+    let (surface_length, offset) = WordInfoList::buffer_to_string_length(&self.bytes, offset);
+    let (_, offset) =
+      WordInfoList::buffer_to_string_length(&self.bytes, offset + surface_length * 2);
+    Cursor::new(&self.bytes[offset..(offset + 2)])
+      .read_u16::<LittleEndian>()
+      .unwrap() as i16
+  }
   fn word_id_to_offset(&self, word_id: usize) -> u32 {
     let i = 4 * word_id;
     let mut cursor = Cursor::new(&self.bytes[i..i + 4]);
@@ -112,3 +173,46 @@ impl WordInfoList {
     self.word_size
   }
 }
+
+/// The ids a word refers to
+pub struct WordReferences {
+  pub pos_id: i16,
+  pub dictionary_form_word_id: i32,
+  pub a_unit_split: Vec<i32>,
+  pub b_unit_split: Vec<i32>,
+  pub word_structure: Vec<i32>,
+}
+
+/// Reads the layout of `WordInfoList` without trusting it
+struct Parser<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn take(&mut self, size: usize) -> Option<&'a [u8]> {
+    let bytes = self.bytes.get(self.offset..self.offset + size)?;
+    self.offset += size;
+    Some(bytes)
+  }
+  fn string_length(&mut self) -> Option<usize> {
+    let len = self.take(1)?[0] as usize;
+    if len < 128 {
+      return Some(len);
+    }
+    let low = self.take(1)?[0] as usize;
+    Some(((len & 0x7F) << 8) | low)
+  }
+  fn skip_string(&mut self) -> Option<()> {
+    let len = self.string_length()?;
+    self.take(len * 2).map(|_| ())
+  }
+  fn int(&mut self) -> Option<i32> {
+    let bytes = self.take(4)?;
+    Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+  fn int_array(&mut self) -> Option<Vec<i32>> {
+    let len = self.take(1)?[0] as usize;
+    (0..len).map(|_| self.int()).collect()
+  }
+}
@@ -1,7 +1,9 @@
-use std::io::{BufRead, Error as IOError};
+use std::io::{BufRead, Seek};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+use super::corruption::{check_remaining, CorruptionErr};
+
 pub struct WordParameterList {
   size: usize,
   array_view: Vec<i16>,
@@ -11,8 +13,11 @@ pub struct WordParameterList {
 const ELEMENT_SIZE_AS_SHORT: usize = 3;
 
 impl WordParameterList {
-  pub fn from_reader<R: BufRead>(reader: &mut R) -> Result<WordParameterList, IOError> {
+  pub fn from_reader<R: BufRead + Seek>(
+    reader: &mut R,
+  ) -> Result<WordParameterList, CorruptionErr> {
     let size = reader.read_u32::<LittleEndian>()? as usize;
+    check_remaining(reader, "the word parameters", 6 * size as u64)?;
     let mut array_view = vec![0i16; ELEMENT_SIZE_AS_SHORT * size];
     reader.read_i16_into::<LittleEndian>(&mut array_view)?;
     Ok(WordParameterList { size, array_view })
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use super::corpus::{sentence_text, CorpusToken};
+use super::dictionary::Dictionary;
+use super::dictionary_lib::grammar::POS_DEPTH;
+use super::tokenizer::{CanTokenize, SplitMode, Tokenizer};
+
+/// A token of a sentence, with where it ends in characters
+struct SpanToken {
+  end: usize,
+  surface: String,
+  pos: Vec<String>,
+}
+
+/// Tokens of the corpus and of the tokenizer that differ over the same span, and how often
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvaluationError {
+  pub gold: String,
+  pub system: String,
+  pub count: usize,
+}
+
+impl EvaluationError {
+  fn to_json(&self) -> Value {
+    json!({
+      "gold": self.gold,
+      "system": self.system,
+      "count": self.count,
+    })
+  }
+}
+
+impl fmt::Display for EvaluationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}\t{} -> {}", self.count, self.gold, self.system)
+  }
+}
+
+/// A numerator over a denominator, reported as a ratio
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Ratio {
+  pub count: usize,
+  pub total: usize,
+}
+
+impl Ratio {
+  pub fn value(&self) -> f64 {
+    if self.total == 0 {
+      0.0
+    } else {
+      self.count as f64 / self.total as f64
+    }
+  }
+  fn to_json(self) -> Value {
+    json!({
+      "value": self.value(),
+      "count": self.count,
+      "total": self.total,
+    })
+  }
+}
+
+impl fmt::Display for Ratio {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:.4} ({}/{})", self.value(), self.count, self.total)
+  }
+}
+
+/// How the tokenizer did on a gold corpus
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+  pub sentences: usize,
+  /// sentences the tokenizer gave no morphemes for, left out of the scores
+  pub failures: usize,
+  /// the word boundaries inside sentences the tokenizer put where the corpus has one
+  pub precision: Ratio,
+  /// the word boundaries inside sentences of the corpus the tokenizer found
+  pub recall: Ratio,
+  /// the POS accuracy of the tokens segmented as in the corpus, at each depth of POS the
+  /// corpus has
+  pub pos_accuracy: Vec<Ratio>,
+  /// the tokens of the corpus whose surface is no headword of the dictionaries
+  pub oov: Ratio,
+  /// the most frequent errors first
+  pub errors: Vec<EvaluationError>,
+}
+
+impl Evaluation {
+  pub fn f1(&self) -> f64 {
+    let (precision, recall) = (self.precision.value(), self.recall.value());
+    if precision + recall == 0.0 {
+      0.0
+    } else {
+      2.0 * precision * recall / (precision + recall)
+    }
+  }
+  pub fn to_json(&self) -> Value {
+    json!({
+      "sentences": self.sentences,
+      "failures": self.failures,
+      "boundary": {
+        "precision": self.precision.to_json(),
+        "recall": self.recall.to_json(),
+        "f1": self.f1(),
+      },
+      "pos_accuracy": self.pos_accuracy.iter().map(|ratio| ratio.to_json()).collect::<Vec<Value>>(),
+      "oov": self.oov.to_json(),
+      "errors": self.errors.iter().map(|error| error.to_json()).collect::<Vec<Value>>(),
+    })
+  }
+}
+
+impl fmt::Display for Evaluation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(
+      f,
+      "sentences: {} ({} not tokenized)",
+      self.sentences, self.failures
+    )?;
+    writeln!(f, "boundary precision: {}", self.precision)?;
+    writeln!(f, "boundary recall: {}", self.recall)?;
+    writeln!(f, "boundary F1: {:.4}", self.f1())?;
+    for (depth, ratio) in self.pos_accuracy.iter().enumerate() {
+      writeln!(f, "POS accuracy at depth {}: {}", depth + 1, ratio)?;
+    }
+    writeln!(f, "OOV rate: {}", self.oov)?;
+    if !self.errors.is_empty() {
+      writeln!(f, "worst errors:")?;
+    }
+    for error in &self.errors {
+      writeln!(f, "{}", error)?;
+    }
+    Ok(())
+  }
+}
+
+/// Compares how a dictionary tokenizes the text of a gold corpus with its tokens.
+///
+/// Sentences are aligned on the spans of their tokens in characters. A span the corpus and the
+/// tokenizer segment differently is an error, and so is a token segmented alike whose POS
+/// differs at a level the corpus has.
+pub struct Evaluator<'a> {
+  dictionary: &'a Dictionary,
+  tokenizer: Tokenizer,
+  mode: SplitMode,
+  sentences: usize,
+  failures: usize,
+  gold_boundaries: usize,
+  system_boundaries: usize,
+  correct_boundaries: usize,
+  pos_accuracy: [Ratio; POS_DEPTH],
+  oov: Ratio,
+  errors: HashMap<(String, String), usize>,
+}
+
+impl<'a> Evaluator<'a> {
+  pub fn new(dictionary: &'a Dictionary, mode: SplitMode) -> Evaluator<'a> {
+    Evaluator {
+      dictionary,
+      tokenizer: dictionary.create(),
+      mode,
+      sentences: 0,
+      failures: 0,
+      gold_boundaries: 0,
+      system_boundaries: 0,
+      correct_boundaries: 0,
+      pos_accuracy: [Ratio::default(); POS_DEPTH],
+      oov: Ratio::default(),
+      errors: HashMap::new(),
+    }
+  }
+  /// Tokenize the text of a sentence of the corpus and score it
+  pub fn add_sentence(&mut self, tokens: &[CorpusToken]) {
+    if tokens.is_empty() {
+      return;
+    }
+    self.sentences += 1;
+    let morphemes = match self
+      .tokenizer
+      .tokenize(sentence_text(tokens), Some(self.mode), None)
+    {
+      Some(morphemes) if !morphemes.is_empty() => morphemes,
+      _ => {
+        self.failures += 1;
+        return;
+      }
+    };
+
+    let mut gold = vec![];
+    let mut end = 0;
+    for token in tokens {
+      end += token.surface.chars().count();
+      gold.push(SpanToken {
+        end,
+        surface: token.surface.clone(),
+        pos: token.pos.clone(),
+      });
+    }
+    let system: Vec<SpanToken> = morphemes
+      .iter()
+      .enumerate()
+      .map(|(i, morpheme)| SpanToken {
+        end: morphemes.get_end(i),
+        surface: morpheme.surface(),
+        pos: morpheme.part_of_speech(),
+      })
+      .collect();
+
+    for token in &gold {
+      self.oov.total += 1;
+      if self.is_oov(&token.surface) {
+        self.oov.count += 1;
+      }
+    }
+    self.score_boundaries(&gold, &system);
+    self.score_regions(&gold, &system);
+  }
+  /// The scores of the sentences so far, with up to `max_errors` of the worst errors
+  pub fn evaluation(&self, max_errors: usize) -> Evaluation {
+    let mut errors: Vec<EvaluationError> = self
+      .errors
+      .iter()
+      .map(|((gold, system), count)| EvaluationError {
+        gold: gold.clone(),
+        system: system.clone(),
+        count: *count,
+      })
+      .collect();
+    errors.sort_by(|a, b| {
+      b.count
+        .cmp(&a.count)
+        .then_with(|| a.gold.cmp(&b.gold))
+        .then_with(|| a.system.cmp(&b.system))
+    });
+    errors.truncate(max_errors);
+    let depth = self
+      .pos_accuracy
+      .iter()
+      .rposition(|ratio| ratio.total > 0)
+      .map_or(0, |depth| depth + 1);
+    Evaluation {
+      sentences: self.sentences,
+      failures: self.failures,
+      precision: Ratio {
+        count: self.correct_boundaries,
+        total: self.system_boundaries,
+      },
+      recall: Ratio {
+        count: self.correct_boundaries,
+        total: self.gold_boundaries,
+      },
+      pos_accuracy: self.pos_accuracy[..depth].to_vec(),
+      oov: self.oov,
+      errors,
+    }
+  }
+  fn is_oov(&self, surface: &str) -> bool {
+    if !self.dictionary.exact_lookup(surface).is_empty() {
+      return false;
+    }
+    // the lexicons are looked up by the text the input text plugins normalized
+    match self.tokenizer.build_input(surface) {
+      Some(input) => self.dictionary.exact_lookup(input.get_text()).is_empty(),
+      None => true,
+    }
+  }
+  fn score_boundaries(&mut self, gold: &[SpanToken], system: &[SpanToken]) {
+    let gold_ends: Vec<usize> = gold[..gold.len() - 1].iter().map(|t| t.end).collect();
+    let system_ends: Vec<usize> = system[..system.len() - 1].iter().map(|t| t.end).collect();
+    self.gold_boundaries += gold_ends.len();
+    self.system_boundaries += system_ends.len();
+    self.correct_boundaries += system_ends
+      .iter()
+      .filter(|end| gold_ends.binary_search(end).is_ok())
+      .count();
+  }
+  /// Score the POS and count the errors of the shortest spans both segmentations agree on
+  fn score_regions(&mut self, gold: &[SpanToken], system: &[SpanToken]) {
+    let (mut i, mut j) = (0, 0);
+    while i < gold.len() && j < system.len() {
+      let (gold_start, system_start) = (i, j);
+      while gold[i].end != system[j].end {
+        if gold[i].end < system[j].end {
+          i += 1;
+        } else {
+          j += 1;
+        }
+        if i == gold.len() || j == system.len() {
+          return;
+        }
+      }
+      i += 1;
+      j += 1;
+      let (gold, system) = (&gold[gold_start..i], &system[system_start..j]);
+      if gold.len() == 1 && system.len() == 1 {
+        self.score_pos(&gold[0], &system[0]);
+      } else {
+        *self
+          .errors
+          .entry((join_surfaces(gold), join_surfaces(system)))
+          .or_insert(0) += 1;
+      }
+    }
+  }
+  fn score_pos(&mut self, gold: &SpanToken, system: &SpanToken) {
+    for (depth, ratio) in self.pos_accuracy.iter_mut().enumerate() {
+      if depth >= gold.pos.len() {
+        break;
+      }
+      ratio.total += 1;
+      if system.pos.get(..=depth) == Some(&gold.pos[..=depth]) {
+        ratio.count += 1;
+      }
+    }
+    let depth = gold.pos.len().min(system.pos.len());
+    if gold.pos[..depth] != system.pos[..depth] {
+      *self
+        .errors
+        .entry((
+          format!("{}/{}", gold.surface, gold.pos.join(",")),
+          format!("{}/{}", system.surface, system.pos[..depth].join(",")),
+        ))
+        .or_insert(0) += 1;
+    }
+  }
+}
+
+fn join_surfaces(tokens: &[SpanToken]) -> String {
+  tokens
+    .iter()
+    .map(|token| token.surface.as_str())
+    .collect::<Vec<&str>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::corpus::read_corpus;
+  use std::path::PathBuf;
+  use std::str::FromStr;
+
+  fn build_dictionary() -> Dictionary {
+    let resource_dir = PathBuf::from_str(file!())
+      .unwrap()
+      .parent()
+      .unwrap()
+      .join("resources/test");
+    let config_path = resource_dir.join("sudachi.json");
+    Dictionary::setup(
+      Some(config_path.to_str().unwrap()),
+      Some(resource_dir.to_str().unwrap()),
+      None,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn test_evaluate() {
+    let dictionary = build_dictionary();
+    let mut evaluator = Evaluator::new(&dictionary, SplitMode::C);
+    let corpus = "\
+東京\t名詞,固有名詞,地名,一般,*,*
+都\t名詞,普通名詞,一般,*,*,*
+に\t助詞,接続助詞
+行っ\t動詞,非自立可能,*,*,五段-カ行,連用形-促音便
+た\t助動詞
+EOS
+";
+    for sentence in read_corpus(corpus.as_bytes()).unwrap() {
+      evaluator.add_sentence(&sentence);
+    }
+    let evaluation = evaluator.evaluation(10);
+    assert_eq!(1, evaluation.sentences);
+    assert_eq!(0, evaluation.failures);
+    // 東京都/に/行っ/た
+    assert_eq!(Ratio { count: 3, total: 3 }, evaluation.precision);
+    assert_eq!(Ratio { count: 3, total: 4 }, evaluation.recall);
+    assert!((evaluation.f1() - 6.0 / 7.0).abs() < 1e-9);
+    assert_eq!(POS_DEPTH, evaluation.pos_accuracy.len());
+    assert_eq!(Ratio { count: 3, total: 3 }, evaluation.pos_accuracy[0]);
+    assert_eq!(Ratio { count: 1, total: 2 }, evaluation.pos_accuracy[1]);
+    assert_eq!(Ratio { count: 1, total: 1 }, evaluation.pos_accuracy[5]);
+    assert_eq!(Ratio { count: 0, total: 5 }, evaluation.oov);
+    assert_eq!(2, evaluation.errors.len());
+    assert!(evaluation.errors.contains(&EvaluationError {
+      gold: String::from("東京 都"),
+      system: String::from("東京都"),
+      count: 1,
+    }));
+    assert!(evaluation.errors.contains(&EvaluationError {
+      gold: String::from("に/助詞,接続助詞"),
+      system: String::from("に/助詞,格助詞"),
+      count: 1,
+    }));
+    assert_eq!(1, evaluator.evaluation(1).errors.len());
+    assert_eq!(
+      1,
+      evaluation.to_json()["errors"].as_array().unwrap()[0]["count"]
+    );
+  }
+
+  #[test]
+  fn test_oov() {
+    let dictionary = build_dictionary();
+    let mut evaluator = Evaluator::new(&dictionary, SplitMode::C);
+    for sentence in read_corpus("京都\nへ\n".as_bytes()).unwrap() {
+      evaluator.add_sentence(&sentence);
+    }
+    assert_eq!(Ratio { count: 1, total: 2 }, evaluator.evaluation(0).oov);
+    assert!(evaluator.evaluation(0).pos_accuracy.is_empty());
+  }
+}
@@ -110,6 +110,10 @@ impl Lattice {
     result.reverse();
     result
   }
+  /// Every node but BOS and EOS, in the order of their end
+  pub fn get_nodes(&self) -> Vec<Arc<Mutex<LatticeNode>>> {
+    self.end_lists[1..=self.size].concat()
+  }
   pub fn connect_eos_node(&mut self) {
     self.connect_node(self.eos_node.as_ref().unwrap().lock().unwrap());
   }
@@ -56,9 +56,13 @@
 #![crate_type = "rlib"]
 
 pub mod config;
+pub mod corpus;
+pub mod cost_trainer;
+pub mod cost_tuner;
 pub mod darts;
 pub mod dictionary;
 pub mod dictionary_lib;
+pub mod evaluation;
 pub mod lattice;
 pub mod lattice_node;
 pub mod morpheme;
@@ -1,6 +1,6 @@
 use std::error::Error;
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Write};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
@@ -8,15 +8,34 @@ use std::str::FromStr;
 use clap::{crate_name, crate_version, App, Arg, ArgMatches, SubCommand};
 use log::info;
 
-use sudachiclone::config::{create_default_link_for_sudachidict_core, Config};
+use sudachiclone::config::dictionary_store::DictionaryStore;
+use sudachiclone::config::{
+  create_default_link_for_sudachidict, write_default_resources, Config, ConfigBuilder,
+};
+use sudachiclone::corpus::{read_corpus, sentence_text, CorpusFormat};
+use sudachiclone::cost_trainer::CostTrainer;
+use sudachiclone::cost_tuner::{read_examples, CostTuner};
 use sudachiclone::dictionary::Dictionary;
 use sudachiclone::dictionary_lib::binary_dictionary::BinaryDictionary;
-use sudachiclone::dictionary_lib::dictionary_builder::DictionaryBuilder;
+use sudachiclone::dictionary_lib::dictionary_builder::{DictionaryBuilder, DictionaryBuilderErr};
+use sudachiclone::dictionary_lib::dictionary_diff::DictionaryDiff;
 use sudachiclone::dictionary_lib::dictionary_header::DictionaryHeader;
+use sudachiclone::dictionary_lib::dictionary_merger::DictionaryMerger;
+use sudachiclone::dictionary_lib::dictionary_printer::{DictionaryPrinter, SplitFormat};
+use sudachiclone::dictionary_lib::dictionary_stats::DictionaryStats;
+use sudachiclone::dictionary_lib::dictionary_trailer::{
+  DictionaryTrailer, SourceFile, SystemDictionaryIdentity,
+};
+use sudachiclone::dictionary_lib::lexicon::Size;
+use sudachiclone::dictionary_lib::mecab_lexicon::{
+  write_rows, MecabColumns, MecabLexiconReader, PosMapping,
+};
 use sudachiclone::dictionary_lib::system_dictionary_version::{
   SYSTEM_DICT_VERSION, USER_DICT_VERSION_2,
 };
 use sudachiclone::dictionary_lib::user_dictionary_builder::UserDictionaryBuilder;
+use sudachiclone::evaluation::Evaluator;
+use sudachiclone::plugin::default_input_text_plugin::DefaultInputTextPlugin;
 use sudachiclone::tokenizer::{CanTokenize, SplitMode, Tokenizer};
 
 // Subcommand names
@@ -24,22 +43,63 @@ const TOKENIZE_SUB_CMD: &str = "tokenize";
 const LINK_SUB_CMD: &str = "link";
 const BUILD_SUB_CMD: &str = "build";
 const UBUILD_SUB_CMD: &str = "ubuild";
+const MERGE_SUB_CMD: &str = "merge";
+const UPGRADE_SUB_CMD: &str = "upgrade";
+const CONVERT_SUB_CMD: &str = "convert";
+const TRAIN_SUB_CMD: &str = "train";
+const EVAL_SUB_CMD: &str = "eval";
+const TUNE_SUB_CMD: &str = "tune";
+const RESOURCES_SUB_CMD: &str = "resources";
+const DUMP_SUB_CMD: &str = "dump";
+const DIFF_SUB_CMD: &str = "diff";
+const INFO_SUB_CMD: &str = "info";
+const DICT_SUB_CMD: &str = "dict";
+const DICT_INSTALL_SUB_CMD: &str = "install";
+const DICT_LIST_SUB_CMD: &str = "list";
+const DICT_USE_SUB_CMD: &str = "use";
+const DICT_REMOVE_SUB_CMD: &str = "remove";
+const CONFIG_SUB_CMD: &str = "config";
+const CONFIG_CHECK_SUB_CMD: &str = "check";
 
 // Argument names
+const COLUMNS_ARG: &str = "columns";
+const CONNECTION_IDS_ARG: &str = "connection_ids";
+const CREATE_TIME_ARG: &str = "create_time";
 const DESCRIPTION_ARG: &str = "description";
+const DICT_NAME_ARG: &str = "dict_name";
 const DICT_TYPE_ARG: &str = "dict_type";
+const ENCODING_ARG: &str = "encoding";
+const EPOCHS_ARG: &str = "epochs";
+const FORCE_ARG: &str = "force";
 const FPATH_OUT_ARG: &str = "fpath_out";
 const FPATH_SETTING_ARG: &str = "fpath_setting";
+const IN_FILE_ARG: &str = "in_file";
 const IN_FILES_ARG: &str = "in_files";
+const JSON_ARG: &str = "json";
+const LEARNING_RATE_ARG: &str = "learning_rate";
 const LOG_TIMESTAMP_ARG: &str = "timestamp";
 const MATRIX_FILE_ARG: &str = "matrix_file";
+const MAX_ERRORS_ARG: &str = "max_errors";
 const MODE_ARG: &str = "mode";
+const NEW_FILE_ARG: &str = "new_file";
+const NEW_SYSTEM_DIC_ARG: &str = "new_system_dic";
+const OLD_FILE_ARG: &str = "old_file";
+const OLD_SYSTEM_DIC_ARG: &str = "old_system_dic";
+const OUT_DIR_ARG: &str = "out_dir";
 const OUT_FILE_ARG: &str = "out_file";
+const POS_ARG: &str = "pos";
+const POS_MAP_ARG: &str = "pos_map";
 const PYTHON_BIN_ARG: &str = "python_exe";
 const QUIET_ARG: &str = "quiet";
+const SEARCH_IDS_ARG: &str = "search_ids";
+const SURFACE_ARG: &str = "surface";
 const PRINT_ALL_ARG: &str = "print_all";
+const STORE_DIR_ARG: &str = "store_dir";
 const SYSTEM_DIC_ARG: &str = "system_dic";
+const THRESHOLD_ARG: &str = "threshold";
 const VERBOSE_ARG: &str = "verbose";
+const WORD_SPLIT_ARG: &str = "word_split";
+const WORST_ERRORS_ARG: &str = "worst_errors";
 
 fn unwrap<T, E: Error>(t: Result<T, E>) -> T {
   match t {
@@ -83,12 +143,12 @@ fn tokenize(args: &ArgMatches) {
     _ => None,
   };
 
-  let fpath_setting = args.value_of(FPATH_SETTING_ARG);
   let python_exe = args.value_of_os(PYTHON_BIN_ARG);
   let print_all = args.is_present(PRINT_ALL_ARG);
   let fpath_out = args.value_of(FPATH_OUT_ARG);
 
-  let dictionary = unwrap(Dictionary::setup(fpath_setting, None, python_exe));
+  let config = unwrap(config_builder(args).build());
+  let dictionary = unwrap(Dictionary::from_config(config, python_exe));
   let tokenizer = dictionary.create();
 
   let stdin = std::io::stdin();
@@ -117,26 +177,61 @@ fn tokenize(args: &ArgMatches) {
 
 fn link(args: &ArgMatches) {
   let python_exe = args.value_of_os(PYTHON_BIN_ARG);
-  unwrap(create_default_link_for_sudachidict_core(python_exe));
+  let dict_type = args.value_of(DICT_TYPE_ARG).unwrap();
+  unwrap(create_default_link_for_sudachidict(dict_type, python_exe));
+}
+
+/// `--timestamp`, or else `SOURCE_DATE_EPOCH` or the current time
+fn create_time(args: &ArgMatches) -> u64 {
+  match args.value_of(CREATE_TIME_ARG) {
+    Some(timestamp) => unwrap(DictionaryHeader::parse_timestamp(timestamp)),
+    None => unwrap(DictionaryHeader::get_build_time()),
+  }
+}
+
+/// Create a file to write a dictionary into, readable to append its trailer afterwards
+fn create_dictionary_file(path: &str) -> File {
+  unwrap(
+    OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .open(path),
+  )
+}
+
+fn source_files(paths: &[&str]) -> Vec<SourceFile> {
+  paths
+    .iter()
+    .map(|path| unwrap(SourceFile::from_path(path)))
+    .collect()
 }
 
 fn build(args: &ArgMatches) {
   let description = args.value_of(DESCRIPTION_ARG).unwrap().to_string();
-  let header = DictionaryHeader::new(
-    SYSTEM_DICT_VERSION,
-    DictionaryHeader::get_time(),
-    description,
-  );
-  let mut writer = BufWriter::new(unwrap(File::create(args.value_of(OUT_FILE_ARG).unwrap())));
+  let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, create_time(args), description);
+  let mut writer = BufWriter::new(create_dictionary_file(args.value_of(OUT_FILE_ARG).unwrap()));
   unwrap(writer.write_all(&unwrap(header.to_bytes())));
   let mut builder = DictionaryBuilder::default();
-  let mut matrix_reader =
-    BufReader::new(unwrap(File::open(args.value_of(MATRIX_FILE_ARG).unwrap())));
+  if let Some(max_errors) = args.value_of(MAX_ERRORS_ARG) {
+    builder.set_max_errors(max_errors.parse().unwrap());
+  }
+  let matrix_path = args.value_of(MATRIX_FILE_ARG).unwrap();
+  let mut matrix_reader = BufReader::new(unwrap(File::open(matrix_path)));
   let lexicon_paths: Vec<&str> = args.values_of(IN_FILES_ARG).unwrap().collect();
   unwrap(builder.build(&lexicon_paths, Some(&mut matrix_reader), &mut writer));
+
+  let mut sources = source_files(&[matrix_path]);
+  sources.extend(source_files(&lexicon_paths));
+  unwrap(DictionaryTrailer::append(
+    &mut unwrap(writer.into_inner()),
+    sources,
+    None,
+  ));
 }
 
-fn ubuild(args: &ArgMatches) {
+fn system_dic_path(args: &ArgMatches) -> PathBuf {
   let system_dic = if let Some(system_dic) = args.value_of(SYSTEM_DIC_ARG) {
     PathBuf::from(system_dic)
   } else {
@@ -152,18 +247,486 @@ fn ubuild(args: &ArgMatches) {
     );
     exit(1);
   }
+  system_dic
+}
+
+fn ubuild(args: &ArgMatches) {
+  let system_dic = system_dic_path(args);
   let description = args.value_of(DESCRIPTION_ARG).unwrap().to_string();
-  let header = DictionaryHeader::new(
-    USER_DICT_VERSION_2,
-    DictionaryHeader::get_time(),
-    description,
-  );
+  let header = DictionaryHeader::new(USER_DICT_VERSION_2, create_time(args), description);
   let dictionary = unwrap(BinaryDictionary::from_system_dictionary(system_dic));
-  let mut writer = BufWriter::new(unwrap(File::create(args.value_of(OUT_FILE_ARG).unwrap())));
+  let system_identity = SystemDictionaryIdentity::of(&dictionary);
+  let mut writer = BufWriter::new(create_dictionary_file(args.value_of(OUT_FILE_ARG).unwrap()));
   unwrap(writer.write_all(&header.to_bytes().unwrap()));
   let mut builder = UserDictionaryBuilder::new(dictionary.grammar, dictionary.lexicon);
+  if let Some(max_errors) = args.value_of(MAX_ERRORS_ARG) {
+    builder.set_max_errors(max_errors.parse().unwrap());
+  }
   let lexicon_paths: Vec<&str> = args.values_of(IN_FILES_ARG).unwrap().collect();
   unwrap(builder.build(&lexicon_paths, &mut writer));
+  for inferred in builder.get_inferred_parameters() {
+    eprintln!("{}", inferred);
+  }
+  unwrap(DictionaryTrailer::append(
+    &mut unwrap(writer.into_inner()),
+    source_files(&lexicon_paths),
+    Some(system_identity),
+  ));
+}
+
+fn merge(args: &ArgMatches) {
+  let system_dic = system_dic_path(args);
+  let description = args.value_of(DESCRIPTION_ARG).unwrap().to_string();
+  let header = DictionaryHeader::new(SYSTEM_DICT_VERSION, create_time(args), description);
+
+  // costs left to loading are computed against the system dictionary alone
+  let mut config = unwrap(config_builder(args).build());
+  config.settings.system_dict = Some(unwrap(system_dic.canonicalize()).display().to_string());
+  config.settings.user_dict.clear();
+  let dictionary = unwrap(Dictionary::from_config(
+    config,
+    args.value_of_os(PYTHON_BIN_ARG),
+  ));
+  let tokenizer = dictionary.create();
+
+  let system_dictionary = unwrap(BinaryDictionary::from_system_dictionary(&system_dic));
+  let mut merger = DictionaryMerger::new(system_dictionary);
+  let in_files: Vec<&str> = args.values_of(IN_FILES_ARG).unwrap().collect();
+  let (dic_paths, lexicon_paths): (Vec<&str>, Vec<&str>) =
+    in_files.iter().partition(|path| path.ends_with(".dic"));
+  for dic_path in dic_paths {
+    let user_dictionary = unwrap(BinaryDictionary::from_user_dictionary(dic_path));
+    unwrap(merger.add_user_dictionary(user_dictionary, &tokenizer));
+  }
+  if !lexicon_paths.is_empty() {
+    // lexicon files are built into one user dictionary first
+    let system_dictionary = unwrap(BinaryDictionary::from_system_dictionary(&system_dic));
+    let mut builder =
+      UserDictionaryBuilder::new(system_dictionary.grammar, system_dictionary.lexicon);
+    if let Some(max_errors) = args.value_of(MAX_ERRORS_ARG) {
+      builder.set_max_errors(max_errors.parse().unwrap());
+    }
+    let user_header = DictionaryHeader::new(USER_DICT_VERSION_2, 0, String::new());
+    let mut user_dic = Cursor::new(unwrap(user_header.to_bytes()));
+    user_dic.set_position(user_dic.get_ref().len() as u64);
+    unwrap(builder.build(&lexicon_paths, &mut user_dic));
+    for inferred in builder.get_inferred_parameters() {
+      eprintln!("{}", inferred);
+    }
+    let user_dictionary = unwrap(BinaryDictionary::from_user_dictionary_bytes(
+      user_dic.get_ref(),
+    ));
+    unwrap(merger.add_user_dictionary(user_dictionary, &tokenizer));
+  }
+
+  let mut writer = Cursor::new(unwrap(header.to_bytes()));
+  writer.set_position(writer.get_ref().len() as u64);
+  unwrap(merger.merge(&mut writer));
+  let mut sources = vec![unwrap(SourceFile::from_path(&system_dic))];
+  sources.extend(source_files(&in_files));
+  unwrap(DictionaryTrailer::append(&mut writer, sources, None));
+  unwrap(std::fs::write(
+    args.value_of(OUT_FILE_ARG).unwrap(),
+    writer.into_inner(),
+  ));
+}
+
+fn upgrade(args: &ArgMatches) {
+  let in_file = args.value_of(IN_FILE_ARG).unwrap();
+  let system_dictionary = unwrap(BinaryDictionary::from_system_dictionary(system_dic_path(
+    args,
+  )));
+  let system_identity = SystemDictionaryIdentity::of(&system_dictionary);
+  // the POS ids of a version 1 user dictionary are only checked against its system dictionary
+  let user_dictionary = unwrap(BinaryDictionary::from_user_dictionary(in_file));
+  unwrap(user_dictionary.validate_user_dictionary(
+    Some(&system_identity),
+    &system_dictionary.grammar,
+    system_dictionary.grammar.get_part_of_speech_size(),
+    system_dictionary.lexicon.size(),
+  ));
+
+  let mut writer = Cursor::new(vec![]);
+  unwrap(BinaryDictionary::upgrade_user_dictionary(
+    &mut BufReader::new(unwrap(File::open(in_file))),
+    &mut writer,
+  ));
+  unwrap(DictionaryTrailer::append(
+    &mut writer,
+    source_files(&[in_file]),
+    Some(system_identity),
+  ));
+  unwrap(std::fs::write(
+    args.value_of(OUT_FILE_ARG).unwrap(),
+    writer.into_inner(),
+  ));
+}
+
+fn convert(args: &ArgMatches) {
+  let config = unwrap(config_builder(args).build());
+  let input_text_plugin = unwrap(DefaultInputTextPlugin::setup(&config));
+  let columns = unwrap(MecabColumns::from_str(args.value_of(COLUMNS_ARG).unwrap()));
+  let mut reader = MecabLexiconReader::new(&input_text_plugin, columns);
+  unwrap(reader.set_encoding(args.value_of(ENCODING_ARG).unwrap()));
+  if let Some(pos_map) = args.value_of(POS_MAP_ARG) {
+    let mut pos_map_reader = BufReader::new(unwrap(File::open(pos_map)));
+    reader.set_pos_mapping(unwrap(PosMapping::from_reader(&mut pos_map_reader)));
+  }
+  let in_files: Vec<&str> = args.values_of(IN_FILES_ARG).unwrap().collect();
+  let rows: Vec<_> = unwrap(reader.read(&in_files))
+    .into_iter()
+    .flat_map(|(_, rows)| rows)
+    .map(|(_, row)| row)
+    .collect();
+
+  let mut diagnostics: Vec<_> = rows.iter().filter_map(|row| row.clone().err()).collect();
+  if !diagnostics.is_empty() {
+    if let Some(max_errors) = args.value_of(MAX_ERRORS_ARG) {
+      diagnostics.truncate(max_errors.parse().unwrap());
+    }
+    unwrap::<(), _>(Err(DictionaryBuilderErr::InvalidLexiconErr(diagnostics)));
+  }
+  let rows = rows.iter().filter_map(|row| row.as_ref().ok());
+  match args.value_of(OUT_FILE_ARG) {
+    Some(out_file) => unwrap(write_rows(
+      rows,
+      BufWriter::new(unwrap(File::create(out_file))),
+    )),
+    None => unwrap(write_rows(rows, std::io::stdout())),
+  };
+}
+
+fn train(args: &ArgMatches) {
+  let system_dic = system_dic_path(args);
+  // the costs of the system dictionary are learned on their own
+  let mut config = unwrap(config_builder(args).build());
+  config.settings.system_dict = Some(unwrap(system_dic.canonicalize()).display().to_string());
+  config.settings.user_dict.clear();
+  let dictionary = unwrap(Dictionary::from_config(
+    config,
+    args.value_of_os(PYTHON_BIN_ARG),
+  ));
+  let mut trainer = CostTrainer::new(&dictionary);
+  trainer.set_learning_rate(args.value_of(LEARNING_RATE_ARG).unwrap().parse().unwrap());
+
+  let mut left_out = 0;
+  for in_file in args.values_of(IN_FILES_ARG).unwrap() {
+    for sentence in unwrap(read_corpus(BufReader::new(unwrap(File::open(in_file))))) {
+      if !trainer.add_sentence(&sentence) {
+        info!(
+          "no path of the lattice matches: {}",
+          sentence_text(&sentence)
+        );
+        left_out += 1;
+      }
+    }
+  }
+  if left_out > 0 {
+    eprintln!(
+      "{} sentences left out, as no path of their lattice matches the corpus (see -v)",
+      left_out
+    );
+  }
+  let epochs: usize = args.value_of(EPOCHS_ARG).unwrap().parse().unwrap();
+  for epoch in 1..=epochs {
+    let result = trainer.train_epoch();
+    eprintln!("epoch {}: {}", epoch, result);
+    if result.errors == 0 {
+      break;
+    }
+  }
+
+  let mut system_dictionary = unwrap(BinaryDictionary::from_system_dictionary(&system_dic));
+  let (words, connections) = trainer.apply(&mut system_dictionary);
+  eprintln!(
+    "{} word costs and {} connection costs changed",
+    words, connections
+  );
+  let printer = unwrap(DictionaryPrinter::new(
+    &system_dictionary,
+    None,
+    SplitFormat::WordId,
+  ));
+  let mut writer = BufWriter::new(unwrap(File::create(
+    args.value_of(MATRIX_FILE_ARG).unwrap(),
+  )));
+  unwrap(printer.print_matrix(&mut writer));
+  unwrap(printer.print_lexicon(BufWriter::new(unwrap(File::create(
+    args.value_of(OUT_FILE_ARG).unwrap(),
+  )))));
+}
+
+fn eval(args: &ArgMatches) {
+  let mode = match args.value_of(MODE_ARG) {
+    Some("A") => SplitMode::A,
+    Some("B") => SplitMode::B,
+    _ => SplitMode::C,
+  };
+  let mut config = unwrap(config_builder(args).build());
+  if let Some(system_dic) = args.value_of(SYSTEM_DIC_ARG) {
+    config.settings.system_dict = Some(
+      unwrap(Path::new(system_dic).canonicalize())
+        .display()
+        .to_string(),
+    );
+  }
+  let dictionary = unwrap(Dictionary::from_config(
+    config,
+    args.value_of_os(PYTHON_BIN_ARG),
+  ));
+  let mut evaluator = Evaluator::new(&dictionary, mode);
+  for in_file in args.values_of(IN_FILES_ARG).unwrap() {
+    let format = CorpusFormat::from_path(in_file);
+    for sentence in unwrap(format.read(BufReader::new(unwrap(File::open(in_file))))) {
+      evaluator.add_sentence(&sentence);
+    }
+  }
+
+  let evaluation = evaluator.evaluation(args.value_of(WORST_ERRORS_ARG).unwrap().parse().unwrap());
+  let output = if args.is_present(JSON_ARG) {
+    format!("{}\n", evaluation.to_json())
+  } else {
+    evaluation.to_string()
+  };
+  match args.value_of(OUT_FILE_ARG) {
+    Some(out_file) => unwrap(std::fs::write(out_file, output)),
+    None => print!("{}", output),
+  }
+}
+
+fn tune(args: &ArgMatches) {
+  let mut config = unwrap(config_builder(args).build());
+  if let Some(system_dic) = args.value_of(SYSTEM_DIC_ARG) {
+    config.settings.system_dict = Some(
+      unwrap(Path::new(system_dic).canonicalize())
+        .display()
+        .to_string(),
+    );
+  }
+  let dictionary = unwrap(Dictionary::from_config(
+    config,
+    args.value_of_os(PYTHON_BIN_ARG),
+  ));
+  let surface = args.value_of(SURFACE_ARG).unwrap();
+  let pos: Vec<&str> = args.value_of(POS_ARG).unwrap().split(',').collect();
+  let mut tuner = unwrap(CostTuner::new(&dictionary, surface, &pos));
+
+  let mut left_out = 0;
+  for in_file in args.values_of(IN_FILES_ARG).unwrap() {
+    let examples = unwrap(read_examples(
+      BufReader::new(unwrap(File::open(in_file))),
+      surface,
+    ));
+    for example in examples {
+      if !tuner.add_example(&example) {
+        left_out += 1;
+      }
+    }
+  }
+  if left_out > 0 {
+    eprintln!(
+      "{} examples left out, as the input text plugins failed",
+      left_out
+    );
+  }
+
+  let mut connection_ids = match args.value_of(CONNECTION_IDS_ARG) {
+    Some(ids) => {
+      let ids: Vec<i16> = ids.split(',').map(|id| id.parse().unwrap()).collect();
+      vec![(ids[0], ids[1])]
+    }
+    None => vec![tuner.connection_ids()[0]],
+  };
+  if args.is_present(SEARCH_IDS_ARG) {
+    for ids in tuner.connection_ids() {
+      if !connection_ids.contains(ids) {
+        connection_ids.push(*ids);
+      }
+    }
+  }
+  print!("{}", unwrap(tuner.tune(&connection_ids)));
+}
+
+fn dump(args: &ArgMatches) {
+  let dictionary = unwrap(BinaryDictionary::from_dictionary(
+    args.value_of(IN_FILE_ARG).unwrap(),
+  ));
+  // the printer checks a user dictionary against its system dictionary
+  let system_dictionary = if dictionary.is_user_dictionary() {
+    Some(unwrap(BinaryDictionary::from_system_dictionary(
+      system_dic_path(args),
+    )))
+  } else {
+    None
+  };
+  let split_format = if args.is_present(WORD_SPLIT_ARG) {
+    SplitFormat::Word
+  } else {
+    SplitFormat::WordId
+  };
+  let printer = unwrap(DictionaryPrinter::new(
+    &dictionary,
+    system_dictionary.as_ref(),
+    split_format,
+  ));
+  if let Some(matrix_file) = args.value_of(MATRIX_FILE_ARG) {
+    let mut writer = BufWriter::new(unwrap(File::create(matrix_file)));
+    unwrap(printer.print_matrix(&mut writer));
+  }
+  match args.value_of(OUT_FILE_ARG) {
+    Some(out_file) => unwrap(printer.print_lexicon(BufWriter::new(unwrap(File::create(out_file))))),
+    None => unwrap(printer.print_lexicon(std::io::stdout())),
+  };
+}
+
+fn diff(args: &ArgMatches) {
+  let old = unwrap(BinaryDictionary::from_dictionary(
+    args.value_of(OLD_FILE_ARG).unwrap(),
+  ));
+  let new = unwrap(BinaryDictionary::from_dictionary(
+    args.value_of(NEW_FILE_ARG).unwrap(),
+  ));
+  // each side may have its own system dictionary, `-s` by default
+  let system_of = |dictionary: &BinaryDictionary, system_dic_arg: &str| {
+    if !dictionary.is_user_dictionary() {
+      return None;
+    }
+    let system_dic = match args.value_of(system_dic_arg) {
+      Some(system_dic) => PathBuf::from(system_dic),
+      None => system_dic_path(args),
+    };
+    Some(unwrap(BinaryDictionary::from_system_dictionary(system_dic)))
+  };
+  let old_system = system_of(&old, OLD_SYSTEM_DIC_ARG);
+  let new_system = system_of(&new, NEW_SYSTEM_DIC_ARG);
+  let threshold = args.value_of(THRESHOLD_ARG).unwrap().parse().unwrap();
+  // a user dictionary is checked against its system dictionary
+  let diff = unwrap(DictionaryDiff::new(
+    &old,
+    old_system.as_ref(),
+    &new,
+    new_system.as_ref(),
+    threshold,
+  ));
+  let output = if args.is_present(JSON_ARG) {
+    format!("{}\n", diff.to_json())
+  } else {
+    diff.to_string()
+  };
+  match args.value_of(OUT_FILE_ARG) {
+    Some(out_file) => unwrap(std::fs::write(out_file, output)),
+    None => print!("{}", output),
+  }
+}
+
+fn info(args: &ArgMatches) {
+  let in_file = args.value_of(IN_FILE_ARG).unwrap();
+  let dictionary = unwrap(BinaryDictionary::from_dictionary(in_file));
+  // the system dictionary only names the shared parts of speech, so it is optional here
+  let system_dictionary = if !dictionary.is_user_dictionary() {
+    None
+  } else if let Some(system_dic) = args.value_of(SYSTEM_DIC_ARG) {
+    Some(unwrap(BinaryDictionary::from_system_dictionary(system_dic)))
+  } else {
+    Config::setup(None, None)
+      .ok()
+      .and_then(|mut config| {
+        config
+          .system_dict_path(args.value_of_os(PYTHON_BIN_ARG))
+          .ok()
+      })
+      .and_then(|system_dic| BinaryDictionary::from_system_dictionary(system_dic).ok())
+  };
+  let mut stats = unwrap(DictionaryStats::new(
+    &dictionary,
+    system_dictionary.as_ref(),
+  ));
+  stats.checksum_verified = unwrap(DictionaryTrailer::verify_checksum(&mut BufReader::new(
+    unwrap(File::open(in_file)),
+  )));
+  print!("{}", stats);
+}
+
+fn resources(args: &ArgMatches) {
+  let out_dir = args.value_of(OUT_DIR_ARG).unwrap();
+  unwrap(write_default_resources(out_dir));
+  info!("wrote the bundled resources into {}", out_dir);
+}
+
+fn dict(args: &ArgMatches) {
+  let store = match args.value_of(STORE_DIR_ARG) {
+    Some(store_dir) => DictionaryStore::new(store_dir),
+    None => unwrap(DictionaryStore::open_default()),
+  };
+  match args.subcommand() {
+    (DICT_INSTALL_SUB_CMD, Some(install_matches)) => {
+      let in_file = install_matches.value_of(IN_FILE_ARG).unwrap();
+      let name = install_matches.value_of(DICT_NAME_ARG);
+      let force = install_matches.is_present(FORCE_ARG);
+      for dictionary in unwrap(store.install(in_file, name, force)) {
+        println!(
+          "installed {}{}",
+          dictionary.name,
+          if dictionary.is_current {
+            " (in use)"
+          } else {
+            ""
+          }
+        );
+      }
+    }
+    (DICT_LIST_SUB_CMD, Some(_)) => {
+      for dictionary in unwrap(store.list()) {
+        println!(
+          "{} {}\t{}\t{}\t{}",
+          if dictionary.is_current { "*" } else { " " },
+          dictionary.name,
+          dictionary.version_name(),
+          dictionary.header.create_time_string(),
+          dictionary.header.description
+        );
+      }
+    }
+    (DICT_USE_SUB_CMD, Some(use_matches)) => {
+      unwrap(store.use_dictionary(use_matches.value_of(DICT_NAME_ARG).unwrap()));
+    }
+    (DICT_REMOVE_SUB_CMD, Some(remove_matches)) => {
+      unwrap(store.remove(remove_matches.value_of(DICT_NAME_ARG).unwrap()));
+    }
+    _ => {
+      eprintln!("{}", args.usage());
+      exit(1);
+    }
+  }
+}
+
+/// Layer the setting files given with `-r` over the bundled one
+fn config_builder(args: &ArgMatches) -> ConfigBuilder {
+  let mut builder = Config::builder();
+  for fpath_setting in args.values_of(FPATH_SETTING_ARG).into_iter().flatten() {
+    builder = builder.settings_file(fpath_setting);
+  }
+  builder
+}
+
+fn config(args: &ArgMatches) {
+  match args.subcommand() {
+    (CONFIG_CHECK_SUB_CMD, Some(check_matches)) => {
+      let problems = config_builder(check_matches).check(check_matches.value_of_os(PYTHON_BIN_ARG));
+      if problems.is_empty() {
+        println!("ok");
+      } else {
+        for problem in problems.iter() {
+          eprintln!("{}", problem);
+        }
+        exit(1);
+      }
+    }
+    _ => {
+      eprintln!("{}", args.usage());
+      exit(1);
+    }
+  }
 }
 
 fn in_files_validator(in_file: String) -> Result<(), String> {
@@ -181,6 +744,8 @@ fn in_files_validator(in_file: String) -> Result<(), String> {
 trait ClapAppExt {
   fn add_python_exe_arg(self) -> Self;
   fn add_log_args(self) -> Self;
+  fn add_max_errors_arg(self) -> Self;
+  fn add_create_time_arg(self) -> Self;
 }
 
 impl<'a, 'b> ClapAppExt for clap::App<'a, 'b> {
@@ -217,6 +782,35 @@ impl<'a, 'b> ClapAppExt for clap::App<'a, 'b> {
           .possible_values(&["none", "sec", "ms", "ns"]),
       )
   }
+
+  fn add_max_errors_arg(self) -> Self {
+    self.arg(
+      Arg::with_name(MAX_ERRORS_ARG)
+        .long("max-errors")
+        .takes_value(true)
+        .help("stop after this many errors in the source files (default: report all)")
+        .validator(|max_errors| {
+          max_errors
+            .parse::<usize>()
+            .map(|_| ())
+            .map_err(|_| format!("{}: error: {} is not a number", crate_name!(), max_errors))
+        }),
+    )
+  }
+
+  fn add_create_time_arg(self) -> Self {
+    self.arg(
+      Arg::with_name(CREATE_TIME_ARG)
+        .long("timestamp")
+        .takes_value(true)
+        .help("create time in seconds since the Unix epoch (default: SOURCE_DATE_EPOCH or now)")
+        .validator(|timestamp| {
+          DictionaryHeader::parse_timestamp(&timestamp)
+            .map(|_| ())
+            .map_err(|e| format!("{}: error: {}", crate_name!(), e))
+        }),
+    )
+  }
 }
 
 fn setup_logging(matches: &clap::ArgMatches) {
@@ -247,6 +841,54 @@ fn setup_logging(matches: &clap::ArgMatches) {
   info!("setup logging to level {} (quiet={})", verbosity, quiet);
 }
 
+fn build_subcommand<'a, 'b>() -> App<'a, 'b> {
+  SubCommand::with_name(BUILD_SUB_CMD)
+    .about("Build Sudachi Dictionary")
+    .help_message("see `build -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .default_value("system.dic")
+        .help("output file (default: system.dic)"),
+    )
+    .arg(
+      Arg::with_name(DESCRIPTION_ARG)
+        .short("d")
+        .takes_value(true)
+        .default_value("")
+        .help("description comment to be embedded on dictionary"),
+    )
+    .arg(
+      Arg::with_name(MATRIX_FILE_ARG)
+        .short("m")
+        .takes_value(true)
+        .required(true)
+        .help("connection matrix file with MeCab\'s matrix.def format")
+        .validator(|matrix_file| {
+          if Path::new(&matrix_file).is_file() {
+            Ok(())
+          } else {
+            Err(format!(
+              "{}: error: {} doesn't exist",
+              crate_name!(),
+              matrix_file
+            ))
+          }
+        }),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .help("source files with CSV format (one of more)")
+        .validator(in_files_validator),
+    )
+    .add_max_errors_arg()
+    .add_create_time_arg()
+}
+
 fn main() {
   let tokenize_subcommand = SubCommand::with_name(TOKENIZE_SUB_CMD)
     .about("Tokenize Text")
@@ -255,7 +897,9 @@ fn main() {
       Arg::with_name(FPATH_SETTING_ARG)
         .short("r")
         .takes_value(true)
-        .help("the setting file in JSON format"),
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format (repeat to layer them)"),
     )
     .arg(
       Arg::with_name(MODE_ARG)
@@ -294,19 +938,21 @@ fn main() {
         .takes_value(true)
         .possible_values(&["small", "core", "full"])
         .default_value("core")
-        .help("dict dict"),
+        .help("the SudachiDict package to link"),
     )
     .add_python_exe_arg();
 
-  let build_subcommand = SubCommand::with_name(BUILD_SUB_CMD)
-    .about("Build Sudachi Dictionary")
-    .help_message("see `build -h`")
+  let build_subcommand = build_subcommand();
+
+  let ubuild_subcommand = SubCommand::with_name(UBUILD_SUB_CMD)
+    .about("Build User Dictionary")
+    .help_message("see `ubuild -h`")
     .arg(
       Arg::with_name(OUT_FILE_ARG)
         .short("o")
         .takes_value(true)
-        .default_value("system.dic")
-        .help("output file (default: system.dic)"),
+        .default_value("user.dic")
+        .help("output file (default: user.dic)"),
     )
     .arg(
       Arg::with_name(DESCRIPTION_ARG)
@@ -315,56 +961,531 @@ fn main() {
         .default_value("")
         .help("description comment to be embedded on dictionary"),
     )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary (default: linked system_dic, see link -h)"),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .help("source files with CSV format, or .tsv/.jsonl in the simplified format (one of more)")
+        .validator(in_files_validator),
+    )
+    .add_max_errors_arg()
+    .add_create_time_arg();
+
+  let merge_subcommand = SubCommand::with_name(MERGE_SUB_CMD)
+    .about("Merge User Dictionaries into a System Dictionary")
+    .help_message("see `merge -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .default_value("merged.dic")
+        .help("output file (default: merged.dic)"),
+    )
+    .arg(
+      Arg::with_name(DESCRIPTION_ARG)
+        .short("d")
+        .takes_value(true)
+        .default_value("")
+        .help("description comment to be embedded on dictionary"),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary (default: linked system_dic, see link -h)"),
+    )
+    .arg(
+      Arg::with_name(FPATH_SETTING_ARG)
+        .short("r")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format, to compute costs left to loading"),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .help("user dictionaries (.dic), merged in this order, or source files, merged after them")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg()
+    .add_max_errors_arg()
+    .add_create_time_arg();
+
+  let upgrade_subcommand = SubCommand::with_name(UPGRADE_SUB_CMD)
+    .about("Rewrite a Version 1 User Dictionary as Version 2")
+    .help_message("see `upgrade -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .default_value("user.dic")
+        .help("output file (default: user.dic)"),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary it was built against (default: linked system_dic, see link -h)"),
+    )
+    .arg(
+      Arg::with_name(IN_FILE_ARG)
+        .required(true)
+        .takes_value(true)
+        .help("version 1 user dictionary")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let convert_subcommand = SubCommand::with_name(CONVERT_SUB_CMD)
+    .about("Convert MeCab Dictionary Sources to the Lexicon Format")
+    .help_message("see `convert -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .help("output lexicon file with CSV format (default: stdout)"),
+    )
+    .arg(
+      Arg::with_name(COLUMNS_ARG)
+        .short("f")
+        .takes_value(true)
+        .default_value("ipadic")
+        .help("column layout: ipadic, unidic or name=column pairs such as reading=13,base=16")
+        .validator(|columns| {
+          MecabColumns::from_str(&columns)
+            .map(|_| ())
+            .map_err(|e| format!("{}: error: {}", crate_name!(), e))
+        }),
+    )
+    .arg(
+      Arg::with_name(ENCODING_ARG)
+        .short("e")
+        .takes_value(true)
+        .default_value("utf-8")
+        .help("encoding of the source files, such as euc-jp for IPADIC"),
+    )
+    .arg(
+      Arg::with_name(POS_MAP_ARG)
+        .long("pos-map")
+        .takes_value(true)
+        .help("MeCab POS to Sudachi POS by tab by line (default: keep MeCab POS)")
+        .validator(in_files_validator),
+    )
+    .arg(
+      Arg::with_name(FPATH_SETTING_ARG)
+        .short("r")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format, to make headwords with its rewrite.def"),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .help("MeCab source files with CSV format, numbered as one lexicon (one or more)")
+        .validator(in_files_validator),
+    )
+    .add_max_errors_arg();
+
+  let train_subcommand = SubCommand::with_name(TRAIN_SUB_CMD)
+    .about("Learn Costs of a System Dictionary from a Segmented Corpus")
+    .help_message("see `train -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .default_value("lex.csv")
+        .help("output lexicon file with CSV format (default: lex.csv)"),
+    )
     .arg(
       Arg::with_name(MATRIX_FILE_ARG)
         .short("m")
+        .takes_value(true)
+        .default_value("matrix.def")
+        .help(
+          "output connection matrix file with MeCab\'s matrix.def format (default: matrix.def)",
+        ),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary to start from (default: linked system_dic, see link -h)"),
+    )
+    .arg(
+      Arg::with_name(FPATH_SETTING_ARG)
+        .short("r")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format, for the plugins the lattice is built with"),
+    )
+    .arg(
+      Arg::with_name(EPOCHS_ARG)
+        .short("n")
+        .takes_value(true)
+        .default_value("10")
+        .help("passes over the corpus, fewer if it is segmented without errors")
+        .validator(|epochs| {
+          epochs
+            .parse::<usize>()
+            .map(|_| ())
+            .map_err(|_| format!("{}: error: {} is not a number", crate_name!(), epochs))
+        }),
+    )
+    .arg(
+      Arg::with_name(LEARNING_RATE_ARG)
+        .long("rate")
+        .takes_value(true)
+        .default_value("100")
+        .help("how much a cost moves by a mistake")
+        .validator(|rate| match rate.parse::<f64>() {
+          Ok(rate) if rate > 0.0 => Ok(()),
+          _ => Err(format!(
+            "{}: error: {} is not a positive number",
+            crate_name!(),
+            rate
+          )),
+        }),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
         .required(true)
-        .help("connection matrix file with MeCab\'s matrix.def format")
-        .validator(|matrix_file| {
-          if Path::new(&matrix_file).is_file() {
+        .help("corpus files of one token by line, surface and POS by tab, EOS between sentences")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let eval_subcommand = SubCommand::with_name(EVAL_SUB_CMD)
+    .about("Evaluate Tokenization against a Gold Corpus")
+    .help_message("see `eval -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .help("output file (default: stdout)"),
+    )
+    .arg(
+      Arg::with_name(FPATH_SETTING_ARG)
+        .short("r")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format (repeat to layer them)"),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary to evaluate (default: the one of the setting file)"),
+    )
+    .arg(
+      Arg::with_name(MODE_ARG)
+        .short("m")
+        .takes_value(true)
+        .possible_values(&["A", "B", "C"])
+        .default_value("C")
+        .help("the mode of splitting"),
+    )
+    .arg(
+      Arg::with_name(WORST_ERRORS_ARG)
+        .short("n")
+        .takes_value(true)
+        .default_value("10")
+        .help("how many of the most frequent errors to report (default: 10)")
+        .validator(|worst_errors| {
+          worst_errors
+            .parse::<usize>()
+            .map(|_| ())
+            .map_err(|_| format!("{}: error: {} is not a number", crate_name!(), worst_errors))
+        }),
+    )
+    .arg(
+      Arg::with_name(JSON_ARG)
+        .short("j")
+        .help("write the scores as JSON"),
+    )
+    .arg(
+      Arg::with_name(IN_FILES_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .required(true)
+        .help("gold corpus files, CoNLL-U if named *.conllu, else one token by line, surface and POS by tab, EOS between sentences")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let tune_subcommand = SubCommand::with_name(TUNE_SUB_CMD)
+    .about("Tune the Cost of a User Word from Example Sentences")
+    .help_message("see `tune -h`")
+    .arg(
+      Arg::with_name(FPATH_SETTING_ARG)
+        .short("r")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("the setting file in JSON format (repeat to layer them)"),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary to tune against (default: the one of the setting file)"),
+    )
+    .arg(
+      Arg::with_name(SURFACE_ARG)
+        .short("w")
+        .takes_value(true)
+        .required(true)
+        .help("surface of the word"),
+    )
+    .arg(
+      Arg::with_name(POS_ARG)
+        .long("pos")
+        .takes_value(true)
+        .required(true)
+        .help("the 6 levels of POS of the word separated by commas"),
+    )
+    .arg(
+      Arg::with_name(CONNECTION_IDS_ARG)
+        .long("ids")
+        .takes_value(true)
+        .help("left id and right id of the word separated by a comma (default: the ones most system words of the POS have)")
+        .validator(|ids| {
+          let ids: Vec<&str> = ids.split(',').collect();
+          if ids.len() == 2 && ids.iter().all(|id| id.parse::<i16>().is_ok()) {
             Ok(())
           } else {
             Err(format!(
-              "{}: error: {} doesn't exist",
+              "{}: error: {} is not a left id and a right id",
               crate_name!(),
-              matrix_file
+              ids.join(",")
             ))
           }
         }),
     )
+    .arg(
+      Arg::with_name(SEARCH_IDS_ARG)
+        .short("i")
+        .help("also try the connection ids of every system word of the POS"),
+    )
     .arg(
       Arg::with_name(IN_FILES_ARG)
         .takes_value(true)
-        .help("source files with CSV format (one of more)"),
-    );
+        .multiple(true)
+        .required(true)
+        .help("example sentences by line, with [surface] where the word should be segmented as a unit")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
 
-  let ubuild_subcommand = SubCommand::with_name(UBUILD_SUB_CMD)
-    .about("Build User Dictionary")
-    .help_message("see `ubuild -h`")
+  let dump_subcommand = SubCommand::with_name(DUMP_SUB_CMD)
+    .about("Dump Dictionary as Source Files")
+    .help_message("see `dump -h`")
     .arg(
       Arg::with_name(OUT_FILE_ARG)
         .short("o")
         .takes_value(true)
-        .default_value("user.dic")
-        .help("output file (default: user.dic)"),
+        .help("output lexicon file with CSV format (default: stdout)"),
     )
     .arg(
-      Arg::with_name(DESCRIPTION_ARG)
-        .short("d")
+      Arg::with_name(MATRIX_FILE_ARG)
+        .short("m")
         .takes_value(true)
-        .default_value("")
-        .help("description comment to be embedded on dictionary"),
+        .help("output connection matrix file with MeCab\'s matrix.def format"),
     )
     .arg(
       Arg::with_name(SYSTEM_DIC_ARG)
         .short("s")
         .takes_value(true)
-        .help("system dictionary (default: linked system_dic, see link -h)"),
+        .help("system dictionary of a user dictionary (default: linked system_dic, see link -h)"),
     )
     .arg(
-      Arg::with_name(IN_FILES_ARG)
+      Arg::with_name(WORD_SPLIT_ARG)
+        .short("w")
+        .help("write split info as (surface, POS, reading) instead of word ids"),
+    )
+    .arg(
+      Arg::with_name(IN_FILE_ARG)
+        .required(true)
         .takes_value(true)
-        .help("source files with CSV format (one of more)"),
+        .help("system or user dictionary")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let diff_subcommand = SubCommand::with_name(DIFF_SUB_CMD)
+    .about("Compare Two Dictionaries")
+    .help_message("see `diff -h`")
+    .arg(
+      Arg::with_name(OUT_FILE_ARG)
+        .short("o")
+        .takes_value(true)
+        .help("output file (default: stdout)"),
+    )
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("system dictionary of user dictionaries (default: linked system_dic, see link -h)"),
+    )
+    .arg(
+      Arg::with_name(OLD_SYSTEM_DIC_ARG)
+        .long("old-system")
+        .takes_value(true)
+        .help("system dictionary of the old user dictionary (default: -s)")
+        .validator(in_files_validator),
+    )
+    .arg(
+      Arg::with_name(NEW_SYSTEM_DIC_ARG)
+        .long("new-system")
+        .takes_value(true)
+        .help("system dictionary of the new user dictionary (default: -s)")
+        .validator(in_files_validator),
+    )
+    .arg(
+      Arg::with_name(THRESHOLD_ARG)
+        .short("t")
+        .takes_value(true)
+        .default_value("0")
+        .help("report connection costs changed by more than this (default: 0)")
+        .validator(|threshold| {
+          threshold
+            .parse::<u16>()
+            .map(|_| ())
+            .map_err(|_| format!("{}: error: {} is not a number", crate_name!(), threshold))
+        }),
+    )
+    .arg(
+      Arg::with_name(JSON_ARG)
+        .short("j")
+        .help("write the differences as JSON"),
+    )
+    .arg(
+      Arg::with_name(OLD_FILE_ARG)
+        .required(true)
+        .takes_value(true)
+        .help("old system or user dictionary")
+        .validator(in_files_validator),
+    )
+    .arg(
+      Arg::with_name(NEW_FILE_ARG)
+        .required(true)
+        .takes_value(true)
+        .help("new system or user dictionary")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let info_subcommand = SubCommand::with_name(INFO_SUB_CMD)
+    .about("Show Dictionary Header and Statistics")
+    .help_message("see `info -h`")
+    .arg(
+      Arg::with_name(SYSTEM_DIC_ARG)
+        .short("s")
+        .takes_value(true)
+        .help(
+          "system dictionary of a user dictionary to name its POS (default: linked system_dic)",
+        ),
+    )
+    .arg(
+      Arg::with_name(IN_FILE_ARG)
+        .required(true)
+        .takes_value(true)
+        .help("system or user dictionary")
+        .validator(in_files_validator),
+    )
+    .add_python_exe_arg();
+
+  let resources_subcommand = SubCommand::with_name(RESOURCES_SUB_CMD)
+    .about("Write Bundled Setting File and Resources")
+    .help_message("see `resources -h`")
+    .arg(
+      Arg::with_name(OUT_DIR_ARG)
+        .short("o")
+        .takes_value(true)
+        .default_value(".")
+        .help("output directory (existing files are kept)"),
+    );
+
+  let dict_name_arg = Arg::with_name(DICT_NAME_ARG)
+    .required(true)
+    .takes_value(true)
+    .help("name of the dictionary in the store (e.g. core, small, full)");
+  let dict_subcommand = SubCommand::with_name(DICT_SUB_CMD)
+    .about("Manage Local Dictionaries")
+    .help_message("see `dict -h`")
+    .arg(
+      Arg::with_name(STORE_DIR_ARG)
+        .short("s")
+        .takes_value(true)
+        .help("store directory (default: $XDG_DATA_HOME/sudachiclone)"),
+    )
+    .subcommand(
+      SubCommand::with_name(DICT_INSTALL_SUB_CMD)
+        .about("Install a dictionary file or a SudachiDict zip archive")
+        .arg(
+          Arg::with_name(IN_FILE_ARG)
+            .required(true)
+            .takes_value(true)
+            .help("dictionary file (.dic) or SudachiDict archive (.zip)")
+            .validator(in_files_validator),
+        )
+        .arg(
+          Arg::with_name(DICT_NAME_ARG)
+            .short("n")
+            .takes_value(true)
+            .help("name in the store (default: core for system_core.dic)"),
+        )
+        .arg(
+          Arg::with_name(FORCE_ARG)
+            .short("f")
+            .help("overwrite an installed dictionary with the same name"),
+        ),
+    )
+    .subcommand(
+      SubCommand::with_name(DICT_LIST_SUB_CMD).about("List installed dictionaries (* is in use)"),
+    )
+    .subcommand(
+      SubCommand::with_name(DICT_USE_SUB_CMD)
+        .about("Use an installed system dictionary")
+        .arg(dict_name_arg.clone()),
+    )
+    .subcommand(
+      SubCommand::with_name(DICT_REMOVE_SUB_CMD)
+        .about("Remove an installed dictionary")
+        .arg(dict_name_arg),
+    );
+
+  let config_subcommand = SubCommand::with_name(CONFIG_SUB_CMD)
+    .about("Inspect Setting File")
+    .help_message("see `config -h`")
+    .subcommand(
+      SubCommand::with_name(CONFIG_CHECK_SUB_CMD)
+        .about("Report all problems of the setting file and the files it refers to")
+        .arg(
+          Arg::with_name(FPATH_SETTING_ARG)
+            .short("r")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("the setting file in JSON format (repeat to layer them)"),
+        )
+        .add_python_exe_arg(),
     );
 
   let mut app = App::new("Japanese Morphological Analyzer")
@@ -372,6 +1493,18 @@ fn main() {
     .subcommand(link_subcommand)
     .subcommand(build_subcommand)
     .subcommand(ubuild_subcommand)
+    .subcommand(merge_subcommand)
+    .subcommand(upgrade_subcommand)
+    .subcommand(convert_subcommand)
+    .subcommand(train_subcommand)
+    .subcommand(eval_subcommand)
+    .subcommand(tune_subcommand)
+    .subcommand(dump_subcommand)
+    .subcommand(diff_subcommand)
+    .subcommand(info_subcommand)
+    .subcommand(resources_subcommand)
+    .subcommand(dict_subcommand)
+    .subcommand(config_subcommand)
     .add_log_args();
   let matches = app.clone().get_matches();
 
@@ -381,9 +1514,42 @@ fn main() {
     (LINK_SUB_CMD, Some(link_matches)) => link(link_matches),
     (BUILD_SUB_CMD, Some(build_matches)) => build(build_matches),
     (UBUILD_SUB_CMD, Some(ubuild_matches)) => ubuild(ubuild_matches),
+    (MERGE_SUB_CMD, Some(merge_matches)) => merge(merge_matches),
+    (UPGRADE_SUB_CMD, Some(upgrade_matches)) => upgrade(upgrade_matches),
+    (CONVERT_SUB_CMD, Some(convert_matches)) => convert(convert_matches),
+    (TRAIN_SUB_CMD, Some(train_matches)) => train(train_matches),
+    (EVAL_SUB_CMD, Some(eval_matches)) => eval(eval_matches),
+    (TUNE_SUB_CMD, Some(tune_matches)) => tune(tune_matches),
+    (DUMP_SUB_CMD, Some(dump_matches)) => dump(dump_matches),
+    (DIFF_SUB_CMD, Some(diff_matches)) => diff(diff_matches),
+    (INFO_SUB_CMD, Some(info_matches)) => info(info_matches),
+    (RESOURCES_SUB_CMD, Some(resources_matches)) => resources(resources_matches),
+    (DICT_SUB_CMD, Some(dict_matches)) => dict(dict_matches),
+    (CONFIG_SUB_CMD, Some(config_matches)) => config(config_matches),
     _ => {
       app.print_help().expect("Unable to write help");
       println!();
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env::temp_dir;
+
+  #[test]
+  fn test_build_matrix_file() {
+    let matrix_path = temp_dir().join("sudachiclone_test_build_matrix.def");
+    File::create(&matrix_path).unwrap();
+    let matrix_path = matrix_path.to_str().unwrap();
+    let lexicon_path = temp_dir().join("sudachiclone_test_build_lex.csv");
+    File::create(&lexicon_path).unwrap();
+    let lexicon_path = lexicon_path.to_str().unwrap();
+    let matches = build_subcommand()
+      .get_matches_from_safe(vec![BUILD_SUB_CMD, "-m", matrix_path, lexicon_path])
+      .unwrap();
+    assert_eq!(Some(matrix_path), matches.value_of(MATRIX_FILE_ARG));
+    assert_eq!(Some(lexicon_path), matches.value_of(IN_FILES_ARG));
+  }
+}
@@ -44,8 +44,10 @@ impl MorphemeList {
     self.input_text.lock().unwrap().get_original_text()[start..end].to_string()
   }
   pub fn get_internal_cost(&self) -> i16 {
-    (self.path.last().unwrap().lock().unwrap().get_path_cost()
-      - self.path[0].lock().unwrap().get_path_cost()) as i16
+    // the first and last nodes are the same one for a single morpheme
+    let last_cost = self.path.last().unwrap().lock().unwrap().get_path_cost();
+    let first_cost = self.path[0].lock().unwrap().get_path_cost();
+    (last_cost - first_cost) as i16
   }
   pub fn len(&self) -> usize {
     self.path.len()
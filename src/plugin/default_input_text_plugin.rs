@@ -111,8 +111,8 @@ pub enum DefaultInputTextPluginSetupErr {
 
 impl DefaultInputTextPlugin {
   pub fn setup(config: &Config) -> Result<DefaultInputTextPlugin, DefaultInputTextPluginSetupErr> {
-    let rewrite_def_path = config.resource_dir.clone().join("rewrite.def");
-    DefaultInputTextPlugin::from_filepath(rewrite_def_path)
+    let mut reader = config.open_resource("rewrite.def")?;
+    DefaultInputTextPlugin::from_reader(&mut reader)
   }
   pub fn from_reader<R: BufRead>(
     reader: &mut R,
@@ -156,6 +156,14 @@ impl DefaultInputTextPlugin {
       ignore_normalize_set,
     })
   }
+  /// `text` as the tokenizer sees it after this plugin, e.g. to make dictionary headwords
+  pub fn normalize(&self, text: &str) -> String {
+    let mut builder = UTF8InputTextBuilder::new(text, ());
+    match self.rewrite(&mut builder) {
+      Ok(()) => builder.get_text(),
+      Err(_) => text.to_string(),
+    }
+  }
   pub fn from_filepath<P: AsRef<Path>>(
     rewrite_def_path: P,
   ) -> Result<DefaultInputTextPlugin, DefaultInputTextPluginSetupErr> {
@@ -1,8 +1,8 @@
-use serde_json::Value;
 use thiserror::Error;
 
 use super::default_input_text_plugin::{DefaultInputTextPlugin, DefaultInputTextPluginSetupErr};
 use super::prolonged_soundmark_input_text_plugin::ProlongedSoundMarkInputTextPlugin;
+use crate::config::settings::InputTextPluginSettings;
 use crate::config::Config;
 use crate::utf8_input_text_builder::{ReplaceErr, UTF8InputTextBuilder};
 
@@ -32,10 +32,6 @@ impl RewriteInputText for InputTextPlugin {
 
 #[derive(Error, Debug)]
 pub enum InputTextPluginGetErr {
-  #[error("{0} is invalid InputTextPlugin class")]
-  InvalidClassErr(String),
-  #[error("config file is invalid format")]
-  InvalidFormatErr,
   #[error("{0}")]
   DefaultInputTextPluginSetupErr(#[from] DefaultInputTextPluginSetupErr),
 }
@@ -48,22 +44,17 @@ pub enum InputTextPluginReplaceErr {
 
 fn get_input_text_plugin(
   config: &Config,
-  json_obj: &Value,
+  settings: &InputTextPluginSettings,
 ) -> Result<InputTextPlugin, InputTextPluginGetErr> {
-  if let Some(Value::String(class)) = json_obj.get("class") {
-    if class == "sudachipy.plugin.input_text.DefaultInputTextPlugin" {
-      Ok(InputTextPlugin::DefaultInputTextPlugin(
-        DefaultInputTextPlugin::setup(config)?,
-      ))
-    } else if class == "sudachipy.plugin.input_text.ProlongedSoundMarkInputTextPlugin" {
+  match settings {
+    InputTextPluginSettings::DefaultInputTextPlugin => Ok(InputTextPlugin::DefaultInputTextPlugin(
+      DefaultInputTextPlugin::setup(config)?,
+    )),
+    InputTextPluginSettings::ProlongedSoundMarkInputTextPlugin(settings) => {
       Ok(InputTextPlugin::ProlongedSoundMarkInputTextPlugin(
-        ProlongedSoundMarkInputTextPlugin::setup(json_obj),
+        ProlongedSoundMarkInputTextPlugin::setup(settings),
       ))
-    } else {
-      Err(InputTextPluginGetErr::InvalidClassErr(class.to_string()))
     }
-  } else {
-    Err(InputTextPluginGetErr::InvalidFormatErr)
   }
 }
 
@@ -71,10 +62,8 @@ pub fn get_input_text_plugins(
   config: &Config,
 ) -> Result<Vec<InputTextPlugin>, InputTextPluginGetErr> {
   let mut plugins = vec![];
-  if let Some(Value::Array(arr)) = config.settings.get("inputTextPlugin") {
-    for v in arr {
-      plugins.push(get_input_text_plugin(config, v)?);
-    }
+  for settings in config.settings.input_text_plugin.iter() {
+    plugins.push(get_input_text_plugin(config, settings)?);
   }
   Ok(plugins)
 }
@@ -1,15 +1,14 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Error as IOError};
+use std::io::{BufRead, Error as IOError};
 use std::num::ParseIntError;
-use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use serde_json::Value;
 use thiserror::Error;
 
 use super::oov_provider_plugin::ProvideOov;
+use crate::config::settings::MecabOovSettings;
+use crate::config::Config;
 use crate::dictionary_lib::category_type::CategoryType;
 use crate::dictionary_lib::grammar::{GetPartOfSpeech, Grammar};
 use crate::dictionary_lib::word_info::WordInfo;
@@ -53,10 +52,6 @@ pub struct MecabOovPlugin {
 
 #[derive(Debug, Error)]
 pub enum MecabOovPluginSetupErr {
-  #[error("charDef is not defined")]
-  CharDefNotDefinedErr,
-  #[error("unkDef is not defined")]
-  UnkDefNotDefinedErr,
   #[error("{0}")]
   IOError(#[from] IOError),
   #[error("{0}")]
@@ -74,24 +69,19 @@ pub enum MecabOovPluginSetupErr {
 }
 
 impl MecabOovPlugin {
-  pub fn setup<P: AsRef<Path>>(
-    resource_dir: P,
-    json_obj: &Value,
+  pub fn setup(
+    config: &Config,
+    settings: &MecabOovSettings,
     grammar: Arc<Mutex<Grammar>>,
   ) -> Result<MecabOovPlugin, MecabOovPluginSetupErr> {
-    let resource_dir = resource_dir.as_ref();
-    let chardef_path = json_obj
-      .get("charDef")
-      .map(|i| i.as_str())
-      .flatten()
-      .map(|i| resource_dir.join(i));
-    let unkdef_path = json_obj
-      .get("unkDef")
-      .map(|i| i.as_str())
-      .flatten()
-      .map(|i| resource_dir.join(i));
-    let categories = MecabOovPlugin::read_character_property(chardef_path)?;
-    let oovs_list = MecabOovPlugin::read_oov(unkdef_path, &categories, grammar)?;
+    let categories = MecabOovPlugin::read_character_property_from_reader(
+      &mut config.open_resource(&settings.char_def)?,
+    )?;
+    let oovs_list = MecabOovPlugin::read_oov_from_reader(
+      &mut config.open_resource(&settings.unk_def)?,
+      &categories,
+      grammar,
+    )?;
     Ok(MecabOovPlugin {
       categories,
       oovs_list,
@@ -136,17 +126,6 @@ impl MecabOovPlugin {
     Ok(categories)
   }
 
-  fn read_character_property(
-    chardef_path: Option<PathBuf>,
-  ) -> Result<Categories, MecabOovPluginSetupErr> {
-    if let Some(chardef_path) = chardef_path {
-      let mut reader = BufReader::new(File::open(chardef_path)?);
-      MecabOovPlugin::read_character_property_from_reader(&mut reader)
-    } else {
-      Err(MecabOovPluginSetupErr::CharDefNotDefinedErr)
-    }
-  }
-
   fn read_oov_from_reader<R: BufRead>(
     reader: &mut R,
     categories: &Categories,
@@ -194,19 +173,6 @@ impl MecabOovPlugin {
     Ok(oovs_list)
   }
 
-  fn read_oov(
-    unkdef_path: Option<PathBuf>,
-    categories: &Categories,
-    grammar: Arc<Mutex<Grammar>>,
-  ) -> Result<OovsList, MecabOovPluginSetupErr> {
-    if let Some(unkdef_path) = unkdef_path {
-      let mut reader = BufReader::new(File::open(unkdef_path)?);
-      MecabOovPlugin::read_oov_from_reader(&mut reader, categories, grammar)
-    } else {
-      Err(MecabOovPluginSetupErr::UnkDefNotDefinedErr)
-    }
-  }
-
   fn get_oov_node(&self, text: &str, oov: &Oov, len: usize) -> Arc<Mutex<LatticeNode>> {
     let mut node = LatticeNode::empty(oov.left_id, oov.right_id, oov.cost);
     node.set_oov();
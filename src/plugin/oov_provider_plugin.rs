@@ -1,10 +1,10 @@
 use std::sync::{Arc, Mutex};
 
-use serde_json::Value;
 use thiserror::Error;
 
 use super::mecab_oov_plugin::{MecabOovPlugin, MecabOovPluginSetupErr};
 use super::simple_oov_plugin::{SimpleOovPlugin, SimpleOovPluginSetupErr};
+use crate::config::settings::OovProviderPluginSettings;
 use crate::config::Config;
 use crate::dictionary_lib::grammar::Grammar;
 use crate::lattice_node::LatticeNode;
@@ -59,37 +59,26 @@ pub fn get_oov<T: InputText>(
 
 #[derive(Error, Debug)]
 pub enum OovProviderPluginGetErr {
-  #[error("{0} is invalid InputTextPlugin class")]
-  InvalidClassErr(String),
-  #[error("config file is invalid format")]
-  InvalidFormatErr,
   #[error("{0}")]
   MecabOovPluginSetupErr(#[from] MecabOovPluginSetupErr),
-  #[error("{0}")]
-  SimpleOovPluginSetupErr(#[from] SimpleOovPluginSetupErr),
+  #[error("`oovProviderPlugin[{0}].oovPOS` {1}")]
+  SimpleOovPluginSetupErr(usize, SimpleOovPluginSetupErr),
 }
 
 fn get_oov_provider_plugin(
   config: &Config,
-  json_obj: &Value,
+  index: usize,
+  settings: &OovProviderPluginSettings,
   grammar: Arc<Mutex<Grammar>>,
 ) -> Result<OovProviderPlugin, OovProviderPluginGetErr> {
-  if let Some(Value::String(class)) = json_obj.get("class") {
-    if class == "sudachipy.plugin.oov.SimpleOovProviderPlugin" {
-      Ok(OovProviderPlugin::SimpleOovPlugin(SimpleOovPlugin::setup(
-        json_obj, grammar,
-      )?))
-    } else if class == "sudachipy.plugin.oov.MeCabOovProviderPlugin" {
-      Ok(OovProviderPlugin::MecabOovPlugin(MecabOovPlugin::setup(
-        &config.resource_dir,
-        json_obj,
-        grammar,
-      )?))
-    } else {
-      Err(OovProviderPluginGetErr::InvalidClassErr(class.to_string()))
-    }
-  } else {
-    Err(OovProviderPluginGetErr::InvalidFormatErr)
+  match settings {
+    OovProviderPluginSettings::SimpleOovPlugin(settings) => Ok(OovProviderPlugin::SimpleOovPlugin(
+      SimpleOovPlugin::setup(settings, grammar)
+        .map_err(|e| OovProviderPluginGetErr::SimpleOovPluginSetupErr(index, e))?,
+    )),
+    OovProviderPluginSettings::MecabOovPlugin(settings) => Ok(OovProviderPlugin::MecabOovPlugin(
+      MecabOovPlugin::setup(config, settings, grammar)?,
+    )),
   }
 }
 
@@ -98,10 +87,13 @@ pub fn get_oov_provider_plugins(
   grammar: Arc<Mutex<Grammar>>,
 ) -> Result<Vec<OovProviderPlugin>, OovProviderPluginGetErr> {
   let mut plugins = vec![];
-  if let Some(Value::Array(arr)) = config.settings.get("oovProviderPlugin") {
-    for v in arr {
-      plugins.push(get_oov_provider_plugin(config, v, Arc::clone(&grammar))?);
-    }
+  for (index, settings) in config.settings.oov_provider_plugin.iter().enumerate() {
+    plugins.push(get_oov_provider_plugin(
+      config,
+      index,
+      settings,
+      Arc::clone(&grammar),
+    )?);
   }
   Ok(plugins)
 }
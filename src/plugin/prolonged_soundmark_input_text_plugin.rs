@@ -1,8 +1,7 @@
 use std::collections::HashSet;
 
-use serde_json::Value;
-
 use super::input_text_plugin::{InputTextPluginReplaceErr, RewriteInputText};
+use crate::config::settings::ProlongedSoundMarkSettings;
 use crate::utf8_input_text_builder::UTF8InputTextBuilder;
 
 #[derive(Debug)]
@@ -42,24 +41,14 @@ impl RewriteInputText for &ProlongedSoundMarkInputTextPlugin {
 }
 
 impl ProlongedSoundMarkInputTextPlugin {
-  pub fn setup(json_obj: &Value) -> ProlongedSoundMarkInputTextPlugin {
-    let mut psm_set = HashSet::new();
-    if let Some(Value::Array(marks)) = json_obj.get("prolongedSoundMarks") {
-      for mark in marks {
-        if let Value::String(psm) = mark {
-          psm_set.insert(psm.chars().next().unwrap() as u32);
-        }
-      }
-    }
-    let replace_symbol =
-      if let Some(Value::String(replacement_symbol)) = json_obj.get("replacementSymbol") {
-        replacement_symbol.to_string()
-      } else {
-        String::from("ー")
-      };
+  pub fn setup(settings: &ProlongedSoundMarkSettings) -> ProlongedSoundMarkInputTextPlugin {
     ProlongedSoundMarkInputTextPlugin {
-      psm_set,
-      replace_symbol,
+      psm_set: settings
+        .prolonged_sound_marks
+        .iter()
+        .map(|&c| c as u32)
+        .collect(),
+      replace_symbol: settings.replacement_symbol.clone(),
     }
   }
 }
@@ -70,7 +59,6 @@ mod tests {
   use super::*;
   use crate::dictionary_lib::character_category::CharacterCategory;
   use crate::dictionary_lib::grammar::{GetCharacterCategory, SetCharacterCategory};
-  use serde_json::json;
   use std::path::PathBuf;
   use std::str::FromStr;
   use std::sync::{Arc, Mutex};
@@ -108,7 +96,10 @@ mod tests {
   }
 
   fn build_plugin() -> ProlongedSoundMarkInputTextPlugin {
-    ProlongedSoundMarkInputTextPlugin::setup(&json!({"prolongedSoundMarks":["ー", "〜", "〰"]}))
+    ProlongedSoundMarkInputTextPlugin::setup(&ProlongedSoundMarkSettings {
+      prolonged_sound_marks: vec!['ー', '〜', '〰'],
+      replacement_symbol: String::from("ー"),
+    })
   }
 
   #[test]
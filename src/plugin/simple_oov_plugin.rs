@@ -1,9 +1,9 @@
 use std::sync::{Arc, Mutex};
 
-use serde_json::Value;
 use thiserror::Error;
 
 use super::oov_provider_plugin::ProvideOov;
+use crate::config::settings::SimpleOovSettings;
 use crate::dictionary_lib::grammar::GetPartOfSpeech;
 use crate::dictionary_lib::grammar::Grammar;
 use crate::dictionary_lib::word_info::WordInfo;
@@ -19,34 +19,27 @@ pub struct SimpleOovPlugin {
 }
 
 #[derive(Debug, Error)]
-pub enum SimpleOovPluginSetupErr {}
+pub enum SimpleOovPluginSetupErr {
+  #[error("{} is not a part of speech of the system dictionary", .0.join(","))]
+  UnknownPosErr(Vec<String>),
+}
 
 impl SimpleOovPlugin {
   pub fn setup(
-    json_obj: &Value,
+    settings: &SimpleOovSettings,
     grammar: Arc<Mutex<Grammar>>,
   ) -> Result<SimpleOovPlugin, SimpleOovPluginSetupErr> {
-    let left_id = get_u64_by_key(json_obj, "leftId") as u32;
-    let right_id = get_u64_by_key(json_obj, "rightId") as u32;
-    let cost = get_i64_by_key(json_obj, "cost") as i32;
-    let strings: Vec<&str> = json_obj
-      .get("oovPOS")
-      .map(|i| i.as_array())
-      .flatten()
-      .unwrap()
-      .iter()
-      .map(|i| i.as_str().unwrap())
-      .collect();
+    let strings: Vec<&str> = settings.oov_pos.iter().map(|s| s.as_str()).collect();
     let oov_pos_id = grammar
       .lock()
       .unwrap()
       .get_part_of_speech_id(&strings)
-      .map(|i| i as i16)
-      .unwrap_or(-1);
+      .ok_or_else(|| SimpleOovPluginSetupErr::UnknownPosErr(settings.oov_pos.clone()))?
+      as i16;
     Ok(SimpleOovPlugin {
-      left_id,
-      right_id,
-      cost,
+      left_id: settings.left_id as u32,
+      right_id: settings.right_id as u32,
+      cost: settings.cost as i32,
       oov_pos_id,
     })
   }
@@ -83,11 +76,3 @@ impl<T: InputText> ProvideOov<T> for &SimpleOovPlugin {
     }
   }
 }
-
-fn get_u64_by_key(v: &Value, k: &str) -> i64 {
-  v.get(k).map(|i| i.as_u64()).flatten().unwrap() as i64
-}
-
-fn get_i64_by_key(v: &Value, k: &str) -> u64 {
-  v.get(k).map(|i| i.as_u64()).flatten().unwrap() as u64
-}
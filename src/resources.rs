@@ -30,7 +30,17 @@ pub fn write_resources<P: AsRef<Path>>(dir: P) -> Result<(), IOError> {
   Ok(())
 }
 
-const SUDACHI_JSON: &str = r#"
+pub fn get_bundled_resource(name: &str) -> Option<&'static str> {
+  match name {
+    "sudachi.json" => Some(SUDACHI_JSON),
+    "char.def" => Some(CHAR_DEF),
+    "rewrite.def" => Some(REWRITE_DEF),
+    "unk.def" => Some(UNK_DEF),
+    _ => None,
+  }
+}
+
+pub const SUDACHI_JSON: &str = r#"
 {
   "characterDefinitionFile" : "char.def",
   "inputTextPlugin" : [
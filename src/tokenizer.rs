@@ -65,7 +65,17 @@ impl Tokenizer {
       path_rewrite_plugins,
     }
   }
-  fn build_lattice(&self, input: &UTF8InputText) -> Lattice {
+  /// `text` rewritten by the input text plugins, or `None` if one of them fails
+  pub(crate) fn build_input(&self, text: &str) -> Option<UTF8InputText> {
+    let mut builder = UTF8InputTextBuilder::new(text, Arc::clone(&self.grammar));
+    for plugin in self.input_text_plugins.iter() {
+      if plugin.rewrite(&mut builder).is_err() {
+        return None;
+      }
+    }
+    Some(builder.build())
+  }
+  pub(crate) fn build_lattice(&self, input: &UTF8InputText) -> Lattice {
     let mut lattice = Lattice::new(Arc::clone(&self.grammar));
     let bytes = input.get_byte_text();
     let len = bytes.len();
@@ -80,6 +90,10 @@ impl Tokenizer {
         if end < len && !input.can_bow(end) {
           continue;
         }
+        // words of connection id -1 are only used in split info
+        if lexicon_set.get_left_id(word_id) < 0 || lexicon_set.get_right_id(word_id) < 0 {
+          continue;
+        }
         has_words = true;
         let node = LatticeNode::new(
           Some(Arc::clone(&self.lexicon_set)),
@@ -175,13 +189,7 @@ impl CanTokenize for Tokenizer {
     }
 
     let mode = mode.unwrap_or(SplitMode::C);
-    let mut builder = UTF8InputTextBuilder::new(text.as_ref(), Arc::clone(&self.grammar));
-    for plugin in self.input_text_plugins.iter() {
-      if plugin.rewrite(&mut builder).is_err() {
-        return None;
-      }
-    }
-    let input = builder.build();
+    let input = self.build_input(text.as_ref())?;
     info!("=== Input dump:\n{}", input.get_text());
 
     let mut lattice = self.build_lattice(&input);